@@ -5,10 +5,10 @@ extern crate yagl;
 
 use anyhow::Result;
 use yagl::AppContext;
-use yagl::DeviceId;
 use yagl::GamepadButton;
 use yagl::Instance;
 use yagl::Key;
+use yagl::KeyInfo;
 use yagl::RenderContext;
 use yagl::SpriteBatch;
 use yagl::TextGrid;
@@ -19,7 +19,7 @@ struct Game {
 
 impl yagl::Game for Game {
     fn update(&mut self, actx: &mut AppContext) -> Result<()> {
-        self.text.update();
+        self.text.update(actx);
         Ok(())
     }
 
@@ -35,7 +35,7 @@ impl yagl::Game for Game {
         Ok(())
     }
 
-    fn key_pressed(&mut self, actx: &mut AppContext, key: Key) -> Result<()> {
+    fn key_pressed(&mut self, actx: &mut AppContext, key: Key, info: KeyInfo) -> Result<()> {
         match key {
             Key::Escape => {
                 actx.exit();
@@ -43,21 +43,15 @@ impl yagl::Game for Game {
             Key::Backspace => {
                 self.text.backspace(actx)?;
             }
-            Key::Right => {
-                self.text.move_right(actx)?;
-            }
-            Key::Left => {
-                self.text.move_left(actx)?;
-            }
-            Key::Up => {
-                self.text.move_up(actx)?;
-            }
-            Key::Down => {
-                self.text.move_down(actx)?;
-            }
             Key::Return => {
                 self.text.enter(actx)?;
             }
+            // actions only fire when first pressed,
+            // so holding an arrow key repeats here
+            Key::Left if info.is_repeat => self.text.move_left(actx)?,
+            Key::Right if info.is_repeat => self.text.move_right(actx)?,
+            Key::Up if info.is_repeat => self.text.move_up(actx)?,
+            Key::Down if info.is_repeat => self.text.move_down(actx)?,
             _ => {}
         }
         Ok(())
@@ -68,34 +62,33 @@ impl yagl::Game for Game {
         Ok(())
     }
 
-    fn gamepad_button_pressed(
-        &mut self,
-        actx: &mut AppContext,
-        dev: DeviceId,
-        button: GamepadButton,
-    ) -> Result<()> {
-        match button {
-            GamepadButton::DPadLeft => {
-                self.text.move_left(actx)?;
-            }
-            GamepadButton::DPadRight => {
-                self.text.move_right(actx)?;
-            }
-            GamepadButton::DPadDown => {
-                self.text.move_down(actx)?;
-            }
-            GamepadButton::DPadUp => {
-                self.text.move_up(actx)?;
-            }
+    fn action_pressed(&mut self, actx: &mut AppContext, action: &str) -> Result<()> {
+        match action {
+            "left" => self.text.move_left(actx)?,
+            "right" => self.text.move_right(actx)?,
+            "up" => self.text.move_up(actx)?,
+            "down" => self.text.move_down(actx)?,
             _ => {}
         }
         Ok(())
     }
 }
 
-fn main() {
+fn main() -> Result<()> {
     yagl::run(|actx| {
-        let text = Text::new(actx, "").unwrap();
+        let actions = actx.actions_mut();
+        for (action, key, button) in &[
+            ("left", Key::Left, GamepadButton::DPadLeft),
+            ("right", Key::Right, GamepadButton::DPadRight),
+            ("up", Key::Up, GamepadButton::DPadUp),
+            ("down", Key::Down, GamepadButton::DPadDown),
+        ] {
+            actions.add_button(action);
+            actions.bind(action, *key)?;
+            actions.bind(action, *button)?;
+        }
+
+        let text = Text::new(actx, "")?;
         println!("scale = {:?}", actx.scale());
         Ok(Game { text })
     })
@@ -126,7 +119,6 @@ impl Cursor {
 }
 
 struct Text {
-    start: std::time::SystemTime,
     cursor: Cursor,
     grid: TextGrid,
     lines: Vec<Vec<char>>,
@@ -142,7 +134,6 @@ impl Text {
         };
         cursor.sprite.add(Instance::builder().build());
         let mut text = Text {
-            start: std::time::SystemTime::now(),
             cursor,
             grid,
             lines,
@@ -152,8 +143,8 @@ impl Text {
         Ok(text)
     }
 
-    fn update(&mut self) {
-        if self.start.elapsed().unwrap().as_secs_f64().fract() < 0.5 {
+    fn update(&mut self, actx: &AppContext) {
+        if actx.time().fract() < 0.5 {
             self.cursor.set_visible(false);
         } else {
             self.cursor.set_visible(true);
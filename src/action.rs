@@ -0,0 +1,489 @@
+use crate::anyhow::Result;
+use crate::Axis;
+use crate::GamepadButton;
+use crate::InputState;
+use crate::Key;
+use crate::MouseButton;
+use std::collections::BTreeMap;
+
+/// An axis action whose value moves by less than this
+/// is not considered to have changed
+const VALUE_EPSILON: f32 = 1e-4;
+
+/// Maps physical inputs to named, game specific actions
+///
+/// Rather than handling e.g. `Key::Left` and `GamepadButton::DPadLeft`
+/// separately, a game can add a "left" action, bind both inputs to it,
+/// and then handle `Game::action_pressed` instead.
+///
+/// There are two kinds of actions:
+///
+///   * button actions are either pressed or not, and are reported
+///     with `Game::action_pressed` and `Game::action_released`
+///   * axis actions have a value between -1 and 1, and are reported
+///     with `Game::action_value`
+///
+/// Any input can be bound to either kind of action. Each binding has
+/// a scale that its input is multiplied by, so that e.g. `Key::Left`
+/// and `Key::Right` can be bound to the same axis action with scales
+/// of -1 and 1.
+///
+/// The action map can be retrieved and modified at any time with
+/// `AppContext::actions` and `AppContext::actions_mut`, so that
+/// bindings can be changed at runtime (e.g. from a rebinding menu).
+/// With the `serde` feature enabled, it can also be serialized, so that
+/// bindings can be saved to disk.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ActionMap {
+    actions: BTreeMap<String, Action>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Action {
+    kind: ActionKind,
+    bindings: Vec<Binding>,
+
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pressed: bool,
+
+    #[cfg_attr(feature = "serde", serde(skip))]
+    value: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ActionKind {
+    Button,
+    Axis,
+}
+
+/// A physical input that can be bound to an action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Input {
+    /// A key, by what it means in the current keyboard layout
+    Key(Key),
+
+    /// A key, by its position on the keyboard (see `KeyInfo::scancode`)
+    ///
+    /// Useful for e.g. WASD movement that should stay in the same place
+    /// on AZERTY keyboards. Use `InputState::last_label_at` to find out what
+    /// the key is labelled with.
+    Scancode(u32),
+
+    MouseButton(MouseButton),
+
+    /// A button on any gamepad
+    GamepadButton(GamepadButton),
+
+    /// An axis on any gamepad
+    ///
+    /// If more than one gamepad is connected, the one furthest
+    /// from its resting position is used
+    Axis(Axis),
+}
+
+impl From<Key> for Input {
+    fn from(key: Key) -> Input {
+        Input::Key(key)
+    }
+}
+
+impl From<MouseButton> for Input {
+    fn from(button: MouseButton) -> Input {
+        Input::MouseButton(button)
+    }
+}
+
+impl From<GamepadButton> for Input {
+    fn from(button: GamepadButton) -> Input {
+        Input::GamepadButton(button)
+    }
+}
+
+impl From<Axis> for Input {
+    fn from(axis: Axis) -> Input {
+        Input::Axis(axis)
+    }
+}
+
+/// An input bound to an action, along with the factor its value
+/// is multiplied by
+///
+/// Buttons have a value of 1 when held down, and 0 otherwise,
+/// except for analog gamepad buttons, whose value is
+/// anywhere in between.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Binding {
+    pub input: Input,
+    pub scale: f32,
+}
+
+/// A change in the state of an action, to be reported to the Game
+pub(crate) enum ActionEvent {
+    Pressed(String),
+    Released(String),
+    Value(String, f32),
+}
+
+impl ActionMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an action that is either pressed or not
+    ///
+    /// If an action with the same name already exists, it is replaced
+    pub fn add_button(&mut self, action: &str) {
+        self.add(action, ActionKind::Button)
+    }
+
+    /// Adds an action with a value between -1 and 1
+    ///
+    /// If an action with the same name already exists, it is replaced
+    pub fn add_axis(&mut self, action: &str) {
+        self.add(action, ActionKind::Axis)
+    }
+
+    fn add(&mut self, action: &str, kind: ActionKind) {
+        self.actions.insert(
+            action.to_owned(),
+            Action {
+                kind,
+                bindings: Vec::new(),
+                pressed: false,
+                value: 0.0,
+            },
+        );
+    }
+
+    /// Removes the given action along with all of its bindings
+    pub fn remove(&mut self, action: &str) {
+        self.actions.remove(action);
+    }
+
+    /// Returns the names of all actions
+    pub fn actions(&self) -> impl Iterator<Item = &str> {
+        self.actions.keys().map(|name| name.as_str())
+    }
+
+    /// Returns the kind of the given action, if it exists
+    pub fn kind(&self, action: &str) -> Option<ActionKind> {
+        self.actions.get(action).map(|action| action.kind)
+    }
+
+    /// Binds the given input to an action
+    pub fn bind<I: Into<Input>>(&mut self, action: &str, input: I) -> Result<()> {
+        self.bind_scaled(action, input, 1.0)
+    }
+
+    /// Binds the given input to an action, with its value
+    /// multiplied by `scale`
+    ///
+    /// For button actions, the action is pressed while the scaled
+    /// value of any of its bindings is at least 0.5. So e.g. binding
+    /// `Axis::LeftStickX` with a scale of -1 to a "left" button action
+    /// would cause it to be pressed when the stick is pushed left
+    pub fn bind_scaled<I: Into<Input>>(
+        &mut self,
+        action: &str,
+        input: I,
+        scale: f32,
+    ) -> Result<()> {
+        self.get_mut(action)?.bindings.push(Binding {
+            input: input.into(),
+            scale,
+        });
+        Ok(())
+    }
+
+    /// Removes all bindings for the given input from an action
+    pub fn unbind<I: Into<Input>>(&mut self, action: &str, input: I) -> Result<()> {
+        let input = input.into();
+        self.get_mut(action)?
+            .bindings
+            .retain(|binding| binding.input != input);
+        Ok(())
+    }
+
+    /// Removes all bindings from an action
+    pub fn clear(&mut self, action: &str) -> Result<()> {
+        self.get_mut(action)?.bindings.clear();
+        Ok(())
+    }
+
+    /// Returns all bindings for the given action
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        self.actions
+            .get(action)
+            .map(|action| &action.bindings[..])
+            .unwrap_or(&[])
+    }
+
+    /// Returns true if the given button action is currently pressed
+    pub fn is_pressed(&self, action: &str) -> bool {
+        self.actions
+            .get(action)
+            .map(|action| action.pressed)
+            .unwrap_or(false)
+    }
+
+    /// Returns the current value of the given axis action
+    pub fn value(&self, action: &str) -> f32 {
+        self.actions
+            .get(action)
+            .map(|action| action.value)
+            .unwrap_or(0.0)
+    }
+
+    fn get_mut(&mut self, action: &str) -> Result<&mut Action> {
+        match self.actions.get_mut(action) {
+            Some(action) => Ok(action),
+            None => Err(anyhow::anyhow!("Action {:?} does not exist", action)),
+        }
+    }
+
+    /// Recomputes the state of every action from the given InputState,
+    /// and returns the changes
+    pub(crate) fn update(&mut self, input: &InputState) -> Vec<ActionEvent> {
+        let mut events = Vec::new();
+        for (name, action) in &mut self.actions {
+            match action.kind {
+                ActionKind::Button => {
+                    let pressed = action
+                        .bindings
+                        .iter()
+                        .any(|binding| binding_value(input, binding) >= 0.5);
+                    if pressed != action.pressed {
+                        action.pressed = pressed;
+                        events.push(if pressed {
+                            ActionEvent::Pressed(name.clone())
+                        } else {
+                            ActionEvent::Released(name.clone())
+                        });
+                    }
+                }
+                ActionKind::Axis => {
+                    let value: f32 = action
+                        .bindings
+                        .iter()
+                        .map(|binding| binding_value(input, binding))
+                        .sum();
+                    let value = value.clamp(-1.0, 1.0);
+                    if (value - action.value).abs() > VALUE_EPSILON {
+                        action.value = value;
+                        events.push(ActionEvent::Value(name.clone(), value));
+                    }
+                }
+            }
+        }
+        events
+    }
+}
+
+fn binding_value(input: &InputState, binding: &Binding) -> f32 {
+    let value = match binding.input {
+        Input::Key(key) => button_value(input.is_key_down(key)),
+        Input::Scancode(scancode) => button_value(input.is_scancode_down(scancode)),
+        Input::MouseButton(button) => button_value(input.is_mouse_button_down(button)),
+        Input::GamepadButton(button) => input
+            .gamepads()
+            .map(|dev| input.gamepad_button_value(dev, button))
+            .fold(0.0, f32::max),
+        Input::Axis(axis) => input
+            .gamepads()
+            .map(|dev| input.gamepad_axis(dev, axis))
+            .fold(0.0, |a: f32, b: f32| if b.abs() > a.abs() { b } else { a }),
+    };
+    value * binding.scale
+}
+
+fn button_value(down: bool) -> f32 {
+    if down {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DeviceId;
+    use crate::InputEvent;
+    use crate::KeyInfo;
+
+    fn events(actions: &mut ActionMap, input: &InputState) -> Vec<String> {
+        actions
+            .update(input)
+            .into_iter()
+            .map(|event| match event {
+                ActionEvent::Pressed(name) => format!("pressed {}", name),
+                ActionEvent::Released(name) => format!("released {}", name),
+                ActionEvent::Value(name, value) => format!("{} = {}", name, value),
+            })
+            .collect()
+    }
+
+    fn key(input: &mut InputState, key: Key, scancode: u32, down: bool) {
+        let info = KeyInfo {
+            scancode,
+            ..KeyInfo::default()
+        };
+        input.handle(&if down {
+            InputEvent::KeyPressed(key, info)
+        } else {
+            InputEvent::KeyReleased(key, info)
+        });
+    }
+
+    #[test]
+    fn buttons_are_reported_once_per_transition() {
+        let mut actions = ActionMap::new();
+        let mut input = InputState::default();
+        actions.add_button("jump");
+        actions.bind("jump", Key::Space).unwrap();
+        actions.bind("jump", GamepadButton::South).unwrap();
+
+        key(&mut input, Key::Space, 57, true);
+        assert_eq!(events(&mut actions, &input), ["pressed jump"]);
+        assert!(actions.is_pressed("jump"));
+
+        // a second binding held down at the same time changes nothing
+        let dev = DeviceId::synthetic(0);
+        input.handle(&InputEvent::GamepadButtonPressed(dev, GamepadButton::South));
+        key(&mut input, Key::Space, 57, false);
+        assert!(events(&mut actions, &input).is_empty());
+
+        input.handle(&InputEvent::GamepadButtonReleased(
+            dev,
+            GamepadButton::South,
+        ));
+        assert_eq!(events(&mut actions, &input), ["released jump"]);
+        assert!(!actions.is_pressed("jump"));
+    }
+
+    #[test]
+    fn buttons_are_pressed_from_half_way() {
+        let mut actions = ActionMap::new();
+        let mut input = InputState::default();
+        let dev = DeviceId::synthetic(0);
+        actions.add_button("fire");
+        actions.bind("fire", GamepadButton::RightTrigger2).unwrap();
+        actions.bind_scaled("fire", Axis::LeftStickX, -2.0).unwrap();
+
+        let trigger = |input: &mut InputState, value| {
+            input.handle(&InputEvent::GamepadButtonChanged(
+                dev,
+                GamepadButton::RightTrigger2,
+                value,
+            ))
+        };
+        trigger(&mut input, 0.49);
+        assert!(events(&mut actions, &input).is_empty());
+        trigger(&mut input, 0.5);
+        assert_eq!(events(&mut actions, &input), ["pressed fire"]);
+        trigger(&mut input, 0.0);
+        assert_eq!(events(&mut actions, &input), ["released fire"]);
+
+        // scaled by -2, so a quarter of the way left is enough
+        input.handle(&InputEvent::GamepadAxisChanged(
+            dev,
+            Axis::LeftStickX,
+            -0.25,
+        ));
+        assert_eq!(events(&mut actions, &input), ["pressed fire"]);
+    }
+
+    #[test]
+    fn axes_combine_opposite_keys() {
+        let mut actions = ActionMap::new();
+        let mut input = InputState::default();
+        actions.add_axis("move");
+        actions.bind_scaled("move", Key::Left, -1.0).unwrap();
+        actions.bind_scaled("move", Key::Right, 1.0).unwrap();
+        actions.bind("move", Axis::LeftStickX).unwrap();
+
+        key(&mut input, Key::Left, 105, true);
+        assert_eq!(events(&mut actions, &input), ["move = -1"]);
+        key(&mut input, Key::Right, 106, true);
+        assert_eq!(events(&mut actions, &input), ["move = 0"]);
+        key(&mut input, Key::Left, 105, false);
+        assert_eq!(events(&mut actions, &input), ["move = 1"]);
+        assert_eq!(actions.value("move"), 1.0);
+
+        // clamped to [-1, 1]
+        let dev = DeviceId::synthetic(0);
+        input.handle(&InputEvent::GamepadAxisChanged(dev, Axis::LeftStickX, 0.5));
+        assert!(events(&mut actions, &input).is_empty());
+        input.handle(&InputEvent::GamepadAxisChanged(dev, Axis::LeftStickX, -0.5));
+        assert_eq!(events(&mut actions, &input), ["move = 0.5"]);
+    }
+
+    #[test]
+    fn scancodes_ignore_the_layout() {
+        let mut actions = ActionMap::new();
+        let mut input = InputState::default();
+        actions.add_button("up");
+        actions.bind("up", Input::Scancode(17)).unwrap();
+
+        // e.g. 'W' on QWERTY is 'Z' on AZERTY
+        key(&mut input, Key::Z, 17, true);
+        assert_eq!(events(&mut actions, &input), ["pressed up"]);
+        key(&mut input, Key::Z, 17, false);
+        key(&mut input, Key::W, 44, true);
+        assert_eq!(events(&mut actions, &input), ["released up"]);
+    }
+
+    #[test]
+    fn bindings_can_change_at_runtime() {
+        let mut actions = ActionMap::new();
+        let mut input = InputState::default();
+        actions.add_button("jump");
+        actions.bind("jump", Key::Space).unwrap();
+        key(&mut input, Key::Space, 57, true);
+        assert_eq!(events(&mut actions, &input), ["pressed jump"]);
+
+        actions.unbind("jump", Key::Space).unwrap();
+        actions.bind("jump", Key::Up).unwrap();
+        assert_eq!(
+            actions.bindings("jump"),
+            [Binding {
+                input: Input::Key(Key::Up),
+                scale: 1.0
+            }]
+        );
+        assert_eq!(events(&mut actions, &input), ["released jump"]);
+        key(&mut input, Key::Up, 103, true);
+        assert_eq!(events(&mut actions, &input), ["pressed jump"]);
+
+        actions.clear("jump").unwrap();
+        assert!(actions.bindings("jump").is_empty());
+        assert!(actions.bind("missing", Key::Up).is_err());
+        actions.remove("jump");
+        assert_eq!(actions.actions().count(), 0);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let mut actions = ActionMap::new();
+        actions.add_button("jump");
+        actions.bind("jump", Key::Space).unwrap();
+        actions.bind("jump", Input::Scancode(57)).unwrap();
+        actions.bind("jump", GamepadButton::South).unwrap();
+        actions.bind("jump", MouseButton::Left).unwrap();
+        actions.add_axis("move");
+        actions.bind_scaled("move", Axis::LeftStickX, -0.5).unwrap();
+
+        let json = serde_json::to_string(&actions).unwrap();
+        let parsed: ActionMap = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.kind("jump"), Some(ActionKind::Button));
+        assert_eq!(parsed.kind("move"), Some(ActionKind::Axis));
+        assert_eq!(parsed.bindings("jump"), actions.bindings("jump"));
+        assert_eq!(parsed.bindings("move"), actions.bindings("move"));
+    }
+}
@@ -0,0 +1,563 @@
+use crate::anyhow::Result;
+use crate::SpriteBatch;
+#[cfg(feature = "aseprite")]
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+/// Frames shorter than this are treated as being this long,
+/// so that an animation can't get stuck skipping over frames forever
+const MIN_FRAME_DURATION: f32 = 1e-3;
+
+/// What happens when an animation reaches its last frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PlayMode {
+    /// Stop on the last frame
+    Once,
+
+    /// Start over from the first frame
+    Loop,
+
+    /// Play the frames backwards, then forwards again, and so on
+    PingPong,
+}
+
+/// A single frame of an animation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// The [upper_left, lower_right] corners of the part of the
+    /// sprite sheet to show, as fractions of the sheet's size
+    pub src: [[f32; 2]; 2],
+
+    /// How long the frame is shown, in seconds
+    pub duration: f32,
+
+    /// If set, `Animator::update` reports this event when
+    /// the frame is reached (e.g. "footstep")
+    pub event: Option<String>,
+}
+
+/// A sequence of frames from a sprite sheet
+///
+/// An animation only describes the frames; playing it is done with an
+/// `Animator`, so that a single animation can be shared by many sprites.
+#[derive(Debug, Clone)]
+pub struct Animation {
+    frames: Vec<Frame>,
+    mode: PlayMode,
+}
+
+impl Animation {
+    pub fn new(mode: PlayMode) -> Self {
+        Self {
+            frames: Vec::new(),
+            mode,
+        }
+    }
+
+    /// Creates an animation from a sprite sheet laid out as a grid
+    /// of equally sized cells
+    ///
+    /// `grid` is the [ncols, nrows] of the sheet, and `cells` are the
+    /// indices of the cells to use, counted left to right, top to bottom.
+    /// Every frame is shown for `duration` seconds
+    pub fn from_grid<I>(grid: [u32; 2], cells: I, duration: f32, mode: PlayMode) -> Result<Self>
+    where
+        I: IntoIterator<Item = u32>,
+    {
+        let [ncols, nrows] = grid;
+        if ncols == 0 || nrows == 0 {
+            anyhow::bail!("A {}x{} grid has no cells", ncols, nrows);
+        }
+        let (width, height) = (1.0 / ncols as f32, 1.0 / nrows as f32);
+        let mut animation = Self::new(mode);
+        for cell in cells {
+            if cell / ncols >= nrows {
+                anyhow::bail!(
+                    "Cell {} is out of range (a {}x{} grid has {} cells)",
+                    cell,
+                    ncols,
+                    nrows,
+                    ncols * nrows
+                );
+            }
+            let (col, row) = ((cell % ncols) as f32, (cell / ncols) as f32);
+            animation.push(
+                [
+                    [col * width, row * height],
+                    [(col + 1.0) * width, (row + 1.0) * height],
+                ],
+                duration,
+            );
+        }
+        Ok(animation)
+    }
+
+    /// Parses the JSON data exported by Aseprite
+    /// (with either the "Hash" or "Array" layout)
+    ///
+    /// Returns an animation for each tag, keyed by the tag's name.
+    /// If there are no tags, the map contains a single animation with
+    /// every frame, keyed by the empty string.
+    ///
+    /// Tags play with `PlayMode::PingPong` if their direction is
+    /// "pingpong", and with `PlayMode::Loop` otherwise.
+    #[cfg(feature = "aseprite")]
+    pub fn from_aseprite_json(json: &str) -> Result<BTreeMap<String, Animation>> {
+        aseprite::parse(json)
+    }
+
+    pub fn mode(&self) -> PlayMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: PlayMode) {
+        self.mode = mode;
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// Adds a frame to the end of the animation
+    pub fn push(&mut self, src: [[f32; 2]; 2], duration: f32) {
+        self.frames.push(Frame {
+            src,
+            duration,
+            event: None,
+        });
+    }
+
+    /// Sets the event reported when the given frame is reached
+    pub fn set_event(&mut self, frame: usize, event: &str) -> Result<()> {
+        match self.frames.get_mut(frame) {
+            Some(frame) => {
+                frame.event = Some(event.to_owned());
+                Ok(())
+            }
+            None => Err(anyhow::anyhow!(
+                "Frame {} is out of range (the animation has {} frames)",
+                frame,
+                self.frames.len()
+            )),
+        }
+    }
+
+    /// Returns the total length of one pass through the frames,
+    /// in seconds
+    pub fn duration(&self) -> f32 {
+        self.frames.iter().map(|frame| frame.duration).sum()
+    }
+}
+
+/// Plays an `Animation`, keeping track of the current frame
+#[derive(Debug, Clone)]
+pub struct Animator {
+    animation: Rc<Animation>,
+    frame: usize,
+    elapsed: f32,
+    speed: f32,
+    backwards: bool,
+    started: bool,
+    finished: bool,
+}
+
+impl Animator {
+    pub fn new(animation: Rc<Animation>) -> Self {
+        Self {
+            animation,
+            frame: 0,
+            elapsed: 0.0,
+            speed: 1.0,
+            backwards: false,
+            started: false,
+            finished: false,
+        }
+    }
+
+    pub fn animation(&self) -> &Rc<Animation> {
+        &self.animation
+    }
+
+    /// Switches to a different animation, starting from its first frame
+    ///
+    /// If it is already the current animation, this does nothing
+    pub fn play(&mut self, animation: Rc<Animation>) {
+        if !Rc::ptr_eq(&self.animation, &animation) {
+            self.animation = animation;
+            self.restart();
+        }
+    }
+
+    /// Goes back to the first frame of the current animation
+    pub fn restart(&mut self) {
+        self.frame = 0;
+        self.elapsed = 0.0;
+        self.backwards = false;
+        self.started = false;
+        self.finished = false;
+    }
+
+    /// Sets how fast the animation plays, where e.g. 2 is twice as fast
+    ///
+    /// By default, set to 1
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0.0);
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Returns the index of the current frame
+    pub fn frame_index(&self) -> usize {
+        self.frame
+    }
+
+    /// Returns the current frame, or None if the animation is empty
+    pub fn frame(&self) -> Option<&Frame> {
+        self.animation.frames.get(self.frame)
+    }
+
+    /// Returns true if a `PlayMode::Once` animation has reached the
+    /// end of its last frame
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Advances the animation by `delta` seconds, and returns the events
+    /// of every frame that was reached, in order
+    ///
+    /// Should be called once per update, usually with `AppContext::delta`
+    pub fn update(&mut self, delta: f64) -> Vec<String> {
+        let mut events = Vec::new();
+        let nframes = self.animation.frames.len();
+        if nframes == 0 {
+            return events;
+        }
+        if !self.started {
+            self.started = true;
+            self.push_event(&mut events);
+        }
+        if self.finished {
+            return events;
+        }
+        self.elapsed += delta as f32 * self.speed;
+        loop {
+            let duration = self.animation.frames[self.frame]
+                .duration
+                .max(MIN_FRAME_DURATION);
+            if self.elapsed < duration {
+                break;
+            }
+            match self.next_frame(nframes) {
+                Some(frame) => {
+                    self.elapsed -= duration;
+                    self.frame = frame;
+                    self.push_event(&mut events);
+                }
+                None => {
+                    self.elapsed = duration;
+                    self.finished = true;
+                    break;
+                }
+            }
+        }
+        events
+    }
+
+    /// Sets the source rectangle of an instance in the batch
+    /// to the current frame
+    pub fn apply(&self, batch: &mut SpriteBatch, index: usize) {
+        if let Some(frame) = self.frame() {
+            batch.get_mut(index).set_src(frame.src);
+        }
+    }
+
+    fn next_frame(&mut self, nframes: usize) -> Option<usize> {
+        match self.animation.mode {
+            PlayMode::Once => Some(self.frame + 1).filter(|frame| *frame < nframes),
+            PlayMode::Loop => Some((self.frame + 1) % nframes),
+            PlayMode::PingPong => {
+                if nframes == 1 {
+                    return Some(0);
+                }
+                if self.backwards && self.frame == 0 {
+                    self.backwards = false;
+                } else if !self.backwards && self.frame + 1 == nframes {
+                    self.backwards = true;
+                }
+                Some(if self.backwards {
+                    self.frame - 1
+                } else {
+                    self.frame + 1
+                })
+            }
+        }
+    }
+
+    fn push_event(&self, events: &mut Vec<String>) {
+        if let Some(event) = &self.animation.frames[self.frame].event {
+            events.push(event.clone());
+        }
+    }
+}
+
+#[cfg(feature = "aseprite")]
+mod aseprite {
+    use super::Animation;
+    use super::PlayMode;
+    use crate::anyhow::Result;
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+
+    #[derive(Deserialize)]
+    struct File {
+        frames: Frames,
+        meta: Meta,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Frames {
+        Array(Vec<AsepriteFrame>),
+        Hash(serde_json::Map<String, serde_json::Value>),
+    }
+
+    #[derive(Deserialize)]
+    struct AsepriteFrame {
+        frame: Rect,
+
+        /// In milliseconds
+        duration: f32,
+    }
+
+    #[derive(Deserialize, Clone, Copy)]
+    struct Rect {
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+    }
+
+    #[derive(Deserialize, Clone, Copy)]
+    struct Size {
+        w: f32,
+        h: f32,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Meta {
+        size: Size,
+        #[serde(default)]
+        frame_tags: Vec<Tag>,
+    }
+
+    #[derive(Deserialize)]
+    struct Tag {
+        name: String,
+        from: usize,
+        to: usize,
+        direction: String,
+    }
+
+    pub(super) fn parse(json: &str) -> Result<BTreeMap<String, Animation>> {
+        let file: File = serde_json::from_str(json)?;
+        let frames = match file.frames {
+            Frames::Array(frames) => frames,
+            Frames::Hash(map) => map
+                .into_iter()
+                .map(|(_, value)| serde_json::from_value(value))
+                .collect::<std::result::Result<_, _>>()?,
+        };
+        let Size { w, h } = file.meta.size;
+        let build =
+            |indices: &mut dyn Iterator<Item = usize>, mode: PlayMode| -> Result<Animation> {
+                let mut animation = Animation::new(mode);
+                for i in indices {
+                    let frame = match frames.get(i) {
+                        Some(frame) => frame,
+                        None => anyhow::bail!("Frame {} does not exist", i),
+                    };
+                    let Rect { x, y, w: fw, h: fh } = frame.frame;
+                    animation.push(
+                        [[x / w, y / h], [(x + fw) / w, (y + fh) / h]],
+                        frame.duration / 1000.0,
+                    );
+                }
+                Ok(animation)
+            };
+
+        let mut animations = BTreeMap::new();
+        if file.meta.frame_tags.is_empty() {
+            animations.insert(
+                String::new(),
+                build(&mut (0..frames.len()), PlayMode::Loop)?,
+            );
+        }
+        for tag in &file.meta.frame_tags {
+            let mut range = tag.from..=tag.to;
+            let animation = match tag.direction.as_str() {
+                "reverse" => build(&mut range.rev(), PlayMode::Loop)?,
+                "pingpong" => build(&mut range, PlayMode::PingPong)?,
+                _ => build(&mut range, PlayMode::Loop)?,
+            };
+            animations.insert(tag.name.clone(), animation);
+        }
+        Ok(animations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn animator(nframes: u32, mode: PlayMode) -> Animator {
+        let mut animation = Animation::from_grid([nframes, 1], 0..nframes, 0.1, mode).unwrap();
+        for i in 0..nframes as usize {
+            animation.set_event(i, &i.to_string()).unwrap();
+        }
+        Animator::new(Rc::new(animation))
+    }
+
+    /// Advances by `steps` updates of 0.05 seconds (half a frame each),
+    /// and returns the frame index after each one
+    fn frames(animator: &mut Animator, steps: usize) -> Vec<usize> {
+        (0..steps)
+            .map(|_| {
+                animator.update(0.05);
+                animator.frame_index()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn frames_advance_after_their_duration() {
+        let mut animator = animator(3, PlayMode::Loop);
+        assert_eq!(frames(&mut animator, 6), vec![0, 1, 1, 2, 2, 0]);
+    }
+
+    #[test]
+    fn loop_reports_events_again_on_each_pass() {
+        let mut animator = animator(2, PlayMode::Loop);
+        assert_eq!(animator.update(0.0), vec!["0"]);
+        assert_eq!(animator.update(0.1), vec!["1"]);
+        assert_eq!(animator.update(0.1), vec!["0"]);
+        assert_eq!(animator.update(0.25), vec!["1", "0"]);
+    }
+
+    #[test]
+    fn once_stops_on_the_last_frame() {
+        let mut animator = animator(2, PlayMode::Once);
+        animator.update(0.15);
+        assert_eq!(animator.frame_index(), 1);
+        assert!(!animator.is_finished());
+        assert!(animator.update(10.0).is_empty());
+        assert_eq!(animator.frame_index(), 1);
+        assert!(animator.is_finished());
+
+        animator.restart();
+        assert_eq!(animator.frame_index(), 0);
+        assert!(!animator.is_finished());
+    }
+
+    #[test]
+    fn ping_pong_reverses_at_either_end() {
+        let mut animator = animator(3, PlayMode::PingPong);
+        let indices: Vec<_> = (0..6)
+            .map(|_| {
+                animator.update(0.1);
+                animator.frame_index()
+            })
+            .collect();
+        assert_eq!(indices, vec![1, 2, 1, 0, 1, 2]);
+    }
+
+    #[test]
+    fn speed_scales_time() {
+        let mut animator = animator(4, PlayMode::Loop);
+        animator.set_speed(2.0);
+        animator.update(0.1);
+        assert_eq!(animator.frame_index(), 2);
+    }
+
+    #[test]
+    fn grid_cells_cover_the_sheet() {
+        let animation = Animation::from_grid([2, 2], vec![3], 1.0, PlayMode::Once).unwrap();
+        assert_eq!(animation.frames()[0].src, [[0.5, 0.5], [1.0, 1.0]]);
+        assert_eq!(animation.duration(), 1.0);
+    }
+
+    #[test]
+    fn grids_must_have_the_cells_used() {
+        assert!(Animation::from_grid([0, 1], vec![0], 1.0, PlayMode::Once).is_err());
+        assert!(Animation::from_grid([2, 0], vec![0], 1.0, PlayMode::Once).is_err());
+        assert!(Animation::from_grid([2, 2], vec![4], 1.0, PlayMode::Once).is_err());
+        assert!(Animation::from_grid([2, 2], vec![], 1.0, PlayMode::Once).is_ok());
+    }
+
+    #[cfg(feature = "aseprite")]
+    const ASEPRITE_META: &str = r#"{
+        "size": { "w": 64, "h": 16 },
+        "frameTags": [
+            { "name": "walk", "from": 1, "to": 2, "direction": "pingpong" },
+            { "name": "back", "from": 0, "to": 1, "direction": "reverse" }
+        ]
+    }"#;
+
+    #[cfg(feature = "aseprite")]
+    fn check_aseprite(frames: &str) {
+        let json = format!(r#"{{ "frames": {}, "meta": {} }}"#, frames, ASEPRITE_META);
+        let animations = Animation::from_aseprite_json(&json).unwrap();
+        assert_eq!(animations.keys().collect::<Vec<_>>(), ["back", "walk"]);
+
+        let walk = &animations["walk"];
+        assert_eq!(walk.mode(), PlayMode::PingPong);
+        let srcs: Vec<_> = walk.frames().iter().map(|frame| frame.src).collect();
+        assert_eq!(srcs, [[[0.25, 0.0], [0.5, 1.0]], [[0.5, 0.0], [0.75, 1.0]]]);
+        assert_eq!(walk.frames()[0].duration, 0.1);
+        assert_eq!(walk.frames()[1].duration, 0.2);
+
+        let back = &animations["back"];
+        assert_eq!(back.mode(), PlayMode::Loop);
+        assert_eq!(back.frames()[0].src, [[0.25, 0.0], [0.5, 1.0]]);
+        assert_eq!(back.frames()[1].src, [[0.0, 0.0], [0.25, 1.0]]);
+    }
+
+    #[cfg(feature = "aseprite")]
+    #[test]
+    fn aseprite_hash_layout() {
+        check_aseprite(
+            r#"{
+                "guy 0.aseprite": { "frame": { "x": 0, "y": 0, "w": 16, "h": 16 }, "duration": 100 },
+                "guy 1.aseprite": { "frame": { "x": 16, "y": 0, "w": 16, "h": 16 }, "duration": 100 },
+                "guy 2.aseprite": { "frame": { "x": 32, "y": 0, "w": 16, "h": 16 }, "duration": 200 }
+            }"#,
+        );
+    }
+
+    #[cfg(feature = "aseprite")]
+    #[test]
+    fn aseprite_array_layout() {
+        check_aseprite(
+            r#"[
+                { "filename": "guy 0.aseprite", "frame": { "x": 0, "y": 0, "w": 16, "h": 16 }, "duration": 100 },
+                { "filename": "guy 1.aseprite", "frame": { "x": 16, "y": 0, "w": 16, "h": 16 }, "duration": 100 },
+                { "filename": "guy 2.aseprite", "frame": { "x": 32, "y": 0, "w": 16, "h": 16 }, "duration": 200 }
+            ]"#,
+        );
+    }
+
+    #[cfg(feature = "aseprite")]
+    #[test]
+    fn aseprite_without_tags_is_one_animation() {
+        let json = r#"{
+            "frames": [{ "frame": { "x": 0, "y": 0, "w": 8, "h": 8 }, "duration": 50 }],
+            "meta": { "size": { "w": 8, "h": 8 } }
+        }"#;
+        let animations = Animation::from_aseprite_json(json).unwrap();
+        assert_eq!(animations[""].frames().len(), 1);
+        assert!(Animation::from_aseprite_json("{}").is_err());
+    }
+}
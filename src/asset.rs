@@ -0,0 +1,387 @@
+use crate::anyhow::Context;
+use crate::anyhow::Result;
+#[cfg(feature = "hot-reload")]
+use crate::reload::WatchList;
+use crate::Font;
+use crate::Sound;
+use crate::SpriteSheet;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// Where the asset manager reads files from
+#[derive(Debug, Clone)]
+pub enum AssetSource {
+    /// Files are read from a directory on disk
+    Directory(PathBuf),
+
+    /// Files are embedded in the binary (see `embed_assets!`)
+    Embedded(HashMap<String, &'static [u8]>),
+}
+
+impl AssetSource {
+    /// Creates a source from a list of [path, contents] pairs
+    pub fn embedded(files: &[(&str, &'static [u8])]) -> Self {
+        AssetSource::Embedded(
+            files
+                .iter()
+                .map(|(path, bytes)| (normalize(path), *bytes))
+                .collect(),
+        )
+    }
+}
+
+impl Default for AssetSource {
+    /// The "assets" directory, relative to the current directory
+    fn default() -> Self {
+        AssetSource::Directory(PathBuf::from("assets"))
+    }
+}
+
+/// Embeds files into the binary, and creates an `AssetSource` for them
+///
+/// The first argument is the asset root, relative to the file the macro is
+/// used in. The remaining arguments are paths relative to that root,
+/// and are the same paths the assets are loaded with.
+///
+/// This makes it possible to read assets from disk during development,
+/// but ship a single binary for release builds:
+///
+/// ```ignore
+/// let source = if cfg!(debug_assertions) {
+///     AssetSource::Directory("assets".into())
+/// } else {
+///     yagl::embed_assets!("../assets", "player.png", "level1.txt")
+/// };
+/// actx.assets_mut().set_source(source);
+/// ```
+#[macro_export]
+macro_rules! embed_assets {
+    ($root:literal $(, $path:literal)* $(,)?) => {
+        $crate::AssetSource::embedded(&[
+            $(($path, include_bytes!(concat!($root, "/", $path)) as &'static [u8]),)*
+        ])
+    };
+}
+
+/// A reference to an asset loaded by the asset manager
+///
+/// Handles are cheap to copy, and can be turned back into the asset with
+/// `Assets::get`. Loading the same path twice returns the same handle.
+pub struct Handle<T> {
+    index: usize,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    fn new(index: usize) -> Self {
+        Self {
+            index,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state)
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Handle({})", self.index)
+    }
+}
+
+/// The contents of a file loaded as-is, for assets that yagl
+/// does not know how to interpret (e.g. levels or config files)
+#[derive(Debug, Clone)]
+pub struct Data {
+    bytes: Vec<u8>,
+}
+
+impl Data {
+    pub(crate) fn new(bytes: Vec<u8>) -> Self {
+        Self { bytes }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the contents as UTF-8 text
+    pub fn as_str(&self) -> Result<&str> {
+        Ok(std::str::from_utf8(&self.bytes)?)
+    }
+}
+
+impl Deref for Data {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+/// A kind of asset that can be loaded by the asset manager
+pub trait Asset: private::Sealed + 'static {}
+
+impl Asset for SpriteSheet {}
+impl Asset for Data {}
+impl Asset for Sound {}
+impl Asset for Font {}
+
+mod private {
+    use super::Assets;
+    use super::Data;
+    use super::Store;
+    use crate::Font;
+    use crate::Sound;
+    use crate::SpriteSheet;
+
+    pub trait Sealed: Sized {
+        fn store(assets: &Assets) -> &Store<Self>;
+        fn store_mut(assets: &mut Assets) -> &mut Store<Self>;
+    }
+
+    impl Sealed for SpriteSheet {
+        fn store(assets: &Assets) -> &Store<Self> {
+            &assets.sheets
+        }
+        fn store_mut(assets: &mut Assets) -> &mut Store<Self> {
+            &mut assets.sheets
+        }
+    }
+
+    impl Sealed for Data {
+        fn store(assets: &Assets) -> &Store<Self> {
+            &assets.data
+        }
+        fn store_mut(assets: &mut Assets) -> &mut Store<Self> {
+            &mut assets.data
+        }
+    }
+
+    impl Sealed for Sound {
+        fn store(assets: &Assets) -> &Store<Self> {
+            &assets.sounds
+        }
+        fn store_mut(assets: &mut Assets) -> &mut Store<Self> {
+            &mut assets.sounds
+        }
+    }
+
+    impl Sealed for Font {
+        fn store(assets: &Assets) -> &Store<Self> {
+            &assets.fonts
+        }
+        fn store_mut(assets: &mut Assets) -> &mut Store<Self> {
+            &mut assets.fonts
+        }
+    }
+}
+
+/// Loaded assets of a single kind
+pub struct Store<T> {
+    indices: HashMap<String, usize>,
+    entries: Vec<(String, Rc<T>)>,
+}
+
+impl<T> Default for Store<T> {
+    fn default() -> Self {
+        Self {
+            indices: HashMap::new(),
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl<T> Store<T> {
+    fn find(&self, path: &str) -> Option<Handle<T>> {
+        self.indices.get(path).map(|index| Handle::new(*index))
+    }
+
+    fn insert(&mut self, path: String, asset: Rc<T>) -> Handle<T> {
+        let index = self.entries.len();
+        self.indices.insert(path.clone(), index);
+        self.entries.push((path, asset));
+        Handle::new(index)
+    }
+}
+
+/// Loads and caches assets by path
+///
+/// Paths are relative to the asset source, which by default is the
+/// "assets" directory in the current directory, and always use '/'
+/// as the separator so that they work the same on every platform and
+/// with embedded assets.
+///
+/// Assets are loaded with e.g. `AppContext::load_sheet`, and retrieved
+/// with `AppContext::assets`.
+#[derive(Default)]
+pub struct Assets {
+    source: AssetSource,
+    sheets: Store<SpriteSheet>,
+    data: Store<Data>,
+    sounds: Store<Sound>,
+    fonts: Store<Font>,
+
+    #[cfg(feature = "hot-reload")]
+    watch_list: WatchList,
+}
+
+impl Assets {
+    pub fn source(&self) -> &AssetSource {
+        &self.source
+    }
+
+    /// Changes where assets are read from
+    ///
+    /// Assets that were already loaded are kept
+    pub fn set_source(&mut self, source: AssetSource) {
+        self.source = source;
+    }
+
+    /// Returns the asset for the given handle
+    pub fn get<T: Asset>(&self, handle: Handle<T>) -> Rc<T> {
+        T::store(self).entries[handle.index].1.clone()
+    }
+
+    /// Returns the path the given asset was loaded from
+    pub fn path<T: Asset>(&self, handle: Handle<T>) -> &str {
+        &T::store(self).entries[handle.index].0
+    }
+
+    /// Returns the handle for the given path, if it was already loaded
+    pub fn find<T: Asset>(&self, path: &str) -> Option<Handle<T>> {
+        T::store(self).find(&normalize(path))
+    }
+
+    /// Reads the raw contents of a file, without caching it
+    pub fn read(&self, path: &str) -> Result<Vec<u8>> {
+        let path = normalize(path);
+        let bytes = match &self.source {
+            AssetSource::Directory(root) => std::fs::read(root.join(&path))
+                .with_context(|| format!("Failed to read asset {:?}", path))?,
+            AssetSource::Embedded(files) => match files.get(&path) {
+                Some(bytes) => bytes.to_vec(),
+                None => anyhow::bail!("Asset {:?} is not embedded", path),
+            },
+        };
+        Ok(bytes)
+    }
+
+    /// Loads a file as-is
+    pub fn load_data(&mut self, path: &str) -> Result<Handle<Data>> {
+        let path = normalize(path);
+        if let Some(handle) = self.data.find(&path) {
+            return Ok(handle);
+        }
+        let bytes = self.read(&path)?;
+        self.watch(&path);
+        Ok(self.data.insert(path, Rc::new(Data::new(bytes))))
+    }
+
+    /// Loads a WAV (or with the `ogg` feature, OGG Vorbis) sound
+    pub fn load_sound(&mut self, path: &str) -> Result<Handle<Sound>> {
+        let path = normalize(path);
+        if let Some(handle) = self.sounds.find(&path) {
+            return Ok(handle);
+        }
+        let bytes = self.read(&path)?;
+        let sound = Sound::from_bytes(&bytes)
+            .with_context(|| format!("Failed to load sound {:?}", path))?;
+        self.watch(&path);
+        Ok(self.sounds.insert(path, Rc::new(sound)))
+    }
+
+    /// Loads a sound that is decoded while it plays, which is
+    /// better suited for long sounds like music
+    /// (see `Sound::streamed_from_bytes`)
+    ///
+    /// If the same path was already loaded with `load_sound`,
+    /// that sound is returned instead.
+    pub fn load_music(&mut self, path: &str) -> Result<Handle<Sound>> {
+        let path = normalize(path);
+        if let Some(handle) = self.sounds.find(&path) {
+            return Ok(handle);
+        }
+        let bytes = self.read(&path)?;
+        let sound = Sound::streamed_from_bytes(bytes)
+            .with_context(|| format!("Failed to load music {:?}", path))?;
+        self.watch(&path);
+        Ok(self.sounds.insert(path, Rc::new(sound)))
+    }
+
+    /// Loads a TrueType (or OpenType) font
+    ///
+    /// To draw text, the font is then baked with `Font::bake`
+    pub fn load_font(&mut self, path: &str) -> Result<Handle<Font>> {
+        let path = normalize(path);
+        if let Some(handle) = self.fonts.find(&path) {
+            return Ok(handle);
+        }
+        let bytes = self.read(&path)?;
+        let font =
+            Font::from_bytes(bytes).with_context(|| format!("Failed to load font {:?}", path))?;
+        self.watch(&path);
+        Ok(self.fonts.insert(path, Rc::new(font)))
+    }
+
+    pub(crate) fn insert_sheet(
+        &mut self,
+        path: &str,
+        sheet: Rc<SpriteSheet>,
+    ) -> Handle<SpriteSheet> {
+        let path = normalize(path);
+        self.watch(&path);
+        self.sheets.insert(path, sheet)
+    }
+
+    /// Swaps out the asset for a handle, e.g. after its file changed
+    #[cfg(feature = "hot-reload")]
+    pub(crate) fn replace<T: Asset>(&mut self, handle: Handle<T>, asset: Rc<T>) {
+        T::store_mut(self).entries[handle.index].1 = asset;
+    }
+
+    #[cfg(feature = "hot-reload")]
+    pub(crate) fn watch_list(&self) -> &WatchList {
+        &self.watch_list
+    }
+
+    #[cfg(feature = "hot-reload")]
+    fn watch(&self, path: &str) {
+        if let AssetSource::Directory(root) = &self.source {
+            self.watch_list.add(path, root.join(path));
+        }
+    }
+
+    #[cfg(not(feature = "hot-reload"))]
+    fn watch(&self, _path: &str) {}
+}
+
+fn normalize(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    path.trim_start_matches("./").to_owned()
+}
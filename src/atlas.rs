@@ -0,0 +1,397 @@
+use crate::anyhow::Context;
+use crate::anyhow::Result;
+use crate::AppContext;
+use crate::SpriteSheet;
+use image::RgbaImage;
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+
+/// Combines many images into as few sprite sheets as possible
+///
+/// Since a SpriteBatch can only draw from a single sheet, packing
+/// images together means fewer batches (and fewer draw calls).
+///
+/// Images are packed at runtime with `AtlasBuilder::build`. For packing
+/// ahead of time (e.g. from a build script), `AtlasBuilder::pack` does
+/// everything except creating the sheets, and the resulting pages can
+/// be saved with `AtlasPage::save`.
+pub struct AtlasBuilder {
+    images: Vec<(String, RgbaImage)>,
+    max_size: u32,
+    padding: u32,
+    extrude: u32,
+}
+
+/// Where an image ended up in an atlas
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Region {
+    /// The index of the page (i.e. sheet) the image is on
+    pub page: usize,
+
+    /// The [upper_left, lower_right] corners of the image,
+    /// as fractions of the page's size (i.e. usable with `Instance::set_src`)
+    pub src: [[f32; 2]; 2],
+
+    /// The [width, height] of the image in pixels
+    pub size: [u32; 2],
+}
+
+/// The result of packing images, before any sheets are created
+pub struct PackedAtlas {
+    pub pages: Vec<AtlasPage>,
+    pub regions: HashMap<String, Region>,
+}
+
+/// The pixels of a single page of a packed atlas
+pub struct AtlasPage {
+    image: RgbaImage,
+}
+
+/// Images packed into one or more sprite sheets
+pub struct Atlas {
+    sheets: Vec<Rc<SpriteSheet>>,
+    regions: HashMap<String, Region>,
+}
+
+impl Default for AtlasBuilder {
+    fn default() -> Self {
+        Self {
+            images: Vec::new(),
+            max_size: 2048,
+            padding: 2,
+            extrude: 1,
+        }
+    }
+}
+
+impl AtlasBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the largest width and height a page can have, in pixels
+    ///
+    /// By default, set to 2048
+    pub fn set_max_size(&mut self, max_size: u32) {
+        self.max_size = max_size;
+    }
+
+    /// Sets the number of transparent pixels left between images
+    ///
+    /// By default, set to 2
+    pub fn set_padding(&mut self, padding: u32) {
+        self.padding = padding;
+    }
+
+    /// Sets how many times the edge pixels of each image are repeated
+    /// outwards, so that neighboring images don't bleed into each other
+    /// when sprites are scaled
+    ///
+    /// By default, set to 1
+    pub fn set_extrude(&mut self, extrude: u32) {
+        self.extrude = extrude;
+    }
+
+    /// Adds an image from the bytes of an image file
+    /// (in any format supported by the `image` crate)
+    ///
+    /// Names must be unique, since they're what regions are looked up by
+    pub fn add_bytes(&mut self, name: &str, bytes: &[u8]) -> Result<()> {
+        let image = image::load_from_memory(bytes)
+            .with_context(|| format!("Failed to decode image {:?}", name))?;
+        self.add_image(name, image.to_rgba8())
+    }
+
+    /// Adds an image from a file in the asset source (see `Assets`)
+    pub fn add_asset(&mut self, actx: &AppContext, path: &str) -> Result<()> {
+        let bytes = actx.assets().read(path)?;
+        self.add_bytes(path, &bytes)
+    }
+
+    /// Adds an image from rgba data
+    ///
+    /// Names must be unique, since they're what regions are looked up by
+    pub fn add_rgba(&mut self, name: &str, width: u32, height: u32, bytes: Vec<u8>) -> Result<()> {
+        match RgbaImage::from_raw(width, height, bytes) {
+            Some(image) => self.add_image(name, image),
+            None => Err(anyhow::anyhow!(
+                "Image {:?} has the wrong number of bytes for {}x{}",
+                name,
+                width,
+                height
+            )),
+        }
+    }
+
+    fn add_image(&mut self, name: &str, image: RgbaImage) -> Result<()> {
+        if self.images.iter().any(|(n, _)| n == name) {
+            anyhow::bail!("An image named {:?} was already added to the atlas", name);
+        }
+        self.images.push((name.to_owned(), image));
+        Ok(())
+    }
+
+    /// Packs the images and creates a sprite sheet for each page
+    pub fn build(&self, actx: &mut AppContext) -> Result<Atlas> {
+        self.pack()?.build(actx)
+    }
+
+    /// Packs the images, without creating any sprite sheets
+    ///
+    /// Images are placed on shelves, tallest first, and a new page
+    /// is started whenever the current one is full.
+    pub fn pack(&self) -> Result<PackedAtlas> {
+        let border = self.extrude + self.padding;
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(self.images[i].1.height()));
+
+        // [x, y] of each image (including its border), and the page it's on
+        let mut placements = vec![(0, [0, 0]); self.images.len()];
+        let mut page_sizes = Vec::new();
+        let mut shelf = Shelf::new(self.max_size);
+        for i in order {
+            let (name, image) = &self.images[i];
+            let size = [image.width() + border * 2, image.height() + border * 2];
+            if size[0] > self.max_size || size[1] > self.max_size {
+                anyhow::bail!(
+                    "Image {:?} ({}x{}) does not fit in an atlas page of {}x{}",
+                    name,
+                    image.width(),
+                    image.height(),
+                    self.max_size,
+                    self.max_size
+                );
+            }
+            let pos = match shelf.place(size) {
+                Some(pos) => pos,
+                None => {
+                    page_sizes.push(shelf.used());
+                    shelf = Shelf::new(self.max_size);
+                    shelf.place(size).unwrap()
+                }
+            };
+            placements[i] = (page_sizes.len(), pos);
+        }
+        page_sizes.push(shelf.used());
+
+        let mut pages: Vec<_> = page_sizes
+            .iter()
+            .map(|&[width, height]| RgbaImage::new(width.max(1), height.max(1)))
+            .collect();
+        let mut regions = HashMap::new();
+        for ((name, image), &(page, [x, y])) in self.images.iter().zip(&placements) {
+            let canvas = &mut pages[page];
+            let [x, y] = [x + self.padding, y + self.padding];
+            blit_extruded(canvas, image, [x, y], self.extrude);
+            let [x, y] = [x + self.extrude, y + self.extrude];
+            let (width, height) = (canvas.width() as f32, canvas.height() as f32);
+            regions.insert(
+                name.clone(),
+                Region {
+                    page,
+                    src: [
+                        [x as f32 / width, y as f32 / height],
+                        [
+                            (x + image.width()) as f32 / width,
+                            (y + image.height()) as f32 / height,
+                        ],
+                    ],
+                    size: [image.width(), image.height()],
+                },
+            );
+        }
+
+        Ok(PackedAtlas {
+            pages: pages.into_iter().map(|image| AtlasPage { image }).collect(),
+            regions,
+        })
+    }
+}
+
+impl PackedAtlas {
+    /// Creates a sprite sheet for each page
+    pub fn build(self, actx: &mut AppContext) -> Result<Atlas> {
+        let sheets = self
+            .pages
+            .into_iter()
+            .map(|page| {
+                let (width, height) = page.image.dimensions();
+                actx.new_sheet_from_rgba_bytes(width, height, page.image.into_raw())
+            })
+            .collect::<Result<_>>()?;
+        Ok(Atlas {
+            sheets,
+            regions: self.regions,
+        })
+    }
+}
+
+impl AtlasPage {
+    pub fn width(&self) -> u32 {
+        self.image.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.image.height()
+    }
+
+    pub fn rgba(&self) -> &[u8] {
+        &self.image
+    }
+
+    /// Saves the page as an image file, in the format implied
+    /// by the extension of the path
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        self.image
+            .save(path)
+            .with_context(|| format!("Failed to save atlas page to {:?}", path))
+    }
+}
+
+impl Atlas {
+    /// Returns the sprite sheet for a page
+    pub fn sheet(&self, page: usize) -> Rc<SpriteSheet> {
+        self.sheets[page].clone()
+    }
+
+    pub fn sheets(&self) -> &[Rc<SpriteSheet>] {
+        &self.sheets
+    }
+
+    /// Returns where the image with the given name is
+    pub fn get(&self, name: &str) -> Option<&Region> {
+        self.regions.get(name)
+    }
+
+    pub fn regions(&self) -> &HashMap<String, Region> {
+        &self.regions
+    }
+}
+
+/// Places rectangles left to right in rows ("shelves"),
+/// each as tall as the first rectangle placed in it
+struct Shelf {
+    max_size: u32,
+    x: u32,
+    y: u32,
+    height: u32,
+    width: u32,
+}
+
+impl Shelf {
+    fn new(max_size: u32) -> Self {
+        Self {
+            max_size,
+            x: 0,
+            y: 0,
+            height: 0,
+            width: 0,
+        }
+    }
+
+    fn place(&mut self, [width, height]: [u32; 2]) -> Option<[u32; 2]> {
+        if self.x + width > self.max_size {
+            self.y += self.height;
+            self.x = 0;
+            self.height = 0;
+        }
+        if self.y + height > self.max_size {
+            return None;
+        }
+        let pos = [self.x, self.y];
+        self.x += width;
+        self.height = self.height.max(height);
+        self.width = self.width.max(self.x);
+        Some(pos)
+    }
+
+    /// Returns the [width, height] of the area used so far
+    fn used(&self) -> [u32; 2] {
+        [self.width, self.y + self.height]
+    }
+}
+
+/// Copies `image` onto `canvas` with its upper left corner at `pos`,
+/// surrounded by `extrude` copies of its edge pixels
+fn blit_extruded(canvas: &mut RgbaImage, image: &RgbaImage, pos: [u32; 2], extrude: u32) {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return;
+    }
+    for y in 0..height + extrude * 2 {
+        for x in 0..width + extrude * 2 {
+            let sx = x.saturating_sub(extrude).min(width - 1);
+            let sy = y.saturating_sub(extrude).min(height - 1);
+            canvas.put_pixel(pos[0] + x, pos[1] + y, *image.get_pixel(sx, sy));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(builder: &mut AtlasBuilder, name: &str, size: u32, color: u8) {
+        let bytes = vec![color; (size * size * 4) as usize];
+        builder.add_rgba(name, size, size, bytes).unwrap();
+    }
+
+    #[test]
+    fn overflow_starts_new_pages() {
+        let mut builder = AtlasBuilder::new();
+        builder.set_max_size(16);
+        for name in &["a", "b", "c"] {
+            solid(&mut builder, name, 8, 255);
+        }
+        let packed = builder.pack().unwrap();
+
+        // 8 pixels plus a border of 3 on each side leaves room for one per page
+        assert_eq!(packed.pages.len(), 3);
+        let mut pages: Vec<_> = packed.regions.values().map(|r| r.page).collect();
+        pages.sort();
+        assert_eq!(pages, [0, 1, 2]);
+    }
+
+    #[test]
+    fn regions_skip_the_border() {
+        let mut builder = AtlasBuilder::new();
+        builder.set_padding(2);
+        builder.set_extrude(1);
+        solid(&mut builder, "a", 4, 200);
+        let packed = builder.pack().unwrap();
+
+        let page = &packed.pages[0];
+        assert_eq!([page.width(), page.height()], [10, 10]);
+        let region = packed.regions["a"];
+        assert_eq!(region.size, [4, 4]);
+        assert_eq!(region.src, [[0.3, 0.3], [0.7, 0.7]]);
+
+        // padding is left transparent, and the extruded edge repeats the image
+        assert_eq!(page.image.get_pixel(1, 1).0, [0; 4]);
+        assert_eq!(page.image.get_pixel(2, 2).0, [200; 4]);
+        assert_eq!(page.image.get_pixel(7, 7).0, [200; 4]);
+        assert_eq!(page.image.get_pixel(8, 8).0, [0; 4]);
+    }
+
+    #[test]
+    fn image_too_big_for_a_page() {
+        let mut builder = AtlasBuilder::new();
+        builder.set_max_size(16);
+        solid(&mut builder, "big", 12, 255);
+        let error = builder.pack().err().unwrap().to_string();
+        assert!(error.contains("\"big\""), "{}", error);
+        assert!(error.contains("does not fit"), "{}", error);
+    }
+
+    #[test]
+    fn names_must_be_unique() {
+        let mut builder = AtlasBuilder::new();
+        solid(&mut builder, "a", 4, 1);
+        assert!(builder.add_rgba("a", 1, 1, vec![0; 4]).is_err());
+        assert!(builder.add_rgba("b", 1, 1, vec![0; 4]).is_ok());
+        assert_eq!(builder.pack().unwrap().regions.len(), 2);
+    }
+}
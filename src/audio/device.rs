@@ -0,0 +1,78 @@
+use super::Mixer;
+use crate::anyhow::Result;
+use cpal::traits::DeviceTrait;
+use cpal::traits::HostTrait;
+use cpal::traits::StreamTrait;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+/// Opens the default output device, and starts playing
+/// whatever the mixer produces
+pub(super) fn open(mixer: Arc<Mutex<Mixer>>) -> Result<cpal::Stream> {
+    let device = match cpal::default_host().default_output_device() {
+        Some(device) => device,
+        None => anyhow::bail!("No audio output device found"),
+    };
+    let config = device.default_output_config()?;
+    let format = config.sample_format();
+    let config: cpal::StreamConfig = config.into();
+    let channels = config.channels as usize;
+    mixer.lock().unwrap().set_rate(config.sample_rate.0);
+
+    let on_error = {
+        let mixer = mixer.clone();
+        move |error: cpal::StreamError| {
+            let error = anyhow::Error::from(error).context("Audio stream failed");
+            mixer.lock().unwrap().report(error);
+        }
+    };
+    let mut buffer = Vec::new();
+    let stream = match format {
+        cpal::SampleFormat::F32 => device.build_output_stream(
+            &config,
+            move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+                fill(&mixer, &mut buffer, channels, data, |s| s)
+            },
+            on_error,
+        )?,
+        cpal::SampleFormat::I16 => device.build_output_stream(
+            &config,
+            move |data: &mut [i16], _: &cpal::OutputCallbackInfo| {
+                fill(&mixer, &mut buffer, channels, data, |s| {
+                    (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+                })
+            },
+            on_error,
+        )?,
+        cpal::SampleFormat::U16 => device.build_output_stream(
+            &config,
+            move |data: &mut [u16], _: &cpal::OutputCallbackInfo| {
+                fill(&mixer, &mut buffer, channels, data, |s| {
+                    ((s.clamp(-1.0, 1.0) * 0.5 + 0.5) * u16::MAX as f32) as u16
+                })
+            },
+            on_error,
+        )?,
+    };
+    stream.play()?;
+    Ok(stream)
+}
+
+/// Fills a device buffer with the mixer's [left, right] samples,
+/// adapting them to however many channels the device has
+fn fill<T, F>(mixer: &Mutex<Mixer>, buffer: &mut Vec<f32>, channels: usize, data: &mut [T], f: F)
+where
+    F: Fn(f32) -> T,
+{
+    buffer.resize(data.len() / channels * 2, 0.0);
+    mixer.lock().unwrap().mix(buffer);
+    for (out, frame) in data.chunks_exact_mut(channels).zip(buffer.chunks_exact(2)) {
+        for (channel, sample) in out.iter_mut().enumerate() {
+            *sample = f(match (channels, channel) {
+                (1, _) => (frame[0] + frame[1]) / 2.0,
+                (_, 0) | (_, 1) => frame[channel],
+                _ => 0.0,
+            });
+        }
+    }
+}
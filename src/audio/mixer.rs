@@ -0,0 +1,223 @@
+use super::sound::Reader;
+use super::Bus;
+use super::PlayOptions;
+use super::Sound;
+use crate::anyhow::Error;
+use crate::anyhow::Result;
+
+/// Combines every playing sound into a single stream of samples
+///
+/// The mixer doesn't know anything about output devices; it is shared
+/// with whatever consumes its samples (see `Output`), which
+/// pulls them with `mix`.
+pub(crate) struct Mixer {
+    rate: u32,
+    master: f32,
+    buses: [f32; Bus::COUNT],
+    voices: Vec<Voice>,
+
+    /// Errors from decoding or from the device, waiting to be
+    /// passed to the game (see `Audio::take_error`)
+    errors: Vec<Error>,
+}
+
+struct Voice {
+    id: u64,
+    reader: Reader,
+    len: usize,
+    rate: u32,
+    options: PlayOptions,
+    pos: f64,
+    paused: bool,
+
+    /// Multiplied on top of the volume, for fading in and out
+    gain: f32,
+    fade_target: f32,
+
+    /// How much gain changes per output frame while fading
+    fade_step: f32,
+
+    /// If set, the voice is removed once it has faded out
+    stop_after_fade: bool,
+}
+
+impl Mixer {
+    pub fn new(rate: u32) -> Self {
+        Self {
+            rate,
+            master: 1.0,
+            buses: [1.0; Bus::COUNT],
+            voices: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    #[cfg(feature = "audio")]
+    pub fn set_rate(&mut self, rate: u32) {
+        self.rate = rate;
+    }
+
+    pub fn master(&self) -> f32 {
+        self.master
+    }
+
+    pub fn set_master(&mut self, volume: f32) {
+        self.master = volume;
+    }
+
+    pub fn bus(&self, bus: Bus) -> f32 {
+        self.buses[bus as usize]
+    }
+
+    pub fn set_bus(&mut self, bus: Bus, volume: f32) {
+        self.buses[bus as usize] = volume;
+    }
+
+    /// Starts playing a sound with the given reader (see `Sound::reader`),
+    /// fading it in over `fade_in` seconds
+    pub fn play(
+        &mut self,
+        id: u64,
+        sound: &Sound,
+        reader: Reader,
+        options: PlayOptions,
+        fade_in: f32,
+    ) {
+        let mut voice = Voice {
+            id,
+            reader,
+            len: sound.len(),
+            rate: sound.rate(),
+            options,
+            pos: 0.0,
+            paused: false,
+            gain: 1.0,
+            fade_target: 1.0,
+            fade_step: 0.0,
+            stop_after_fade: false,
+        };
+        if fade_in > 0.0 {
+            voice.gain = 0.0;
+            voice.fade_to(1.0, fade_in, self.rate);
+        }
+        self.voices.push(voice);
+    }
+
+    /// Stops a sound, fading it out over `fade_out` seconds
+    pub fn stop(&mut self, id: u64, fade_out: f32) {
+        let rate = self.rate;
+        if fade_out > 0.0 {
+            if let Some(voice) = self.voice_mut(id) {
+                voice.fade_to(0.0, fade_out, rate);
+                voice.stop_after_fade = true;
+            }
+        } else {
+            self.voices.retain(|voice| voice.id != id);
+        }
+    }
+
+    pub fn stop_all(&mut self) {
+        self.voices.clear();
+    }
+
+    pub fn is_playing(&self, id: u64) -> bool {
+        self.voices.iter().any(|voice| voice.id == id)
+    }
+
+    pub fn set_paused(&mut self, id: u64, paused: bool) {
+        if let Some(voice) = self.voice_mut(id) {
+            voice.paused = paused;
+        }
+    }
+
+    pub fn set_options(&mut self, id: u64, options: PlayOptions) {
+        if let Some(voice) = self.voice_mut(id) {
+            voice.options = options;
+        }
+    }
+
+    /// Fills `out` with interleaved [left, right] samples,
+    /// advancing every playing sound
+    pub fn mix(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            *sample = 0.0;
+        }
+        let rate = self.rate as f64;
+        let (master, buses) = (self.master, self.buses);
+        for voice in &mut self.voices {
+            if voice.paused {
+                continue;
+            }
+            let options = voice.options;
+            let step = voice.rate as f64 / rate * options.pitch.max(0.0) as f64;
+            let volume = options.volume * buses[options.bus as usize] * master;
+            let pan = options.pan.clamp(-1.0, 1.0);
+            let (left, right) = ((1.0 - pan).min(1.0), (1.0 + pan).min(1.0));
+            let len = voice.len as f64;
+            for frame in out.chunks_exact_mut(2) {
+                let [l, r] = match voice.reader.sample(voice.pos, options.looping) {
+                    Ok(sample) => sample,
+                    Err(error) => {
+                        self.errors.push(error.context("Failed to decode sound"));
+                        voice.pos = len;
+                        break;
+                    }
+                };
+                let gain = volume * voice.gain;
+                frame[0] += l * gain * left;
+                frame[1] += r * gain * right;
+
+                voice.advance_fade();
+                if voice.stop_after_fade && voice.gain <= 0.0 {
+                    voice.pos = len;
+                    break;
+                }
+                voice.pos += step;
+                if voice.pos >= len {
+                    if options.looping {
+                        voice.pos %= len;
+                    } else {
+                        break;
+                    }
+                }
+            }
+        }
+        self.voices.retain(|voice| voice.pos < voice.len as f64);
+    }
+
+    pub fn report(&mut self, error: Error) {
+        self.errors.push(error);
+    }
+
+    /// Returns the oldest error that hasn't been taken yet
+    pub fn take_error(&mut self) -> Result<()> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors.remove(0))
+        }
+    }
+
+    fn voice_mut(&mut self, id: u64) -> Option<&mut Voice> {
+        self.voices.iter_mut().find(|voice| voice.id == id)
+    }
+}
+
+impl Voice {
+    fn fade_to(&mut self, target: f32, seconds: f32, rate: u32) {
+        self.fade_target = target;
+        self.fade_step = (target - self.gain).abs() / (seconds * rate as f32);
+    }
+
+    fn advance_fade(&mut self) {
+        if self.gain < self.fade_target {
+            self.gain = (self.gain + self.fade_step).min(self.fade_target);
+        } else if self.gain > self.fade_target {
+            self.gain = (self.gain - self.fade_step).max(self.fade_target);
+        }
+    }
+}
@@ -0,0 +1,390 @@
+use crate::anyhow::Result;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+
+#[cfg(feature = "audio")]
+mod device;
+mod mixer;
+mod sound;
+
+use mixer::Mixer;
+pub use sound::Sound;
+
+/// The sample rate used when there is no output device
+const DEFAULT_RATE: u32 = 44100;
+
+/// A group of sounds whose volume can be changed together
+/// (e.g. from an options menu)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Bus {
+    Music,
+    Sfx,
+    Voice,
+}
+
+impl Bus {
+    pub(crate) const COUNT: usize = 3;
+}
+
+/// How a sound is played
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlayOptions {
+    /// By default, set to 1
+    pub volume: f32,
+
+    /// How fast the sound plays, which also changes how high it sounds
+    /// (e.g. 2 is twice as fast, and an octave higher)
+    ///
+    /// By default, set to 1
+    pub pitch: f32,
+
+    /// Where the sound is between the left (-1) and right (1) speakers
+    ///
+    /// By default, set to 0
+    pub pan: f32,
+
+    /// By default, set to `Bus::Sfx`
+    pub bus: Bus,
+
+    /// By default, set to false
+    pub looping: bool,
+}
+
+impl Default for PlayOptions {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            pitch: 1.0,
+            pan: 0.0,
+            bus: Bus::Sfx,
+            looping: false,
+        }
+    }
+}
+
+/// Identifies a single playback of a sound, so that it can be
+/// stopped or changed while playing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Playback(u64);
+
+/// Plays sounds, reachable with `AppContext::audio`
+///
+/// Sounds are mixed by yagl and sent to the default output device
+/// when the `audio` feature is enabled. Without it (or if no device
+/// could be opened, or when running headless), a null output is used
+/// instead, which plays sounds silently, but otherwise behaves the same
+/// (e.g. sounds still end after the right amount of time).
+///
+/// Errors from the device (e.g. failing to open it) or from decoding a
+/// streamed sound are passed to `Game::on_error` on the next frame.
+/// Returning Ok from there keeps the game running, without sound if
+/// the device is gone.
+///
+/// Music is played on `Bus::Music` with `play_music`, which makes it
+/// easy to crossfade between tracks. Music is best loaded with
+/// `Assets::load_music`, so that it's decoded while it plays instead of
+/// all at once.
+pub struct Audio {
+    mixer: Arc<Mutex<Mixer>>,
+    output: Output,
+    next_id: u64,
+    music: Option<Playback>,
+}
+
+enum Output {
+    /// Samples are pulled and thrown away as time passes
+    Null { remainder: f64, scratch: Vec<f32> },
+
+    #[cfg(feature = "audio")]
+    Device(cpal::Stream),
+}
+
+impl Audio {
+    /// Opens the default output device, falling back to a null output
+    /// if it can't be opened
+    pub(crate) fn new() -> Self {
+        #[cfg(feature = "audio")]
+        {
+            let mixer = Arc::new(Mutex::new(Mixer::new(DEFAULT_RATE)));
+            match device::open(mixer.clone()) {
+                Ok(stream) => {
+                    return Self {
+                        mixer,
+                        output: Output::Device(stream),
+                        next_id: 0,
+                        music: None,
+                    }
+                }
+                Err(error) => {
+                    let audio = Self::null(DEFAULT_RATE);
+                    audio
+                        .mixer()
+                        .report(error.context("Failed to open audio device"));
+                    return audio;
+                }
+            }
+        }
+        Self::null(DEFAULT_RATE)
+    }
+
+    /// Creates an Audio that doesn't play through any device
+    ///
+    /// Samples can be pulled from it with `mix`, which makes it
+    /// possible to check what would have been played
+    /// (e.g. from a unit test)
+    pub fn null(sample_rate: u32) -> Self {
+        Self {
+            mixer: Arc::new(Mutex::new(Mixer::new(sample_rate))),
+            output: Output::Null {
+                remainder: 0.0,
+                scratch: Vec::new(),
+            },
+            next_id: 0,
+            music: None,
+        }
+    }
+
+    /// Returns true if sounds are not actually being played
+    /// through a device
+    pub fn is_null(&self) -> bool {
+        match self.output {
+            Output::Null { .. } => true,
+            #[cfg(feature = "audio")]
+            Output::Device(_) => false,
+        }
+    }
+
+    /// Returns the number of samples per second sent to the output
+    pub fn sample_rate(&self) -> u32 {
+        self.mixer().rate()
+    }
+
+    /// Plays a sound once, with the default options
+    pub fn play(&mut self, sound: &Sound) -> Playback {
+        self.play_with(sound, PlayOptions::default())
+    }
+
+    pub fn play_with(&mut self, sound: &Sound, options: PlayOptions) -> Playback {
+        self.start(sound, options, 0.0)
+    }
+
+    /// Stops a sound immediately
+    ///
+    /// If the sound already finished, this does nothing
+    pub fn stop(&mut self, playback: Playback) {
+        self.mixer().stop(playback.0, 0.0);
+    }
+
+    /// Stops a sound, fading it out over the given number of seconds
+    pub fn fade_out(&mut self, playback: Playback, seconds: f32) {
+        self.mixer().stop(playback.0, seconds);
+    }
+
+    /// Stops every sound, including music
+    pub fn stop_all(&mut self) {
+        self.mixer().stop_all();
+        self.music = None;
+    }
+
+    /// Returns true if the sound has not yet finished or been stopped
+    pub fn is_playing(&self, playback: Playback) -> bool {
+        self.mixer().is_playing(playback.0)
+    }
+
+    pub fn set_paused(&mut self, playback: Playback, paused: bool) {
+        self.mixer().set_paused(playback.0, paused);
+    }
+
+    /// Changes the options of a sound while it plays
+    pub fn set_options(&mut self, playback: Playback, options: PlayOptions) {
+        self.mixer().set_options(playback.0, options);
+    }
+
+    /// Starts playing a music track in a loop, on `Bus::Music`
+    ///
+    /// If other music is already playing, the two are crossfaded
+    /// over the given number of seconds
+    pub fn play_music(&mut self, sound: &Sound, fade: f32) -> Playback {
+        self.stop_music(fade);
+        let options = PlayOptions {
+            bus: Bus::Music,
+            looping: true,
+            ..PlayOptions::default()
+        };
+        let playback = self.start(sound, options, fade);
+        self.music = Some(playback);
+        playback
+    }
+
+    /// Stops the current music track, fading it out over
+    /// the given number of seconds
+    pub fn stop_music(&mut self, fade: f32) {
+        if let Some(music) = self.music.take() {
+            self.mixer().stop(music.0, fade);
+        }
+    }
+
+    /// Returns the current music track, if any
+    pub fn music(&self) -> Option<Playback> {
+        self.music.filter(|music| self.is_playing(*music))
+    }
+
+    /// Returns the volume of all sounds on the given bus
+    pub fn volume(&self, bus: Bus) -> f32 {
+        self.mixer().bus(bus)
+    }
+
+    /// Sets the volume of all sounds on the given bus
+    ///
+    /// By default, set to 1
+    pub fn set_volume(&mut self, bus: Bus, volume: f32) {
+        self.mixer().set_bus(bus, volume);
+    }
+
+    pub fn master_volume(&self) -> f32 {
+        self.mixer().master()
+    }
+
+    /// Sets the volume of all sounds
+    ///
+    /// By default, set to 1
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.mixer().set_master(volume);
+    }
+
+    /// Mixes the next samples of every playing sound into `out`,
+    /// as interleaved [left, right] pairs
+    ///
+    /// This is meant for a null output (see `Audio::null`). With a real
+    /// device, the samples pulled here are never heard.
+    pub fn mix(&mut self, out: &mut [f32]) {
+        self.mixer().mix(out);
+    }
+
+    /// Lets `seconds` of time pass for a null output
+    pub(crate) fn advance(&mut self, seconds: f64) {
+        let rate = self.sample_rate();
+        match &mut self.output {
+            Output::Null { remainder, scratch } => {
+                let frames = seconds * rate as f64 + *remainder;
+                *remainder = frames.fract();
+                scratch.resize(frames as usize * 2, 0.0);
+                self.mixer.lock().unwrap().mix(scratch);
+            }
+            #[cfg(feature = "audio")]
+            Output::Device(_) => {}
+        }
+    }
+
+    /// Returns the oldest error from the device or from decoding
+    /// that hasn't been reported yet
+    pub(crate) fn take_error(&mut self) -> Result<()> {
+        self.mixer().take_error()
+    }
+
+    fn start(&mut self, sound: &Sound, options: PlayOptions, fade_in: f32) -> Playback {
+        let id = self.next_id;
+        self.next_id += 1;
+        if sound.len() > 0 {
+            // streamed sounds start decoding here, so that
+            // the mixer isn't locked while waiting for that
+            match sound.reader() {
+                Ok(reader) => self.mixer().play(id, sound, reader, options, fade_in),
+                Err(error) => self.mixer().report(error.context("Failed to play sound")),
+            }
+        }
+        Playback(id)
+    }
+
+    fn mixer(&self) -> MutexGuard<'_, Mixer> {
+        self.mixer.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A sound at 100 frames per second, so that frames are easy to count
+    fn sound(samples: &[f32]) -> Sound {
+        Sound::from_samples(100, 1, samples).unwrap()
+    }
+
+    /// Mixes `frames` frames and returns just the left channel
+    fn mix(audio: &mut Audio, frames: usize) -> Vec<f32> {
+        let mut out = vec![0.0; frames * 2];
+        audio.mix(&mut out);
+        out.chunks_exact(2).map(|frame| frame[0]).collect()
+    }
+
+    #[test]
+    fn volumes_multiply() {
+        let mut audio = Audio::null(100);
+        audio.set_master_volume(0.5);
+        audio.set_volume(Bus::Sfx, 0.5);
+        audio.set_volume(Bus::Music, 0.0);
+        let options = PlayOptions {
+            volume: 2.0,
+            ..PlayOptions::default()
+        };
+        audio.play_with(&sound(&[0.5; 10]), options);
+        assert_eq!(mix(&mut audio, 2), [0.25, 0.25]);
+    }
+
+    #[test]
+    fn sounds_finish_after_their_length() {
+        let mut audio = Audio::null(100);
+        let playback = audio.play(&sound(&[1.0; 10]));
+        assert_eq!(mix(&mut audio, 9), [1.0; 9]);
+        assert!(audio.is_playing(playback));
+        assert_eq!(mix(&mut audio, 3), [1.0, 0.0, 0.0]);
+        assert!(!audio.is_playing(playback));
+    }
+
+    #[test]
+    fn looping_sounds_start_over() {
+        let mut audio = Audio::null(100);
+        let options = PlayOptions {
+            looping: true,
+            ..PlayOptions::default()
+        };
+        let playback = audio.play_with(&sound(&[0.25, 0.5, 0.75, 1.0]), options);
+        assert_eq!(
+            mix(&mut audio, 10),
+            [0.25, 0.5, 0.75, 1.0, 0.25, 0.5, 0.75, 1.0, 0.25, 0.5]
+        );
+        assert!(audio.is_playing(playback));
+    }
+
+    #[test]
+    fn stop_silences_immediately() {
+        let mut audio = Audio::null(100);
+        let playback = audio.play(&sound(&[1.0; 10]));
+        mix(&mut audio, 2);
+        audio.stop(playback);
+        assert!(!audio.is_playing(playback));
+        assert_eq!(mix(&mut audio, 2), [0.0; 2]);
+    }
+
+    #[test]
+    fn fade_out_ends_the_sound() {
+        let mut audio = Audio::null(100);
+        let playback = audio.play(&sound(&[1.0; 100]));
+        audio.fade_out(playback, 0.04);
+        assert_eq!(mix(&mut audio, 6), [1.0, 0.75, 0.5, 0.25, 0.0, 0.0]);
+        assert!(!audio.is_playing(playback));
+    }
+
+    #[test]
+    fn null_output_advances_with_time() {
+        let mut audio = Audio::null(100);
+        let playback = audio.play(&sound(&[1.0; 10]));
+        audio.advance(0.05);
+        assert!(audio.is_playing(playback));
+        audio.advance(0.05);
+        assert!(!audio.is_playing(playback));
+        assert!(audio.take_error().is_ok());
+    }
+}
@@ -0,0 +1,533 @@
+use crate::anyhow::Result;
+use std::sync::Arc;
+
+/// Audio data that can be played with `Audio::play`
+///
+/// Sounds are cheap to clone, since clones share the same samples.
+#[derive(Clone)]
+pub struct Sound {
+    rate: u32,
+    len: usize,
+    data: Data,
+}
+
+#[derive(Clone)]
+enum Data {
+    Decoded(Arc<[[f32; 2]]>),
+
+    /// The bytes of an OGG Vorbis file, decoded while playing
+    #[cfg(feature = "ogg")]
+    Ogg(Arc<[u8]>),
+}
+
+/// Reads the frames of a sound for a single playback
+pub(crate) enum Reader {
+    Decoded(Arc<[[f32; 2]]>),
+
+    #[cfg(feature = "ogg")]
+    Ogg(ogg::OggStream),
+}
+
+impl Sound {
+    /// Decodes a sound from the bytes of a WAV file, or an OGG Vorbis
+    /// file if the `ogg` feature is enabled
+    ///
+    /// The whole file is decoded up front.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Sound> {
+        if bytes.starts_with(b"RIFF") {
+            decode_wav(bytes)
+        } else if bytes.starts_with(b"OggS") {
+            decode_ogg(bytes)
+        } else {
+            Err(anyhow::anyhow!("Unrecognized sound format"))
+        }
+    }
+
+    /// Like `from_bytes`, except that OGG Vorbis files are decoded a bit
+    /// at a time while they play, instead of all at once
+    ///
+    /// This is meant for long sounds like music, which take a lot of
+    /// memory once decoded. WAV files are still decoded up front.
+    pub fn streamed_from_bytes(bytes: Vec<u8>) -> Result<Sound> {
+        if bytes.starts_with(b"OggS") {
+            stream_ogg(bytes)
+        } else {
+            Sound::from_bytes(&bytes)
+        }
+    }
+
+    /// Creates a sound from interleaved samples between -1 and 1,
+    /// with either 1 (mono) or 2 (stereo) channels
+    pub fn from_samples(rate: u32, channels: u16, samples: &[f32]) -> Result<Sound> {
+        let frames: Vec<[f32; 2]> = match channels {
+            1 => samples.iter().map(|s| [*s, *s]).collect(),
+            2 => samples.chunks_exact(2).map(|s| [s[0], s[1]]).collect(),
+            _ => anyhow::bail!("Sounds must have 1 or 2 channels, not {}", channels),
+        };
+        Ok(Sound {
+            rate,
+            len: frames.len(),
+            data: Data::Decoded(frames.into()),
+        })
+    }
+
+    /// Returns the number of samples per second (per channel)
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    /// Returns the length of the sound in seconds
+    pub fn duration(&self) -> f64 {
+        self.len as f64 / self.rate as f64
+    }
+
+    /// Returns true if the sound is decoded while it plays
+    /// (see `streamed_from_bytes`)
+    pub fn is_streamed(&self) -> bool {
+        match self.data {
+            Data::Decoded(_) => false,
+            #[cfg(feature = "ogg")]
+            Data::Ogg(_) => true,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Starts reading the sound from the beginning
+    ///
+    /// For streamed sounds, this starts decoding on another thread, and
+    /// waits for the first frames, so it shouldn't be called while
+    /// the mixer is locked.
+    pub(crate) fn reader(&self) -> Result<Reader> {
+        Ok(match &self.data {
+            Data::Decoded(frames) => Reader::Decoded(frames.clone()),
+            #[cfg(feature = "ogg")]
+            Data::Ogg(bytes) => Reader::Ogg(ogg::OggStream::new(bytes.clone())?),
+        })
+    }
+}
+
+impl Reader {
+    /// Returns the [left, right] sample at the given position,
+    /// interpolating between frames
+    ///
+    /// Positions should only go backwards when looping, since
+    /// streamed sounds are decoded ahead of time in order.
+    pub(crate) fn sample(&mut self, pos: f64, looping: bool) -> Result<[f32; 2]> {
+        let i = pos as usize;
+        let t = (pos - i as f64) as f32;
+        let (a, b) = match self {
+            Reader::Decoded(frames) => {
+                let a = frames[i];
+                let b = match frames.get(i + 1) {
+                    Some(b) => *b,
+                    None if looping => frames[0],
+                    None => a,
+                };
+                (a, b)
+            }
+            #[cfg(feature = "ogg")]
+            Reader::Ogg(reader) => reader.pair(i, looping)?,
+        };
+        Ok([a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t])
+    }
+}
+
+fn decode_wav(bytes: &[u8]) -> Result<Sound> {
+    if bytes.len() < 12 || &bytes[8..12] != b"WAVE" {
+        anyhow::bail!("Not a WAV file");
+    }
+    let u16_at = |b: &[u8], i: usize| u16::from_le_bytes([b[i], b[i + 1]]);
+    let u32_at = |b: &[u8], i: usize| u32::from_le_bytes([b[i], b[i + 1], b[i + 2], b[i + 3]]);
+
+    let mut format = None;
+    let mut data = None;
+    let mut i = 12;
+    while i + 8 <= bytes.len() {
+        let id = &bytes[i..i + 4];
+        let size = u32_at(bytes, i + 4) as usize;
+        let body = &bytes[i + 8..(i + 8 + size).min(bytes.len())];
+        match id {
+            b"fmt " if body.len() >= 16 => {
+                let mut tag = u16_at(body, 0);
+                if tag == 0xFFFE && body.len() >= 26 {
+                    // WAVE_FORMAT_EXTENSIBLE stores the real format
+                    // at the start of the sub-format GUID
+                    tag = u16_at(body, 24);
+                }
+                format = Some((tag, u16_at(body, 2), u32_at(body, 4), u16_at(body, 14)));
+            }
+            b"data" => data = Some(body),
+            _ => {}
+        }
+        // chunks are padded to an even size
+        i += 8 + size + (size & 1);
+    }
+
+    let (tag, channels, rate, bits) = match format {
+        Some(format) => format,
+        None => anyhow::bail!("WAV file has no fmt chunk"),
+    };
+    let data = match data {
+        Some(data) => data,
+        None => anyhow::bail!("WAV file has no data chunk"),
+    };
+    let samples: Vec<f32> = match (tag, bits) {
+        (1, 8) => data.iter().map(|s| (*s as f32 - 128.0) / 128.0).collect(),
+        (1, 16) => data
+            .chunks_exact(2)
+            .map(|s| i16::from_le_bytes([s[0], s[1]]) as f32 / 32768.0)
+            .collect(),
+        (1, 24) => data
+            .chunks_exact(3)
+            .map(|s| i32::from_le_bytes([0, s[0], s[1], s[2]]) as f32 / 2147483648.0)
+            .collect(),
+        (1, 32) => data
+            .chunks_exact(4)
+            .map(|s| i32::from_le_bytes([s[0], s[1], s[2], s[3]]) as f32 / 2147483648.0)
+            .collect(),
+        (3, 32) => data
+            .chunks_exact(4)
+            .map(|s| f32::from_le_bytes([s[0], s[1], s[2], s[3]]))
+            .collect(),
+        _ => anyhow::bail!(
+            "Unsupported WAV format (format tag {}, {} bits per sample)",
+            tag,
+            bits
+        ),
+    };
+
+    from_interleaved(rate, channels as usize, &samples)
+}
+
+/// Like `Sound::from_samples`, except that only the first two channels
+/// of surround sound are kept
+fn from_interleaved(rate: u32, channels: usize, samples: &[f32]) -> Result<Sound> {
+    if channels > 2 {
+        let samples: Vec<f32> = samples
+            .chunks_exact(channels)
+            .flat_map(|frame| frame[..2].to_vec())
+            .collect();
+        Sound::from_samples(rate, 2, &samples)
+    } else {
+        Sound::from_samples(rate, channels as u16, samples)
+    }
+}
+
+#[cfg(feature = "ogg")]
+fn decode_ogg(bytes: &[u8]) -> Result<Sound> {
+    use lewton::inside_ogg::OggStreamReader;
+    let mut reader = OggStreamReader::new(std::io::Cursor::new(bytes))?;
+    let rate = reader.ident_hdr.audio_sample_rate;
+    let channels = reader.ident_hdr.audio_channels as usize;
+    let mut samples = Vec::new();
+    while let Some(packet) = reader.read_dec_packet_itl()? {
+        samples.extend(packet.into_iter().map(|s| s as f32 / 32768.0));
+    }
+    from_interleaved(rate, channels, &samples)
+}
+
+#[cfg(feature = "ogg")]
+fn stream_ogg(bytes: Vec<u8>) -> Result<Sound> {
+    let bytes: Arc<[u8]> = bytes.into();
+    let rate = ogg::header(&bytes)?;
+    let len = match ogg::frame_count(&bytes) {
+        Some(len) => len,
+        None => anyhow::bail!("OGG file has no length"),
+    };
+    Ok(Sound {
+        rate,
+        len,
+        data: Data::Ogg(bytes),
+    })
+}
+
+#[cfg(not(feature = "ogg"))]
+fn decode_ogg(_bytes: &[u8]) -> Result<Sound> {
+    Err(anyhow::anyhow!(
+        "OGG sounds require the `ogg` feature to be enabled"
+    ))
+}
+
+#[cfg(not(feature = "ogg"))]
+fn stream_ogg(bytes: Vec<u8>) -> Result<Sound> {
+    decode_ogg(&bytes)
+}
+
+#[cfg(feature = "ogg")]
+mod ogg {
+    use crate::anyhow::Error;
+    use crate::anyhow::Result;
+    use lewton::inside_ogg::OggStreamReader;
+    use std::collections::VecDeque;
+    use std::convert::TryInto;
+    use std::io::Cursor;
+    use std::sync::mpsc::sync_channel;
+    use std::sync::mpsc::Receiver;
+    use std::sync::mpsc::SyncSender;
+    use std::sync::mpsc::TryRecvError;
+    use std::sync::Arc;
+
+    /// How many packets are decoded ahead of what's playing
+    /// (usually a few hundred to a few thousand frames each)
+    const PACKETS_AHEAD: usize = 64;
+
+    /// Plays an OGG Vorbis file that is decoded on its own thread
+    ///
+    /// The decoder thread stays ahead of playback, so the mixer only
+    /// ever takes frames that are already decoded, and never waits
+    /// for them. It decodes the file over and over (for looping),
+    /// until the stream is dropped.
+    pub(crate) struct OggStream {
+        chunks: Receiver<Chunk>,
+
+        /// Decoded passes through the file, the first of which is playing
+        passes: VecDeque<Pass>,
+
+        /// Kept around for interpolating across the end of a loop
+        first: [f32; 2],
+    }
+
+    /// What the decoder thread sends
+    pub(crate) enum Chunk {
+        Frames(Vec<[f32; 2]>),
+
+        /// Every frame of the file has been sent, and
+        /// the next frames start over from the beginning
+        End,
+
+        Failed(Error),
+    }
+
+    #[derive(Default)]
+    struct Pass {
+        /// Decoded frames, the first of which is frame number `start`
+        frames: VecDeque<[f32; 2]>,
+        start: usize,
+        done: bool,
+    }
+
+    enum Frame {
+        Ready([f32; 2]),
+        End,
+
+        /// Not decoded yet
+        Pending,
+    }
+
+    impl OggStream {
+        /// Starts decoding, and waits for the first frames
+        pub fn new(bytes: Arc<[u8]>) -> Result<Self> {
+            header(&bytes)?;
+            let (sender, chunks) = sync_channel(PACKETS_AHEAD);
+            std::thread::spawn(move || decode_ahead(bytes, sender));
+            Self::from_chunks(chunks)
+        }
+
+        pub(crate) fn from_chunks(chunks: Receiver<Chunk>) -> Result<Self> {
+            let first = match chunks.recv() {
+                Ok(Chunk::Frames(frames)) => frames,
+                Ok(Chunk::Failed(error)) => return Err(error),
+                Ok(Chunk::End) | Err(_) => anyhow::bail!("OGG file has no frames"),
+            };
+            let mut stream = Self {
+                chunks,
+                passes: VecDeque::new(),
+                first: first[0],
+            };
+            stream.passes.push_back(Pass {
+                frames: first.into(),
+                ..Pass::default()
+            });
+            Ok(stream)
+        }
+
+        /// Returns frame `i` and the one after it, which wraps around
+        /// to the first frame when looping
+        ///
+        /// Frames before `i` are thrown away, so asking for an earlier
+        /// frame moves on to the next pass through the file. Frames that
+        /// aren't decoded yet are silent.
+        pub fn pair(&mut self, i: usize, looping: bool) -> Result<([f32; 2], [f32; 2])> {
+            if i < self.passes[0].start {
+                while self.passes.len() < 2 {
+                    if !self.receive()? {
+                        return Ok(([0.0; 2], [0.0; 2]));
+                    }
+                }
+                self.passes.pop_front();
+            }
+            let pass = &mut self.passes[0];
+            let played = (i - pass.start).min(pass.frames.len());
+            pass.frames.drain(..played);
+            pass.start += played;
+
+            let a = match self.frame(i)? {
+                Frame::Ready(a) => a,
+                Frame::End | Frame::Pending => [0.0; 2],
+            };
+            let b = match self.frame(i + 1)? {
+                Frame::Ready(b) => b,
+                Frame::End if looping => self.first,
+                Frame::End | Frame::Pending => a,
+            };
+            Ok((a, b))
+        }
+
+        /// Looks up frame `i` of the playing pass,
+        /// taking whatever the decoder has sent so far
+        fn frame(&mut self, i: usize) -> Result<Frame> {
+            loop {
+                let pass = &self.passes[0];
+                if let Some(frame) = pass.frames.get(i - pass.start) {
+                    return Ok(Frame::Ready(*frame));
+                }
+                if pass.done {
+                    return Ok(Frame::End);
+                }
+                if !self.receive()? {
+                    return Ok(Frame::Pending);
+                }
+            }
+        }
+
+        /// Takes the next chunk from the decoder without waiting,
+        /// and returns false if there isn't one yet
+        fn receive(&mut self) -> Result<bool> {
+            let chunk = match self.chunks.try_recv() {
+                Ok(chunk) => chunk,
+                Err(TryRecvError::Empty) => return Ok(false),
+                Err(TryRecvError::Disconnected) => anyhow::bail!("OGG decoder stopped"),
+            };
+            // there's always at least the playing pass
+            if self.passes[self.passes.len() - 1].done {
+                self.passes.push_back(Pass::default());
+            }
+            let pass = self.passes.back_mut().unwrap();
+            match chunk {
+                Chunk::Frames(frames) => pass.frames.extend(frames),
+                Chunk::End => pass.done = true,
+                Chunk::Failed(error) => return Err(error),
+            }
+            Ok(true)
+        }
+    }
+
+    /// Decodes the file over and over, until the stream is dropped
+    fn decode_ahead(bytes: Arc<[u8]>, chunks: SyncSender<Chunk>) {
+        loop {
+            match decode_pass(&bytes, &chunks) {
+                Ok(true) => {}
+                Ok(false) => return,
+                Err(error) => {
+                    let _ = chunks.send(Chunk::Failed(error));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Sends every frame of the file, followed by an End,
+    /// and returns false if the stream was dropped
+    fn decode_pass(bytes: &Arc<[u8]>, chunks: &SyncSender<Chunk>) -> Result<bool> {
+        let mut reader = OggStreamReader::new(Cursor::new(bytes.clone()))?;
+        let channels = reader.ident_hdr.audio_channels as usize;
+        while let Some(packet) = reader.read_dec_packet_itl()? {
+            let frames: Vec<_> = packet
+                .chunks_exact(channels)
+                .map(|frame| {
+                    let left = frame[0] as f32 / 32768.0;
+                    let right = frame[channels.min(2) - 1] as f32 / 32768.0;
+                    [left, right]
+                })
+                .collect();
+            if !frames.is_empty() && chunks.send(Chunk::Frames(frames)).is_err() {
+                return Ok(false);
+            }
+        }
+        Ok(chunks.send(Chunk::End).is_ok())
+    }
+
+    /// Reads the header of an OGG Vorbis file, and returns its rate
+    pub(crate) fn header(bytes: &Arc<[u8]>) -> Result<u32> {
+        let reader = OggStreamReader::new(Cursor::new(bytes.clone()))?;
+        if reader.ident_hdr.audio_channels == 0 {
+            anyhow::bail!("OGG file has no channels");
+        }
+        Ok(reader.ident_hdr.audio_sample_rate)
+    }
+
+    /// Returns the number of frames in an OGG Vorbis file, which is the
+    /// granule position of its last page
+    pub(crate) fn frame_count(bytes: &[u8]) -> Option<usize> {
+        let page = bytes.windows(5).rposition(|w| w == b"OggS\0")?;
+        let granule = bytes.get(page + 6..page + 14)?;
+        Some(u64::from_le_bytes(granule.try_into().ok()?) as usize)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn frames(values: &[f32]) -> Chunk {
+            Chunk::Frames(values.iter().map(|v| [*v, -*v]).collect())
+        }
+
+        fn left(pair: ([f32; 2], [f32; 2])) -> [f32; 2] {
+            [(pair.0)[0], (pair.1)[0]]
+        }
+
+        #[test]
+        fn plays_what_was_decoded_and_is_silent_past_it() {
+            let (sender, chunks) = sync_channel(PACKETS_AHEAD);
+            sender.send(frames(&[0.1, 0.2])).unwrap();
+            sender.send(frames(&[0.3])).unwrap();
+            let mut stream = OggStream::from_chunks(chunks).unwrap();
+            assert_eq!(stream.pair(0, false).unwrap(), ([0.1, -0.1], [0.2, -0.2]));
+            assert_eq!(left(stream.pair(1, false).unwrap()), [0.2, 0.3]);
+            // the decoder hasn't gotten further yet
+            assert_eq!(left(stream.pair(2, false).unwrap()), [0.3, 0.3]);
+            assert_eq!(left(stream.pair(3, false).unwrap()), [0.0, 0.0]);
+            sender.send(frames(&[0.4, 0.5])).unwrap();
+            assert_eq!(left(stream.pair(4, false).unwrap()), [0.5, 0.5]);
+        }
+
+        #[test]
+        fn loops_into_the_next_pass() {
+            let (sender, chunks) = sync_channel(PACKETS_AHEAD);
+            for _ in 0..2 {
+                sender.send(frames(&[0.1, 0.2, 0.3])).unwrap();
+                sender.send(Chunk::End).unwrap();
+            }
+            let mut stream = OggStream::from_chunks(chunks).unwrap();
+            assert_eq!(left(stream.pair(1, true).unwrap()), [0.2, 0.3]);
+            assert_eq!(left(stream.pair(2, true).unwrap()), [0.3, 0.1]);
+            assert_eq!(left(stream.pair(2, false).unwrap()), [0.3, 0.3]);
+            assert_eq!(left(stream.pair(0, true).unwrap()), [0.1, 0.2]);
+            assert_eq!(left(stream.pair(2, true).unwrap()), [0.3, 0.1]);
+            // the third pass hasn't been decoded yet
+            assert_eq!(left(stream.pair(0, true).unwrap()), [0.0, 0.0]);
+            sender.send(frames(&[0.1, 0.2, 0.3])).unwrap();
+            assert_eq!(left(stream.pair(1, true).unwrap()), [0.2, 0.3]);
+        }
+
+        #[test]
+        fn decoding_errors_are_passed_on() {
+            let (sender, chunks) = sync_channel(PACKETS_AHEAD);
+            sender.send(frames(&[0.1])).unwrap();
+            sender
+                .send(Chunk::Failed(anyhow::anyhow!("broken")))
+                .unwrap();
+            let mut stream = OggStream::from_chunks(chunks).unwrap();
+            assert!(stream.pair(1, false).is_err());
+            drop(sender);
+            assert!(stream.pair(2, false).is_err());
+
+            let (sender, chunks) = sync_channel(PACKETS_AHEAD);
+            sender.send(Chunk::End).unwrap();
+            assert!(OggStream::from_chunks(chunks).is_err());
+        }
+    }
+}
@@ -0,0 +1,397 @@
+use crate::SpriteBatch;
+
+/// A view into a 2D world that can be panned, zoomed and rotated
+///
+/// a2d only has the single global transform set with
+/// `AppContext::set_scale`, so a camera is applied to a batch by
+/// placing each of its instances on the screen (see `Camera2D::apply`).
+/// Since this is done per batch, the world and e.g. a HUD can be drawn
+/// with different cameras (or no camera at all) in the same call
+/// to `RenderContext::render`.
+///
+/// The camera's position is the point in the world that is shown at
+/// the center of the screen. A zoom of 2 makes everything twice
+/// as large.
+///
+/// NOTE, a2d cannot rotate individual sprites, so rotating the camera
+/// only rotates where sprites are placed (and the conversions between
+/// screen and world coordinates), and each sprite is still drawn upright.
+#[derive(Debug, Clone)]
+pub struct Camera2D {
+    screen_size: [f32; 2],
+    position: [f32; 2],
+    zoom: f32,
+    rotation: f32,
+
+    target: Option<[f32; 2]>,
+    smoothing: f32,
+    dead_zone: [f32; 2],
+    bounds: Option<[[f32; 2]; 2]>,
+
+    shake_intensity: f32,
+    shake_duration: f32,
+    shake_remaining: f32,
+    shake_offset: [f32; 2],
+    rng: u32,
+}
+
+impl Camera2D {
+    /// Creates a camera for a screen of the given size,
+    /// which should usually be `AppContext::scale`
+    pub fn new(screen_size: [f32; 2]) -> Self {
+        Self {
+            screen_size,
+            position: [screen_size[0] / 2.0, screen_size[1] / 2.0],
+            zoom: 1.0,
+            rotation: 0.0,
+            target: None,
+            smoothing: 0.0,
+            dead_zone: [0.0, 0.0],
+            bounds: None,
+            shake_intensity: 0.0,
+            shake_duration: 0.0,
+            shake_remaining: 0.0,
+            shake_offset: [0.0, 0.0],
+            rng: 0x9e37_79b9,
+        }
+    }
+
+    pub fn screen_size(&self) -> [f32; 2] {
+        self.screen_size
+    }
+
+    /// Should be called when the scale changes (e.g. from `Game::resize`)
+    pub fn set_screen_size(&mut self, screen_size: [f32; 2]) {
+        self.screen_size = screen_size;
+        self.clamp_to_bounds();
+    }
+
+    /// Returns the point in the world shown at the center of the screen
+    pub fn position(&self) -> [f32; 2] {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: [f32; 2]) {
+        self.position = position;
+        self.clamp_to_bounds();
+    }
+
+    /// Moves the camera by the given amount in world coordinates
+    pub fn pan(&mut self, delta: [f32; 2]) {
+        self.set_position([self.position[0] + delta[0], self.position[1] + delta[1]]);
+    }
+
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    /// Sets how much the world is magnified
+    ///
+    /// By default, set to 1
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom.max(f32::EPSILON);
+        self.clamp_to_bounds();
+    }
+
+    /// Returns the rotation of the camera in radians
+    pub fn rotation(&self) -> f32 {
+        self.rotation
+    }
+
+    /// Sets the rotation of the camera in radians
+    ///
+    /// Positive values rotate the camera clockwise, which makes
+    /// the world appear to rotate counter-clockwise
+    pub fn set_rotation(&mut self, rotation: f32) {
+        self.rotation = rotation;
+    }
+
+    /// Sets a point in the world the camera moves towards in `update`,
+    /// or None to stop following
+    pub fn set_target(&mut self, target: Option<[f32; 2]>) {
+        self.target = target;
+    }
+
+    pub fn target(&self) -> Option<[f32; 2]> {
+        self.target
+    }
+
+    /// Sets how quickly the camera catches up to its target
+    ///
+    /// With a smoothing of 0, the camera snaps to the target immediately.
+    /// Otherwise, roughly this many seconds pass before the camera
+    /// covers most (about 63%) of the distance to the target.
+    ///
+    /// By default, set to 0
+    pub fn set_smoothing(&mut self, smoothing: f32) {
+        self.smoothing = smoothing.max(0.0);
+    }
+
+    /// Sets the [width, height] of a box in world coordinates
+    /// around the camera's position in which the target can move
+    /// without the camera following it
+    ///
+    /// By default, set to [0, 0]
+    pub fn set_dead_zone(&mut self, dead_zone: [f32; 2]) {
+        self.dead_zone = dead_zone;
+    }
+
+    /// Sets the [upper_left, lower_right] corners in world coordinates
+    /// that the camera's view is kept inside of, or None to let
+    /// the camera go anywhere
+    ///
+    /// If the bounds are smaller than the view, the view
+    /// is centered on the bounds instead.
+    ///
+    /// Bounds are enforced on the unrotated view.
+    pub fn set_bounds(&mut self, bounds: Option<[[f32; 2]; 2]>) {
+        self.bounds = bounds;
+        self.clamp_to_bounds();
+    }
+
+    /// Shakes the camera by up to `intensity` world units in each
+    /// direction, fading out over `duration` seconds
+    pub fn shake(&mut self, intensity: f32, duration: f32) {
+        self.shake_intensity = intensity;
+        self.shake_duration = duration;
+        self.shake_remaining = duration;
+    }
+
+    /// Moves the camera towards its target and advances screen shake
+    ///
+    /// Should be called once per update, usually with `AppContext::delta`
+    pub fn update(&mut self, delta: f64) {
+        let delta = delta as f32;
+
+        if let Some(target) = self.target {
+            let mut goal = self.position;
+            for i in 0..2 {
+                let half = self.dead_zone[i] / 2.0;
+                if target[i] < goal[i] - half {
+                    goal[i] = target[i] + half;
+                } else if target[i] > goal[i] + half {
+                    goal[i] = target[i] - half;
+                }
+            }
+            let t = if self.smoothing > 0.0 {
+                1.0 - (-delta / self.smoothing).exp()
+            } else {
+                1.0
+            };
+            self.position = [
+                self.position[0] + (goal[0] - self.position[0]) * t,
+                self.position[1] + (goal[1] - self.position[1]) * t,
+            ];
+            self.clamp_to_bounds();
+        }
+
+        if self.shake_remaining > 0.0 {
+            self.shake_remaining = (self.shake_remaining - delta).max(0.0);
+            let strength = self.shake_intensity * self.shake_remaining / self.shake_duration;
+            self.shake_offset = [self.next_random() * strength, self.next_random() * strength];
+        } else {
+            self.shake_offset = [0.0, 0.0];
+        }
+    }
+
+    /// Returns the [upper_left, lower_right] corners of the part
+    /// of the world that is visible, ignoring rotation and shake
+    pub fn visible_rect(&self) -> [[f32; 2]; 2] {
+        let [hw, hh] = self.half_view();
+        let [x, y] = self.position;
+        [[x - hw, y - hh], [x + hw, y + hh]]
+    }
+
+    /// Converts a point in the world to a point on the screen
+    pub fn world_to_screen(&self, pos: [f32; 2]) -> [f32; 2] {
+        let [cx, cy] = self.view_center();
+        let [x, y] = rotate([pos[0] - cx, pos[1] - cy], -self.rotation);
+        [
+            x * self.zoom + self.screen_size[0] / 2.0,
+            y * self.zoom + self.screen_size[1] / 2.0,
+        ]
+    }
+
+    /// Converts a point on the screen to a point in the world
+    ///
+    /// This can be used with e.g. the mouse position passed to
+    /// `Game::mouse_moved` to find what is under the mouse
+    pub fn screen_to_world(&self, pos: [f32; 2]) -> [f32; 2] {
+        let [cx, cy] = self.view_center();
+        let [x, y] = rotate(
+            [
+                (pos[0] - self.screen_size[0] / 2.0) / self.zoom,
+                (pos[1] - self.screen_size[1] / 2.0) / self.zoom,
+            ],
+            self.rotation,
+        );
+        [x + cx, y + cy]
+    }
+
+    /// Converts a [upper_left, lower_right] rect in the world to
+    /// where it should be drawn on the screen
+    ///
+    /// The center of the rect is rotated with the camera, but the rect
+    /// itself stays axis aligned.
+    pub fn world_rect_to_screen(&self, rect: [[f32; 2]; 2]) -> [[f32; 2]; 2] {
+        let [[x1, y1], [x2, y2]] = rect;
+        let [cx, cy] = self.world_to_screen([(x1 + x2) / 2.0, (y1 + y2) / 2.0]);
+        let hw = (x2 - x1) / 2.0 * self.zoom;
+        let hh = (y2 - y1) / 2.0 * self.zoom;
+        [[cx - hw, cy - hh], [cx + hw, cy + hh]]
+    }
+
+    /// Places the instances of a batch on the screen as seen
+    /// through this camera
+    ///
+    /// `dests` are the [upper_left, lower_right] rects of each instance
+    /// in world coordinates, in the same order as the instances were
+    /// added to the batch. This should be called whenever the camera or
+    /// any of the rects change, before the batch is rendered.
+    ///
+    /// Rects past the end of the batch are ignored.
+    pub fn apply(&self, batch: &mut SpriteBatch, dests: &[[[f32; 2]; 2]]) {
+        for (dest, i) in dests.iter().zip(0..batch.len()) {
+            batch.get_mut(i).set_dest(self.world_rect_to_screen(*dest));
+        }
+    }
+
+    fn view_center(&self) -> [f32; 2] {
+        [
+            self.position[0] + self.shake_offset[0],
+            self.position[1] + self.shake_offset[1],
+        ]
+    }
+
+    fn half_view(&self) -> [f32; 2] {
+        [
+            self.screen_size[0] / 2.0 / self.zoom,
+            self.screen_size[1] / 2.0 / self.zoom,
+        ]
+    }
+
+    fn clamp_to_bounds(&mut self) {
+        if let Some([lo, hi]) = self.bounds {
+            let half = self.half_view();
+            for i in 0..2 {
+                self.position[i] = if hi[i] - lo[i] <= half[i] * 2.0 {
+                    (lo[i] + hi[i]) / 2.0
+                } else {
+                    self.position[i].clamp(lo[i] + half[i], hi[i] - half[i])
+                };
+            }
+        }
+    }
+
+    /// Returns a pseudo random number between -1 and 1 (xorshift)
+    fn next_random(&mut self) -> f32 {
+        let mut x = self.rng;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.rng = x;
+        (x as f32 / u32::MAX as f32) * 2.0 - 1.0
+    }
+}
+
+fn rotate(pos: [f32; 2], angle: f32) -> [f32; 2] {
+    let (sin, cos) = angle.sin_cos();
+    [pos[0] * cos - pos[1] * sin, pos[0] * sin + pos[1] * cos]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Instance;
+    use crate::SpriteSheet;
+
+    fn assert_near(a: [f32; 2], b: [f32; 2]) {
+        assert!(
+            (a[0] - b[0]).abs() < 1e-3 && (a[1] - b[1]).abs() < 1e-3,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn follow_snaps_without_smoothing() {
+        let mut camera = Camera2D::new([100.0, 100.0]);
+        camera.set_target(Some([500.0, 300.0]));
+        camera.update(1.0 / 60.0);
+        assert_near(camera.position(), [500.0, 300.0]);
+    }
+
+    #[test]
+    fn follow_with_smoothing_covers_most_of_the_distance() {
+        let mut camera = Camera2D::new([100.0, 100.0]);
+        camera.set_position([0.0, 0.0]);
+        camera.set_smoothing(1.0);
+        camera.set_target(Some([100.0, 0.0]));
+        camera.update(1.0);
+        let [x, _] = camera.position();
+        assert!((x - 63.2).abs() < 0.1, "{}", x);
+    }
+
+    #[test]
+    fn dead_zone_only_follows_past_its_edge() {
+        let mut camera = Camera2D::new([100.0, 100.0]);
+        camera.set_position([0.0, 0.0]);
+        camera.set_dead_zone([20.0, 20.0]);
+
+        camera.set_target(Some([5.0, -8.0]));
+        camera.update(1.0);
+        assert_near(camera.position(), [0.0, 0.0]);
+
+        camera.set_target(Some([30.0, -8.0]));
+        camera.update(1.0);
+        assert_near(camera.position(), [20.0, 0.0]);
+    }
+
+    #[test]
+    fn bounds_keep_the_view_inside() {
+        let mut camera = Camera2D::new([100.0, 100.0]);
+        camera.set_bounds(Some([[0.0, 0.0], [1000.0, 1000.0]]));
+        camera.set_position([-500.0, 2000.0]);
+        assert_near(camera.position(), [50.0, 950.0]);
+        assert_eq!(camera.visible_rect(), [[0.0, 900.0], [100.0, 1000.0]]);
+
+        camera.set_zoom(2.0);
+        camera.set_position([0.0, 0.0]);
+        assert_near(camera.position(), [25.0, 25.0]);
+    }
+
+    #[test]
+    fn bounds_smaller_than_the_view_are_centered() {
+        let mut camera = Camera2D::new([100.0, 100.0]);
+        camera.set_bounds(Some([[0.0, 0.0], [40.0, 200.0]]));
+        camera.set_position([500.0, 500.0]);
+        assert_near(camera.position(), [20.0, 150.0]);
+    }
+
+    #[test]
+    fn screen_and_world_coordinates_round_trip() {
+        let mut camera = Camera2D::new([200.0, 100.0]);
+        camera.set_position([10.0, 20.0]);
+        camera.set_zoom(2.0);
+        camera.set_rotation(0.5);
+        assert_near(camera.world_to_screen([10.0, 20.0]), [100.0, 50.0]);
+        let world = camera.screen_to_world([30.0, 70.0]);
+        assert_near(camera.world_to_screen(world), [30.0, 70.0]);
+    }
+
+    #[test]
+    fn apply_ignores_rects_past_the_end_of_the_batch() {
+        let sheet = SpriteSheet::from_color(None, [1.0, 1.0, 1.0]).unwrap();
+        let mut batch = SpriteBatch::new(sheet);
+        batch.add(Instance::builder().build());
+        let mut camera = Camera2D::new([100.0, 100.0]);
+        camera.set_position([0.0, 0.0]);
+        camera.apply(
+            &mut batch,
+            &[[[0.0, 0.0], [10.0, 10.0]], [[20.0, 20.0], [30.0, 30.0]]],
+        );
+        assert_eq!(batch.len(), 1);
+        assert_eq!(batch.get(0).dest(), [[50.0, 50.0], [60.0, 60.0]]);
+    }
+}
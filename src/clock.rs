@@ -0,0 +1,161 @@
+use std::time::Instant;
+
+/// How much weight a new frame's duration has when
+/// computing the smoothed frames per second
+const FPS_SMOOTHING: f64 = 0.1;
+
+/// Keeps track of timing information that is made available
+/// through the AppContext
+pub(crate) struct Clock {
+    start: Instant,
+    now: Instant,
+    last_update: Option<Instant>,
+    last_frame: Option<Instant>,
+    delta: f64,
+    update_count: u64,
+    frame_count: u64,
+    frame_time: f64,
+}
+
+impl Clock {
+    pub fn new(start: Instant) -> Self {
+        Self {
+            start,
+            now: start,
+            last_update: None,
+            last_frame: None,
+            delta: 0.0,
+            update_count: 0,
+            frame_count: 0,
+            frame_time: 0.0,
+        }
+    }
+
+    /// Should be called right before each call to `Game::update`
+    ///
+    /// If updates are done at a fixed rate, the step size should be
+    /// passed, so that the delta seen by the game is always exactly
+    /// one step
+    pub fn update(&mut self, now: Instant, step: Option<f64>) {
+        self.now = now;
+        let last = self.last_update.replace(now);
+        self.delta = match step {
+            Some(step) => step,
+            None => match last {
+                Some(last) => now.duration_since(last).as_secs_f64(),
+                None => 0.0,
+            },
+        };
+        self.update_count += 1;
+    }
+
+    /// Should be called instead of `update` while updates are paused,
+    /// so that the time spent paused doesn't end up in the next delta
+    pub fn pause(&mut self) {
+        self.last_update = None;
+    }
+
+    /// Should be called right before each call to `Game::render`
+    pub fn frame(&mut self, now: Instant) {
+        self.now = now;
+        if let Some(last) = self.last_frame.replace(now) {
+            let duration = now.duration_since(last).as_secs_f64();
+            self.frame_time = if self.frame_time == 0.0 {
+                duration
+            } else {
+                self.frame_time * (1.0 - FPS_SMOOTHING) + duration * FPS_SMOOTHING
+            };
+        }
+        self.frame_count += 1;
+    }
+
+    pub fn delta(&self) -> f64 {
+        self.delta
+    }
+
+    pub fn time(&self) -> f64 {
+        self.now.duration_since(self.start).as_secs_f64()
+    }
+
+    pub fn update_count(&self) -> u64 {
+        self.update_count
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    pub fn fps(&self) -> f64 {
+        if self.frame_time == 0.0 {
+            0.0
+        } else {
+            1.0 / self.frame_time
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn after(start: Instant, secs: f64) -> Instant {
+        start + Duration::from_secs_f64(secs)
+    }
+
+    #[test]
+    fn variable_delta_is_the_time_between_updates() {
+        let start = Instant::now();
+        let mut clock = Clock::new(start);
+        clock.update(start, None);
+        assert_eq!(clock.delta(), 0.0);
+        clock.update(after(start, 0.25), None);
+        assert!((clock.delta() - 0.25).abs() < 1e-9);
+        assert!((clock.time() - 0.25).abs() < 1e-9);
+        assert_eq!(clock.update_count(), 2);
+    }
+
+    #[test]
+    fn fixed_delta_is_always_one_step() {
+        let start = Instant::now();
+        let mut clock = Clock::new(start);
+        clock.update(start, Some(0.1));
+        assert_eq!(clock.delta(), 0.1);
+        clock.update(after(start, 0.37), Some(0.1));
+        assert_eq!(clock.delta(), 0.1);
+    }
+
+    #[test]
+    fn pause_drops_the_paused_time() {
+        let start = Instant::now();
+        let mut clock = Clock::new(start);
+        clock.update(start, None);
+        clock.pause();
+        clock.update(after(start, 60.0), None);
+        assert_eq!(clock.delta(), 0.0);
+        clock.update(after(start, 60.5), None);
+        assert!((clock.delta() - 0.5).abs() < 1e-9);
+
+        // the game's time keeps going while paused though
+        assert!((clock.time() - 60.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fps_is_smoothed() {
+        let start = Instant::now();
+        let mut clock = Clock::new(start);
+        assert_eq!(clock.fps(), 0.0);
+        clock.frame(start);
+        assert_eq!(clock.fps(), 0.0);
+
+        // the first frame's duration is taken as is
+        clock.frame(after(start, 0.1));
+        assert!((clock.fps() - 10.0).abs() < 1e-6);
+
+        // after that, a single slow frame only moves it a little
+        clock.frame(after(start, 0.3));
+        let frame_time = 0.1 * (1.0 - FPS_SMOOTHING) + 0.2 * FPS_SMOOTHING;
+        assert!((clock.fps() - 1.0 / frame_time).abs() < 1e-6);
+        assert_eq!(clock.frame_count(), 3);
+    }
+}
@@ -1,7 +1,35 @@
 use crate::a2d::Graphics2D;
+use crate::anyhow::bail;
+use crate::anyhow::Context;
 use crate::anyhow::Result;
+use crate::asset::Data;
+use crate::asset::Handle;
+use crate::clock::Clock;
+use crate::cursor::CursorState;
+use crate::headless::Recorder;
+use crate::rumble::Rumble;
+use crate::sprite;
+use crate::viewport::Viewport;
 use crate::winit::event_loop::ControlFlow;
+use crate::winit::window::Window as WinitWindow;
+use crate::ActionMap;
+use crate::Assets;
+use crate::Audio;
+use crate::AxisConfig;
 use crate::Color;
+use crate::CursorGrab;
+use crate::CursorIcon;
+use crate::CustomCursor;
+use crate::DeviceId;
+use crate::Font;
+use crate::Fullscreen;
+use crate::GamepadInfo;
+use crate::GamepadMapping;
+use crate::GamepadUuid;
+use crate::InputState;
+use crate::MappingRecorder;
+use crate::MappingTarget;
+use crate::Sound;
 use crate::SpriteBatch;
 use crate::SpriteSheet;
 use crate::TextGrid;
@@ -12,13 +40,325 @@ use std::rc::Rc;
 /// with yagl about App related things (e.g. requesting to exit)
 pub struct AppContext<'a> {
     pub(crate) control_flow: &'a mut ControlFlow,
-    pub(crate) graphics: &'a mut Graphics2D,
+    pub(crate) backend: Backend<'a>,
+    pub(crate) clock: &'a mut Clock,
+    pub(crate) input: &'a mut InputState,
+    pub(crate) actions: &'a mut ActionMap,
+    pub(crate) assets: &'a mut Assets,
+    pub(crate) audio: &'a mut Audio,
+    pub(crate) rumble: &'a Rumble,
+    pub(crate) cursor: &'a mut CursorState,
+    pub(crate) viewport: &'a mut Viewport,
+}
+
+/// What the AppContext ultimately draws with
+pub(crate) enum Backend<'a> {
+    Window(&'a WinitWindow, &'a mut Graphics2D),
+    Headless(&'a mut Recorder),
 }
 
 impl<'a> AppContext<'a> {
     pub fn exit(&mut self) {
         *self.control_flow = ControlFlow::Exit;
     }
+
+    /// Returns true if the game is running without a window
+    /// (see `Headless`)
+    pub fn is_headless(&self) -> bool {
+        match self.backend {
+            Backend::Window(..) => false,
+            Backend::Headless(_) => true,
+        }
+    }
+
+    /// Returns the current state of all input devices,
+    /// for games that prefer polling input over handling
+    /// it in callbacks
+    pub fn input(&self) -> &InputState {
+        self.input
+    }
+
+    /// Returns information about the given gamepad (e.g. its name),
+    /// or None if it is not connected
+    pub fn gamepad_info(&self, dev: DeviceId) -> Option<&GamepadInfo> {
+        self.input.gamepad_info(dev)
+    }
+
+    /// Returns the IDs of all gamepads that are currently connected
+    pub fn connected_gamepads(&self) -> impl Iterator<Item = DeviceId> + '_ {
+        self.input.connected_gamepads()
+    }
+
+    /// Returns the player slot of the given gamepad
+    /// (see `InputState::player_slot`)
+    pub fn player_slot(&self, dev: DeviceId) -> Option<usize> {
+        self.input.player_slot(dev)
+    }
+
+    /// Returns the gamepad in the given player slot, if it's connected
+    pub fn player_gamepad(&self, slot: usize) -> Option<DeviceId> {
+        self.input.player_gamepad(slot)
+    }
+
+    /// Frees the given player slot, so that the next gamepad to
+    /// connect can take it (e.g. when a player leaves the game)
+    ///
+    /// If a gamepad is connected in the slot, it loses its slot
+    /// until it reconnects.
+    pub fn release_player_slot(&mut self, slot: usize) {
+        self.input.release_player_slot(slot);
+    }
+
+    /// Sets the custom mapping for gamepads with the mapping's UUID,
+    /// replacing any previous one
+    pub fn set_gamepad_mapping(&mut self, mapping: GamepadMapping) {
+        self.input.set_gamepad_mapping(mapping);
+    }
+
+    pub fn remove_gamepad_mapping(&mut self, uuid: GamepadUuid) {
+        self.input.remove_gamepad_mapping(uuid);
+    }
+
+    /// Starts recording a mapping for the given gamepad, asking for
+    /// the given targets in order (see `MappingRecorder::STANDARD_TARGETS`)
+    ///
+    /// This replaces any recording already in progress. Fails if the
+    /// gamepad isn't connected.
+    pub fn record_gamepad_mapping(
+        &mut self,
+        dev: DeviceId,
+        targets: &[MappingTarget],
+    ) -> Result<()> {
+        let info = match self.input.gamepad_info(dev) {
+            Some(info) => info,
+            None => bail!("Gamepad {:?} is not connected", dev),
+        };
+        let recorder = MappingRecorder::new(dev, info, targets.to_vec());
+        *self.input.mapping_recorder_mut() = Some(recorder);
+        Ok(())
+    }
+
+    /// Returns the mapping recorder, if a mapping is being recorded
+    pub fn mapping_recorder(&self) -> Option<&MappingRecorder> {
+        self.input.mapping_recorder()
+    }
+
+    /// Skips the target the mapping recorder is currently asking for
+    pub fn skip_mapping_target(&mut self) {
+        if let Some(recorder) = self.input.mapping_recorder_mut() {
+            recorder.skip();
+        }
+    }
+
+    /// Stops recording, and starts using the recorded mapping
+    ///
+    /// The mapping is returned so that it can be saved (e.g. with
+    /// `to_string`) and passed to `Options::custom_gamepad_mappings`
+    /// the next time the game starts
+    pub fn finish_gamepad_mapping(&mut self) -> Option<GamepadMapping> {
+        let mapping = self.input.mapping_recorder_mut().take()?.into_mapping();
+        self.input.set_gamepad_mapping(mapping.clone());
+        Some(mapping)
+    }
+
+    /// Stops recording, throwing away the recorded mapping
+    pub fn cancel_gamepad_mapping(&mut self) {
+        self.input.mapping_recorder_mut().take();
+    }
+
+    /// Changes how raw gamepad axis values are processed,
+    /// overriding `Options::axis_config`
+    pub fn set_axis_config(&mut self, axis_config: AxisConfig) {
+        self.input.set_axis_config(axis_config);
+    }
+
+    /// Starts rumbling the given gamepad, replacing any rumble
+    /// already in progress on it
+    ///
+    /// `strong` and `weak` are the magnitudes of the strong (low frequency)
+    /// and weak (high frequency) motors, between 0 and 1, and `duration`
+    /// is in seconds. A duration that isn't positive stops the rumble
+    /// instead (see `stop_rumble`).
+    ///
+    /// Does nothing for devices that don't support force feedback,
+    /// when gamepads are disabled (see `Options::enable_gamepad`),
+    /// or when running headless
+    pub fn rumble(&mut self, dev: DeviceId, strong: f32, weak: f32, duration: f64) {
+        self.rumble.start(dev, strong, weak, duration);
+    }
+
+    /// Stops any rumble in progress on the given gamepad
+    pub fn stop_rumble(&mut self, dev: DeviceId) {
+        self.rumble.stop(dev);
+    }
+
+    /// Returns the mapping from inputs to the game's actions
+    /// (see `ActionMap`)
+    pub fn actions(&self) -> &ActionMap {
+        self.actions
+    }
+
+    /// Returns the mapping from inputs to the game's actions,
+    /// so that actions and bindings can be added or changed
+    pub fn actions_mut(&mut self) -> &mut ActionMap {
+        self.actions
+    }
+
+    /// Returns the asset manager, which holds every asset
+    /// loaded so far
+    pub fn assets(&self) -> &Assets {
+        self.assets
+    }
+
+    /// Returns the asset manager, e.g. to change where assets
+    /// are loaded from
+    pub fn assets_mut(&mut self) -> &mut Assets {
+        self.assets
+    }
+
+    /// Returns the audio mixer, for checking e.g. volumes
+    pub fn audio(&self) -> &Audio {
+        self.audio
+    }
+
+    /// Returns the audio mixer, for playing sounds
+    pub fn audio_mut(&mut self) -> &mut Audio {
+        self.audio
+    }
+
+    pub(crate) fn winit_window(&self) -> Option<&'a WinitWindow> {
+        match self.backend {
+            Backend::Window(window, _) => Some(window),
+            Backend::Headless(_) => None,
+        }
+    }
+
+    pub(crate) fn graphics_if_any(&mut self) -> Option<&mut Graphics2D> {
+        match &mut self.backend {
+            Backend::Window(_, graphics) => Some(graphics),
+            Backend::Headless(_) => None,
+        }
+    }
+
+    pub(crate) fn graphics(&mut self) -> Result<&mut Graphics2D> {
+        match &mut self.backend {
+            Backend::Window(_, graphics) => Ok(graphics),
+            Backend::Headless(_) => Err(anyhow::anyhow!(
+                "Graphics resources are not available when running headless"
+            )),
+        }
+    }
+}
+
+/// Window methods
+///
+/// When running headless, these do nothing
+impl<'a> AppContext<'a> {
+    /// Sets the title of the window
+    pub fn set_title(&mut self, title: &str) {
+        if let Backend::Window(window, _) = &self.backend {
+            window.set_title(title);
+        }
+    }
+
+    /// Returns the current fullscreen mode of the window,
+    /// or None if it is not fullscreen
+    pub fn fullscreen(&self) -> Option<Fullscreen> {
+        match &self.backend {
+            Backend::Window(window, _) => Fullscreen::from_winit(window.fullscreen()),
+            Backend::Headless(_) => None,
+        }
+    }
+
+    /// Switches the window to the given fullscreen mode,
+    /// or back to a regular window if None
+    pub fn set_fullscreen(&mut self, fullscreen: Option<Fullscreen>) {
+        if let Backend::Window(window, _) = &self.backend {
+            window.set_fullscreen(fullscreen.and_then(|f| f.to_winit(window)));
+        }
+    }
+
+    pub fn is_cursor_visible(&self) -> bool {
+        self.cursor.visible()
+    }
+
+    /// Shows or hides the mouse cursor while it's over the window
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        let window = self.winit_window();
+        self.cursor.set_visible(window, visible);
+    }
+
+    pub fn cursor_grab(&self) -> CursorGrab {
+        self.cursor.grab()
+    }
+
+    /// Changes how the mouse cursor is kept in the window
+    ///
+    /// Fails if the platform doesn't support grabbing the cursor
+    pub fn set_cursor_grab(&mut self, grab: CursorGrab) -> Result<()> {
+        let window = self.winit_window();
+        self.cursor.set_grab(window, grab)
+    }
+
+    pub fn cursor_icon(&self) -> CursorIcon {
+        self.cursor.icon()
+    }
+
+    /// Changes the image of the system cursor
+    pub fn set_cursor_icon(&mut self, icon: CursorIcon) {
+        let window = self.winit_window();
+        self.cursor.set_icon(window, icon);
+    }
+
+    /// Replaces the system cursor with an image from a sprite sheet,
+    /// or goes back to the system cursor with None
+    pub fn set_custom_cursor(&mut self, cursor: Option<CustomCursor>) {
+        let window = self.winit_window();
+        self.cursor.set_custom(window, cursor);
+    }
+}
+
+/// Timing methods
+impl<'a> AppContext<'a> {
+    /// Returns the time in seconds since the previous call to
+    /// `Game::update`
+    ///
+    /// If `Options::update_rate` is set, this is always
+    /// exactly the length of a single update step.
+    ///
+    /// During the very first update, this is 0
+    /// (unless updates are done at a fixed rate)
+    pub fn delta(&self) -> f64 {
+        self.clock.delta()
+    }
+
+    /// Returns the time in seconds since the game was started,
+    /// as of the start of the current update or frame
+    ///
+    /// This is based on a monotonic clock, so unlike e.g. `SystemTime`,
+    /// it will never go backwards
+    pub fn time(&self) -> f64 {
+        self.clock.time()
+    }
+
+    /// Returns the number of times `Game::update` has been called,
+    /// including the current call if called from inside `update`
+    pub fn update_count(&self) -> u64 {
+        self.clock.update_count()
+    }
+
+    /// Returns the number of times `Game::render` has been called,
+    /// including the current call if called from inside `render`
+    pub fn frame_count(&self) -> u64 {
+        self.clock.frame_count()
+    }
+
+    /// Returns the number of frames rendered per second,
+    /// smoothed over recent frames so that it's readable
+    /// when e.g. displayed on the screen
+    pub fn fps(&self) -> f64 {
+        self.clock.fps()
+    }
 }
 
 /// Graphics methods
@@ -31,22 +371,34 @@ impl<'a> AppContext<'a> {
     ///
     /// When the window is resized, the scale will always be
     /// rescaled to match the default.
-    /// To override this behavior, you should implement Game::resize
+    /// To override this behavior, you should implement Game::resize,
+    /// or set `Options::virtual_size`
     ///
+    /// With a virtual size, this covers the whole window, bars included,
+    /// so it's larger than the virtual screen whenever there are bars
     pub fn scale(&self) -> [f32; 2] {
-        self.graphics.scale()
+        match &self.backend {
+            Backend::Window(_, graphics) => graphics.scale(),
+            Backend::Headless(recorder) => recorder.scale,
+        }
     }
 
     pub fn set_scale(&mut self, scale: [f32; 2]) {
-        self.graphics.set_scale(scale)
+        match &mut self.backend {
+            Backend::Window(_, graphics) => graphics.set_scale(scale),
+            Backend::Headless(recorder) => recorder.scale = scale,
+        }
     }
 
     /// Creates a new sprite sheet from the bytes of some image
     /// file.
     /// The data is ultimately parsed passing to the
     /// `load_from_memory` function in the `image` crate.
+    ///
+    /// When running headless, this and the other methods that create
+    /// sprite sheets return a stand-in sheet (see `SpriteSheet`)
     pub fn new_sheet_from_bytes(&mut self, bytes: &[u8]) -> Result<Rc<SpriteSheet>> {
-        Ok(SpriteSheet::from_bytes(self.graphics, bytes)?)
+        SpriteSheet::from_bytes(self.graphics_if_any(), bytes)
     }
 
     /// Creates a new sprite sheet from explicitly specified colors
@@ -60,18 +412,13 @@ impl<'a> AppContext<'a> {
         C: Into<Color>,
         V: IntoIterator<Item = C>,
     {
-        Ok(SpriteSheet::from_colors(
-            self.graphics,
-            width,
-            height,
-            colors,
-        )?)
+        SpriteSheet::from_colors(self.graphics_if_any(), width, height, colors)
     }
 
     /// Creates a new sprite sheet 1 pixel by 1 pixel wide
     /// with the given color
     pub fn new_sheet_from_color<C: Into<Color>>(&mut self, color: C) -> Result<Rc<SpriteSheet>> {
-        Ok(SpriteSheet::from_color(self.graphics, color)?)
+        SpriteSheet::from_color(self.graphics_if_any(), color)
     }
 
     /// Creates a new sprite sheet from a Vec<u8> of rgba data
@@ -81,16 +428,51 @@ impl<'a> AppContext<'a> {
         height: u32,
         bytes: Vec<u8>,
     ) -> Result<Rc<SpriteSheet>> {
-        Ok(SpriteSheet::from_rgba_bytes(
-            self.graphics,
-            width,
-            height,
-            bytes,
-        )?)
+        SpriteSheet::from_rgba_bytes(self.graphics_if_any(), width, height, bytes)
     }
 
+    /// Loads a sprite sheet from an image file in the asset source
+    /// (see `Assets`)
+    ///
+    /// If the same path was loaded before, the same sheet is returned
+    pub fn load_sheet(&mut self, path: &str) -> Result<Handle<SpriteSheet>> {
+        if let Some(handle) = self.assets.find::<SpriteSheet>(path) {
+            return Ok(handle);
+        }
+        let bytes = self.assets.read(path)?;
+        let sheet = self
+            .new_sheet_from_bytes(&bytes)
+            .with_context(|| format!("Failed to load sprite sheet {:?}", path))?;
+        Ok(self.assets.insert_sheet(path, sheet))
+    }
+
+    /// Loads a file from the asset source as-is (see `Assets::load_data`)
+    pub fn load_data(&mut self, path: &str) -> Result<Handle<Data>> {
+        self.assets.load_data(path)
+    }
+
+    /// Loads a sound from the asset source (see `Assets::load_sound`)
+    pub fn load_sound(&mut self, path: &str) -> Result<Handle<Sound>> {
+        self.assets.load_sound(path)
+    }
+
+    /// Loads a sound that is decoded while it plays
+    /// (see `Assets::load_music`)
+    pub fn load_music(&mut self, path: &str) -> Result<Handle<Sound>> {
+        self.assets.load_music(path)
+    }
+
+    /// Loads a font from the asset source (see `Assets::load_font`)
+    pub fn load_font(&mut self, path: &str) -> Result<Handle<Font>> {
+        self.assets.load_font(path)
+    }
+
+    /// Returns the sheet of the courier font bundled with a2d
+    ///
+    /// Unlike the other sheets, this one can't be created
+    /// when running headless
     pub fn load_courier_font_sheet(&mut self) -> Result<Rc<SpriteSheet>> {
-        Ok(self.graphics.courier_sprite_sheet()?)
+        Ok(SpriteSheet::wrap(self.graphics()?.courier_sprite_sheet()?))
     }
 
     /// Creates a new SpriteBatch from a SpriteSheet
@@ -105,22 +487,64 @@ impl<'a> AppContext<'a> {
 
     /// Create a new text grid from a character width and
     /// [nrows, ncols] pair.
-    /// Uses the default courier font bundled with A2D
+    /// Uses the default courier font bundled with A2D,
+    /// so like `load_courier_font_sheet`, this fails when running headless
     pub fn new_text_grid(&mut self, char_width: f32, dim: [u32; 2]) -> Result<TextGrid> {
-        Ok(self.graphics.new_text_grid(char_width, dim)?)
+        let sheet = self.load_courier_font_sheet()?;
+        let grid = self.graphics()?.new_text_grid(char_width, dim)?;
+        Ok(TextGrid::new(grid, sheet))
     }
 }
 
-pub struct RenderContext<'a> {
-    pub(crate) actx: &'a mut AppContext<'a>,
+pub struct RenderContext<'a, 'b> {
+    pub(crate) actx: &'a mut AppContext<'b>,
+    pub(crate) alpha: f32,
 }
 
-impl<'a> RenderContext<'a> {
-    pub fn actx(&mut self) -> &mut AppContext<'a> {
+impl<'a, 'b> RenderContext<'a, 'b> {
+    pub fn actx(&mut self) -> &mut AppContext<'b> {
         self.actx
     }
 
+    /// When `Options::update_rate` is set, returns how far along
+    /// we are between the last update and the next one, as a fraction
+    /// between 0 and 1.
+    ///
+    /// This can be used to interpolate between the previous and
+    /// current state of the game, so that movement looks smooth
+    /// even when the update rate and frame rate don't match.
+    ///
+    /// If updates are not done at a fixed rate, this is always 1
+    pub fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    /// Draws the given batches on the screen
+    ///
+    /// When running headless, nothing is drawn, and instead the
+    /// call is recorded (see `Headless::frames`)
+    ///
+    /// If there is a custom cursor (see `AppContext::set_custom_cursor`),
+    /// it is drawn on top of the batches. So are the bars around the
+    /// virtual screen, if `Options::virtual_size` is set
     pub fn render(&mut self, batches: &[&SpriteBatch]) {
-        self.actx.graphics.render(batches)
+        let actx = &mut *self.actx;
+        let mouse_pos = actx.input.mouse_pos();
+        match &mut actx.backend {
+            Backend::Window(_, graphics) => {
+                // the game's batches are moved to where the virtual screen
+                // is, but the bars are placed relative to the window
+                let offset = actx.viewport.offset();
+                let mut layers: Vec<_> = batches.iter().map(|batch| (*batch, offset)).collect();
+                if let Some(bars) = actx.viewport.bars() {
+                    layers.push((bars, [0.0, 0.0]));
+                }
+                if let Some(cursor) = actx.cursor.batch(mouse_pos) {
+                    layers.push((cursor, offset));
+                }
+                sprite::draw(graphics, &layers)
+            }
+            Backend::Headless(recorder) => recorder.record(batches),
+        }
     }
 }
@@ -0,0 +1,240 @@
+use crate::anyhow::Result;
+use crate::winit::dpi::PhysicalPosition;
+use crate::winit::window::CursorIcon as WinitCursorIcon;
+use crate::winit::window::Window as WinitWindow;
+use crate::Instance;
+use crate::SpriteBatch;
+use crate::SpriteSheet;
+use std::rc::Rc;
+
+/// How the mouse cursor is kept in the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CursorGrab {
+    /// The cursor can move freely
+    None,
+
+    /// The cursor can't leave the window
+    ///
+    /// NOTE, on macOS and Wayland, winit can only lock the cursor
+    /// in place, so this behaves like `Locked`, except that
+    /// the cursor stays visible
+    Confined,
+
+    /// The cursor is hidden and kept in the middle of the window,
+    /// for mouselook style controls (see `Game::mouse_motion`)
+    ///
+    /// While locked, `Game::mouse_moved` is not called
+    Locked,
+}
+
+/// The system provided cursor images
+///
+/// Mirrors the icons supported by winit. Not every platform
+/// has every icon, in which case a similar one is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CursorIcon {
+    Default,
+    Crosshair,
+    Hand,
+    Arrow,
+    Move,
+    Text,
+    Wait,
+    Help,
+    Progress,
+    NotAllowed,
+    ContextMenu,
+    Cell,
+    VerticalText,
+    Alias,
+    Copy,
+    NoDrop,
+    Grab,
+    Grabbing,
+    AllScroll,
+    ZoomIn,
+    ZoomOut,
+    EResize,
+    NResize,
+    NeResize,
+    NwResize,
+    SResize,
+    SeResize,
+    SwResize,
+    WResize,
+    EwResize,
+    NsResize,
+    NeswResize,
+    NwseResize,
+    ColResize,
+    RowResize,
+}
+
+/// A cursor drawn from a sprite sheet, used with
+/// `AppContext::set_custom_cursor`
+///
+/// winit can't change the image of the system cursor, so instead
+/// the system cursor is hidden, and this is drawn by yagl at the mouse
+/// position, on top of everything else passed to `RenderContext::render`.
+/// Like the rest of the game, it only moves as fast as frames are drawn.
+#[derive(Clone)]
+pub struct CustomCursor {
+    pub sheet: Rc<SpriteSheet>,
+
+    /// The [upper_left, lower_right] corners of the image on the sheet,
+    /// as fractions of the sheet's size (e.g. `Region::src`)
+    pub src: [[f32; 2]; 2],
+
+    /// The [width, height] the cursor is drawn with, in the same
+    /// units as `InputState::mouse_pos`
+    pub size: [f32; 2],
+
+    /// The point of the image that is placed at the mouse position,
+    /// as an offset from its upper left corner, in the same units as size
+    pub hotspot: [f32; 2],
+}
+
+/// What the game asked the cursor to look like
+pub(crate) struct CursorState {
+    visible: bool,
+    grab: CursorGrab,
+    icon: CursorIcon,
+    custom: Option<(CustomCursor, SpriteBatch)>,
+}
+
+impl Default for CursorState {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            grab: CursorGrab::None,
+            icon: CursorIcon::Default,
+            custom: None,
+        }
+    }
+}
+
+impl CursorState {
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, window: Option<&WinitWindow>, visible: bool) {
+        self.visible = visible;
+        self.apply_visibility(window);
+    }
+
+    pub fn grab(&self) -> CursorGrab {
+        self.grab
+    }
+
+    pub fn set_grab(&mut self, window: Option<&WinitWindow>, grab: CursorGrab) -> Result<()> {
+        if let Some(window) = window {
+            window.set_cursor_grab(grab != CursorGrab::None)?;
+        }
+        self.grab = grab;
+        self.apply_visibility(window);
+        if grab == CursorGrab::Locked {
+            self.recenter(window);
+        }
+        Ok(())
+    }
+
+    pub fn icon(&self) -> CursorIcon {
+        self.icon
+    }
+
+    pub fn set_icon(&mut self, window: Option<&WinitWindow>, icon: CursorIcon) {
+        self.icon = icon;
+        if let Some(window) = window {
+            window.set_cursor_icon(icon.to_winit());
+        }
+    }
+
+    pub fn set_custom(&mut self, window: Option<&WinitWindow>, custom: Option<CustomCursor>) {
+        self.custom = custom.map(|custom| {
+            let mut batch = SpriteBatch::new(custom.sheet.clone());
+            batch.add(Instance::builder().build());
+            batch.get_mut(0).set_src(custom.src);
+            (custom, batch)
+        });
+        self.apply_visibility(window);
+    }
+
+    /// Returns the batch to draw the custom cursor with, placed at the
+    /// given mouse position, if there is a custom cursor to draw
+    pub fn batch(&mut self, mouse_pos: [f32; 2]) -> Option<&SpriteBatch> {
+        if !self.visible || self.grab == CursorGrab::Locked {
+            return None;
+        }
+        let (custom, batch) = self.custom.as_mut()?;
+        let [x, y] = mouse_pos;
+        let [hx, hy] = custom.hotspot;
+        let [w, h] = custom.size;
+        batch
+            .get_mut(0)
+            .set_dest([[x - hx, y - hy], [x - hx + w, y - hy + h]]);
+        Some(batch)
+    }
+
+    /// Moves the cursor back to the middle of the window, if it's locked
+    pub fn recenter(&self, window: Option<&WinitWindow>) {
+        if let (Some(window), CursorGrab::Locked) = (window, self.grab) {
+            let size = window.inner_size();
+            let center = PhysicalPosition::new(size.width / 2, size.height / 2);
+            // not every platform can move the cursor, and there's
+            // nothing to be done about it if it can't
+            let _ = window.set_cursor_position(center);
+        }
+    }
+
+    fn apply_visibility(&self, window: Option<&WinitWindow>) {
+        if let Some(window) = window {
+            let visible = self.visible && self.custom.is_none() && self.grab != CursorGrab::Locked;
+            window.set_cursor_visible(visible);
+        }
+    }
+}
+
+impl CursorIcon {
+    fn to_winit(self) -> WinitCursorIcon {
+        match self {
+            CursorIcon::Default => WinitCursorIcon::Default,
+            CursorIcon::Crosshair => WinitCursorIcon::Crosshair,
+            CursorIcon::Hand => WinitCursorIcon::Hand,
+            CursorIcon::Arrow => WinitCursorIcon::Arrow,
+            CursorIcon::Move => WinitCursorIcon::Move,
+            CursorIcon::Text => WinitCursorIcon::Text,
+            CursorIcon::Wait => WinitCursorIcon::Wait,
+            CursorIcon::Help => WinitCursorIcon::Help,
+            CursorIcon::Progress => WinitCursorIcon::Progress,
+            CursorIcon::NotAllowed => WinitCursorIcon::NotAllowed,
+            CursorIcon::ContextMenu => WinitCursorIcon::ContextMenu,
+            CursorIcon::Cell => WinitCursorIcon::Cell,
+            CursorIcon::VerticalText => WinitCursorIcon::VerticalText,
+            CursorIcon::Alias => WinitCursorIcon::Alias,
+            CursorIcon::Copy => WinitCursorIcon::Copy,
+            CursorIcon::NoDrop => WinitCursorIcon::NoDrop,
+            CursorIcon::Grab => WinitCursorIcon::Grab,
+            CursorIcon::Grabbing => WinitCursorIcon::Grabbing,
+            CursorIcon::AllScroll => WinitCursorIcon::AllScroll,
+            CursorIcon::ZoomIn => WinitCursorIcon::ZoomIn,
+            CursorIcon::ZoomOut => WinitCursorIcon::ZoomOut,
+            CursorIcon::EResize => WinitCursorIcon::EResize,
+            CursorIcon::NResize => WinitCursorIcon::NResize,
+            CursorIcon::NeResize => WinitCursorIcon::NeResize,
+            CursorIcon::NwResize => WinitCursorIcon::NwResize,
+            CursorIcon::SResize => WinitCursorIcon::SResize,
+            CursorIcon::SeResize => WinitCursorIcon::SeResize,
+            CursorIcon::SwResize => WinitCursorIcon::SwResize,
+            CursorIcon::WResize => WinitCursorIcon::WResize,
+            CursorIcon::EwResize => WinitCursorIcon::EwResize,
+            CursorIcon::NsResize => WinitCursorIcon::NsResize,
+            CursorIcon::NeswResize => WinitCursorIcon::NeswResize,
+            CursorIcon::NwseResize => WinitCursorIcon::NwseResize,
+            CursorIcon::ColResize => WinitCursorIcon::ColResize,
+            CursorIcon::RowResize => WinitCursorIcon::RowResize,
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use crate::action::ActionEvent;
+use crate::anyhow::Result;
+use crate::AppContext;
+use crate::Axis;
+use crate::DeviceId;
+use crate::Game;
+use crate::GamepadButton;
+use crate::Key;
+use crate::KeyInfo;
+use crate::MouseButton;
+
+/// An input event, as delivered to the Game
+///
+/// Both real input from a window and synthetic input
+/// (e.g. `Headless::schedule`) go through this type, so that
+/// both end up calling Game methods in exactly the same way.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputEvent {
+    /// See `Game::char`
+    Char(char),
+
+    /// See `Game::key_pressed`
+    ///
+    /// `KeyInfo::is_repeat` is filled in by yagl, based on
+    /// whether the key is already down
+    KeyPressed(Key, KeyInfo),
+
+    /// See `Game::key_released`
+    KeyReleased(Key, KeyInfo),
+
+    /// See `Game::key_scancode_pressed`
+    KeyScancodePressed(KeyInfo),
+
+    /// See `Game::key_scancode_released`
+    KeyScancodeReleased(KeyInfo),
+
+    /// See `Game::mouse_motion`
+    MouseMotion([f32; 2]),
+
+    /// See `Game::mouse_moved`
+    ///
+    /// Also updates the mouse position passed with
+    /// other mouse events (see `InputState::mouse_pos`)
+    MouseMoved([f32; 2]),
+
+    /// See `Game::mouse_button_pressed`
+    MouseButtonPressed(MouseButton),
+
+    /// See `Game::mouse_button_released`
+    MouseButtonReleased(MouseButton),
+
+    /// See `Game::scroll`
+    Scroll([f32; 2]),
+
+    /// See `Game::focus_gained`
+    FocusGained,
+
+    /// See `Game::focus_lost`
+    ///
+    /// Everything held down on the keyboard and mouse is released first
+    FocusLost,
+
+    /// See `Game::cursor_entered`
+    CursorEntered,
+
+    /// See `Game::cursor_left`
+    CursorLeft,
+
+    /// See `Game::suspended`
+    Suspended,
+
+    /// See `Game::resumed`
+    Resumed,
+
+    /// See `Game::gamepad_connected`
+    GamepadConnected(DeviceId),
+
+    /// See `Game::gamepad_disconnected`
+    ///
+    /// Buttons held down on the gamepad are released first
+    GamepadDisconnected(DeviceId),
+
+    /// See `Game::gamepad_button_pressed`
+    GamepadButtonPressed(DeviceId, GamepadButton),
+
+    /// See `Game::gamepad_button_released`
+    GamepadButtonReleased(DeviceId, GamepadButton),
+
+    /// See `Game::gamepad_button_changed`
+    GamepadButtonChanged(DeviceId, GamepadButton, f32),
+
+    /// See `Game::gamepad_axis_changed`
+    ///
+    /// The value is the raw value from the device; deadzones
+    /// (see `AxisConfig`) are applied before it reaches the Game
+    GamepadAxisChanged(DeviceId, Axis, f32),
+}
+
+/// Updates the InputState with the given event, and then
+/// calls the Game method corresponding to it
+///
+/// Axis events have their deadzones applied first, which can turn
+/// them into zero or more events, and losing focus or disconnecting a
+/// gamepad releases everything held down first (see `InputState::process`)
+///
+/// Afterwards, any actions whose state changed because of the
+/// event are reported as well
+pub(crate) fn dispatch<G: Game>(
+    game: &mut G,
+    actx: &mut AppContext,
+    event: InputEvent,
+) -> Result<()> {
+    for event in actx.input.process(event) {
+        actx.input.handle(&event);
+        call(game, actx, event)?;
+    }
+
+    for event in actx.actions.update(actx.input) {
+        match event {
+            ActionEvent::Pressed(action) => game.action_pressed(actx, &action)?,
+            ActionEvent::Released(action) => game.action_released(actx, &action)?,
+            ActionEvent::Value(action, value) => game.action_value(actx, &action, value)?,
+        }
+    }
+    Ok(())
+}
+
+/// Calls the Game method corresponding to the given event
+fn call<G: Game>(game: &mut G, actx: &mut AppContext, event: InputEvent) -> Result<()> {
+    let mouse_pos = actx.input.mouse_pos();
+    match event {
+        InputEvent::Char(ch) => game.char(actx, ch),
+        InputEvent::KeyPressed(key, info) => game.key_pressed(actx, key, info),
+        InputEvent::KeyReleased(key, info) => game.key_released(actx, key, info),
+        InputEvent::KeyScancodePressed(info) => game.key_scancode_pressed(actx, info),
+        InputEvent::KeyScancodeReleased(info) => game.key_scancode_released(actx, info),
+        InputEvent::MouseMoved(pos) => game.mouse_moved(actx, pos),
+        InputEvent::MouseMotion(delta) => game.mouse_motion(actx, delta),
+        InputEvent::MouseButtonPressed(button) => {
+            game.mouse_button_pressed(actx, mouse_pos, button)
+        }
+        InputEvent::MouseButtonReleased(button) => {
+            game.mouse_button_released(actx, mouse_pos, button)
+        }
+        InputEvent::Scroll(delta) => game.scroll(actx, mouse_pos, delta),
+        InputEvent::FocusGained => game.focus_gained(actx),
+        InputEvent::FocusLost => game.focus_lost(actx),
+        InputEvent::CursorEntered => game.cursor_entered(actx),
+        InputEvent::CursorLeft => game.cursor_left(actx),
+        InputEvent::Suspended => game.suspended(actx),
+        InputEvent::Resumed => game.resumed(actx),
+        InputEvent::GamepadConnected(dev) => game.gamepad_connected(actx, dev),
+        InputEvent::GamepadDisconnected(dev) => game.gamepad_disconnected(actx, dev),
+        InputEvent::GamepadButtonPressed(dev, button) => {
+            game.gamepad_button_pressed(actx, dev, button)
+        }
+        InputEvent::GamepadButtonReleased(dev, button) => {
+            game.gamepad_button_released(actx, dev, button)
+        }
+        InputEvent::GamepadButtonChanged(dev, button, value) => {
+            game.gamepad_button_changed(actx, dev, button, value)
+        }
+        InputEvent::GamepadAxisChanged(dev, axis, value) => {
+            game.gamepad_axis_changed(actx, dev, axis, value)
+        }
+    }
+}
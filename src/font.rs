@@ -0,0 +1,197 @@
+use crate::anyhow::Result;
+use crate::AppContext;
+use crate::Atlas;
+use crate::AtlasBuilder;
+use crate::Color;
+use crate::Instance;
+use crate::Region;
+use crate::SpriteBatch;
+use std::collections::HashMap;
+
+/// A TrueType (or OpenType) font, e.g. loaded with `Assets::load_font`
+///
+/// A font can't be drawn as-is. The characters a game needs are first
+/// rasterized at a fixed pixel size with `Font::bake`, which gives a
+/// `FontSheet` that writes text into sprite batches.
+#[derive(Clone)]
+pub struct Font {
+    font: rusttype::Font<'static>,
+}
+
+/// The characters of a font rasterized at a single size
+/// (see `Font::bake`)
+pub struct FontSheet {
+    font: rusttype::Font<'static>,
+    scale: rusttype::Scale,
+    atlas: Atlas,
+    glyphs: HashMap<char, Glyph>,
+    line_height: f32,
+}
+
+struct Glyph {
+    /// None for characters that draw nothing (e.g. spaces)
+    region: Option<Region>,
+
+    /// Where the upper left corner of the glyph's image is,
+    /// relative to the upper left corner of the character's line
+    offset: [f32; 2],
+
+    advance: f32,
+}
+
+impl Font {
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        let font = rusttype::Font::from_bytes(bytes)?;
+        Ok(Self { font })
+    }
+
+    /// Returns whether the font has a glyph for the given character
+    pub fn has_char(&self, ch: char) -> bool {
+        self.font.glyph(ch).id().0 != 0
+    }
+
+    /// Rasterizes the given characters `size` pixels tall, and packs them
+    /// into as few sprite sheets as possible
+    ///
+    /// Characters the font doesn't have are drawn as the font's
+    /// placeholder glyph (usually an empty box).
+    pub fn bake(&self, actx: &mut AppContext, size: f32, chars: &str) -> Result<FontSheet> {
+        let scale = rusttype::Scale::uniform(size);
+        let v_metrics = self.font.v_metrics(scale);
+        let mut builder = AtlasBuilder::new();
+        let mut glyphs = HashMap::new();
+        for ch in chars.chars() {
+            if glyphs.contains_key(&ch) {
+                continue;
+            }
+            let glyph = self.font.glyph(ch).scaled(scale);
+            let advance = glyph.h_metrics().advance_width;
+            let glyph = glyph.positioned(rusttype::point(0.0, v_metrics.ascent));
+            let offset = match glyph.pixel_bounding_box() {
+                Some(bounds) => {
+                    let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+                    // white, so that color factors tint the text
+                    let mut rgba = vec![255; (width * height * 4) as usize];
+                    glyph.draw(|x, y, coverage| {
+                        rgba[((y * width + x) * 4 + 3) as usize] = (coverage * 255.0).round() as u8;
+                    });
+                    builder.add_rgba(&ch.to_string(), width, height, rgba)?;
+                    [bounds.min.x as f32, bounds.min.y as f32]
+                }
+                None => [0.0, 0.0],
+            };
+            glyphs.insert(
+                ch,
+                Glyph {
+                    region: None,
+                    offset,
+                    advance,
+                },
+            );
+        }
+
+        let atlas = builder.build(actx)?;
+        for (ch, glyph) in &mut glyphs {
+            glyph.region = atlas.get(&ch.to_string()).copied();
+        }
+        Ok(FontSheet {
+            font: self.font.clone(),
+            scale,
+            atlas,
+            glyphs,
+            line_height: v_metrics.ascent - v_metrics.descent + v_metrics.line_gap,
+        })
+    }
+}
+
+impl FontSheet {
+    /// Returns the distance between the tops of two lines of text
+    pub fn line_height(&self) -> f32 {
+        self.line_height
+    }
+
+    /// Returns the atlas the characters are packed into
+    pub fn atlas(&self) -> &Atlas {
+        &self.atlas
+    }
+
+    /// Creates an empty batch for each page of the atlas,
+    /// to be passed to `add_text`
+    pub fn new_batches(&self, actx: &mut AppContext) -> Result<Vec<SpriteBatch>> {
+        self.atlas
+            .sheets()
+            .iter()
+            .map(|sheet| actx.new_batch(sheet.clone()))
+            .collect()
+    }
+
+    /// Returns the [width, height] the text takes up when drawn
+    pub fn measure(&self, text: &str) -> [f32; 2] {
+        self.layout(text, |_, _| {})
+    }
+
+    /// Adds a sprite for each character of the text, with the upper left
+    /// corner of the first line at `pos`, and returns the [width, height]
+    /// the text takes up
+    ///
+    /// `batches` must have a batch for each page of the atlas, in order
+    /// (see `new_batches`). A '\n' starts a new line, and characters
+    /// that weren't baked are skipped.
+    pub fn add_text<C: Into<Color>>(
+        &self,
+        batches: &mut [SpriteBatch],
+        pos: [f32; 2],
+        text: &str,
+        color: C,
+    ) -> [f32; 2] {
+        let color = color.into();
+        self.layout(text, |region, [x, y]| {
+            let [x, y] = [pos[0] + x, pos[1] + y];
+            let [width, height] = [region.size[0] as f32, region.size[1] as f32];
+            batches[region.page].add(
+                Instance::builder()
+                    .src(region.src)
+                    .dest([[x, y], [x + width, y + height]])
+                    .color_factor(color)
+                    .build(),
+            );
+        })
+    }
+
+    /// Calls `f` with the region and position of every character
+    /// that draws something, and returns the [width, height] of the text
+    fn layout<F: FnMut(&Region, [f32; 2])>(&self, text: &str, mut f: F) -> [f32; 2] {
+        let mut size = [0.0f32, 0.0];
+        for (i, line) in text.split('\n').enumerate() {
+            let y = i as f32 * self.line_height;
+            let mut x = 0.0;
+            let mut last = None;
+            for ch in line.chars() {
+                let glyph = match self.glyphs.get(&ch) {
+                    Some(glyph) => glyph,
+                    None => continue,
+                };
+                if let Some(last) = last {
+                    x += self.font.pair_kerning(self.scale, last, ch);
+                }
+                if let Some(region) = &glyph.region {
+                    f(region, [x + glyph.offset[0], y + glyph.offset[1]]);
+                }
+                x += glyph.advance;
+                last = Some(ch);
+            }
+            size = [size[0].max(x), y + self.line_height];
+        }
+        size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn garbage_is_not_a_font() {
+        assert!(Font::from_bytes(b"not a font".to_vec()).is_err());
+    }
+}
@@ -1,11 +1,16 @@
 use crate::anyhow::Result;
 use crate::AppContext;
 use crate::Axis;
+use crate::AxisConfig;
 use crate::DeviceId;
 use crate::GamepadButton;
+use crate::GamepadMapping;
 use crate::Key;
+use crate::KeyInfo;
 use crate::MouseButton;
 use crate::RenderContext;
+use crate::WindowOptions;
+use std::path::PathBuf;
 
 /// Trait describing the behavior of a game.
 ///
@@ -37,11 +42,101 @@ where
     /// If called more than once, it may erase the previous draw
     fn render(&mut self, rctx: &mut RenderContext) -> Result<()>;
 
+    /// Called when any of the other methods on Game return an error
+    ///
+    /// If this method returns Ok, the game keeps running as normal,
+    /// so this can be used to e.g. log the error, or switch to a screen
+    /// that displays it.
+    ///
+    /// If this method returns an error, the game exits and the error is
+    /// returned from `yagl::run`, so that `main` gets a chance to
+    /// clean up (e.g. save the game or write a crash report).
+    ///
+    /// The default behavior is to return the error as is, so that
+    /// any error causes the game to exit
+    fn on_error(&mut self, actx: &mut AppContext, error: anyhow::Error) -> Result<()> {
+        Err(error)
+    }
+
     /// Called when the window is resized
     fn resize(&mut self, actx: &mut AppContext, width: u32, height: u32) -> Result<()> {
         Ok(())
     }
 
+    /// Called when the window gains keyboard focus
+    fn focus_gained(&mut self, actx: &mut AppContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when the window loses keyboard focus (e.g. the player
+    /// alt-tabs away)
+    ///
+    /// Right before this is called, every key and mouse button that
+    /// is held down is released (with the usual `key_released` and
+    /// `mouse_button_released` calls), since the window won't be told
+    /// when they are actually released. Gamepads keep working without
+    /// focus, so their buttons are left as they are.
+    ///
+    /// See also `Options::pause_when_unfocused`
+    fn focus_lost(&mut self, actx: &mut AppContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when the mouse cursor enters the window
+    fn cursor_entered(&mut self, actx: &mut AppContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when the mouse cursor leaves the window
+    fn cursor_left(&mut self, actx: &mut AppContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when the game is no longer visible, i.e. the window is
+    /// minimized, or the app is sent to the background on mobile
+    ///
+    /// yagl keeps calling `update` and `render` while suspended (unless
+    /// paused by `Options::pause_when_unfocused`), so this is the place
+    /// to e.g. pause the game or save progress.
+    fn suspended(&mut self, actx: &mut AppContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when the game becomes visible again after `suspended`
+    fn resumed(&mut self, actx: &mut AppContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when an asset loaded through the asset manager
+    /// changes on disk and has been reloaded
+    ///
+    /// This is only ever called with the `hot-reload` feature enabled.
+    ///
+    /// The handle for the asset stays the same, so `Assets::get` returns
+    /// the new version. Sprite sheets are updated in place, so existing
+    /// batches draw the new image without having to be recreated.
+    /// Data assets are not parsed by yagl, and fonts aren't baked again,
+    /// so this is also the place to redo either of those.
+    fn asset_reloaded(&mut self, actx: &mut AppContext, path: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called instead of `asset_reloaded` when an asset changed on disk,
+    /// but couldn't be reloaded (e.g. because an image was only partly
+    /// written, or is broken)
+    ///
+    /// The old version of the asset is kept. By default, the error is
+    /// ignored, so that a broken file doesn't end the game; returning it
+    /// instead passes it on to `on_error`.
+    fn asset_reload_failed(
+        &mut self,
+        actx: &mut AppContext,
+        path: &str,
+        error: anyhow::Error,
+    ) -> Result<()> {
+        Ok(())
+    }
+
     /// Called on character input
     fn char(&mut self, actx: &mut AppContext, ch: char) -> Result<()> {
         Ok(())
@@ -49,15 +144,15 @@ where
 
     /// Called to notify the game that a key was pressed.
     ///
-    /// The default behavior of this method is to exit when Escape is pressed
+    /// This is also called repeatedly while a key is held down, with
+    /// `KeyInfo::is_repeat` set. The info also has the modifiers that
+    /// are held down, for handling shortcuts (e.g. Ctrl+S).
     ///
-    /// NOTE, not all keys may be recognized. If it isn't, this method
-    /// will not get called for those keys.
+    /// The default behavior of this method is to exit when Escape is pressed
     ///
-    /// In the future, there should be a separate 'key_scancode_*' method
-    /// so that even if the key is not recognized, the raw scancode can be
-    /// passed to the client to process.
-    fn key_pressed(&mut self, actx: &mut AppContext, key: Key) -> Result<()> {
+    /// NOTE, not all keys may be recognized. If it isn't,
+    /// `key_scancode_pressed` is called instead.
+    fn key_pressed(&mut self, actx: &mut AppContext, key: Key, info: KeyInfo) -> Result<()> {
         if let Key::Escape = key {
             actx.exit();
         }
@@ -66,13 +161,20 @@ where
 
     /// Called to notify the game that a key was released.
     ///
-    /// NOTE, not all keys may be recognized. If it isn't, this method
-    /// will not get called for those keys.
-    ///
-    /// In the future, there should be a separate 'key_scancode_*' method
-    /// so that even if the key is not recognized, the raw scancode can be
-    /// passed to the client to process.
-    fn key_released(&mut self, actx: &mut AppContext, key: Key) -> Result<()> {
+    /// NOTE, not all keys may be recognized. If it isn't,
+    /// `key_scancode_released` is called instead.
+    fn key_released(&mut self, actx: &mut AppContext, key: Key, info: KeyInfo) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when a key that isn't recognized as a `Key` was pressed,
+    /// so that it can still be handled by its scancode
+    fn key_scancode_pressed(&mut self, actx: &mut AppContext, info: KeyInfo) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when a key that isn't recognized as a `Key` was released
+    fn key_scancode_released(&mut self, actx: &mut AppContext, info: KeyInfo) -> Result<()> {
         Ok(())
     }
 
@@ -98,6 +200,18 @@ where
         Ok(())
     }
 
+    /// Called when the mouse itself moves, with how far it moved
+    ///
+    /// Unlike `mouse_moved`, this isn't affected by the cursor hitting the
+    /// edge of the screen, or by cursor acceleration, so it's meant for
+    /// mouselook style controls (usually with `CursorGrab::Locked`).
+    /// The delta is in raw, device specific units, not game coordinates.
+    ///
+    /// Only called while the window has focus.
+    fn mouse_motion(&mut self, actx: &mut AppContext, delta: [f32; 2]) -> Result<()> {
+        Ok(())
+    }
+
     /// Fired when a scroll event is received.
     /// This could be triggered by a mouse wheel or trackpad.
     /// The delta of [horizontal, vertical] is provided.
@@ -105,10 +219,22 @@ where
         Ok(())
     }
 
+    /// A gamepad was connected
+    ///
+    /// This is also called for every gamepad that is already
+    /// connected when the game starts, right after `Game::options`.
+    /// By the time this is called, the gamepad has been assigned a player
+    /// slot (see `AppContext::player_slot`), and its info is available
+    /// from `AppContext::gamepad_info`.
     fn gamepad_connected(&mut self, actx: &mut AppContext, dev: DeviceId) -> Result<()> {
         Ok(())
     }
 
+    /// A gamepad was disconnected
+    ///
+    /// Right before this is called, every button held down on it is
+    /// released (with the usual `gamepad_button_released` calls).
+    /// Its player slot stays reserved for when it reconnects
     fn gamepad_disconnected(&mut self, actx: &mut AppContext, dev: DeviceId) -> Result<()> {
         Ok(())
     }
@@ -133,7 +259,26 @@ where
         Ok(())
     }
 
+    /// The analog value of a button on a gamepad changed,
+    /// to somewhere between 0 and 1
+    ///
+    /// This is called for analog buttons (e.g. triggers) as well as
+    /// digital ones, in addition to `gamepad_button_pressed` and
+    /// `gamepad_button_released`
+    fn gamepad_button_changed(
+        &mut self,
+        actx: &mut AppContext,
+        dev: DeviceId,
+        button: GamepadButton,
+        value: f32,
+    ) -> Result<()> {
+        Ok(())
+    }
+
     /// An axis on a gamepad was changed
+    ///
+    /// The value has deadzones applied (see `AxisConfig`), so e.g.
+    /// a stick at rest reports exactly 0
     fn gamepad_axis_changed(
         &mut self,
         actx: &mut AppContext,
@@ -143,6 +288,42 @@ where
     ) -> Result<()> {
         Ok(())
     }
+
+    /// A button action was pressed (see `ActionMap`)
+    ///
+    /// This is called after the method for the input that
+    /// caused it (e.g. `key_pressed`)
+    fn action_pressed(&mut self, actx: &mut AppContext, action: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// A button action was released (see `ActionMap`)
+    fn action_released(&mut self, actx: &mut AppContext, action: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// The value of an axis action changed (see `ActionMap`)
+    fn action_value(&mut self, actx: &mut AppContext, action: &str, value: f32) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Passes the error in `result` (if any) to `Game::on_error`
+///
+/// If on_error also fails, the game is asked to exit, and the
+/// resulting error is returned
+pub(crate) fn handle_result<G: Game>(
+    game: &mut G,
+    actx: &mut AppContext,
+    result: Result<()>,
+) -> Result<()> {
+    if let Err(error) = result {
+        if let Err(error) = game.on_error(actx, error) {
+            actx.exit();
+            return Err(error);
+        }
+    }
+    Ok(())
 }
 
 #[derive(Debug, Clone)]
@@ -169,6 +350,99 @@ pub struct Options {
     ///
     /// By default, set to 1.0
     pub scroll_pixel_factor: f32,
+
+    /// If set, `Game::update` will be called at a fixed rate of this
+    /// many times per second, instead of once per frame.
+    ///
+    /// The time left over between updates is made available to
+    /// `Game::render` through `RenderContext::alpha`, so that
+    /// the game can interpolate between the previous and current state.
+    ///
+    /// Must be a positive number if set.
+    ///
+    /// By default, set to None
+    pub update_rate: Option<f64>,
+
+    /// When `update_rate` is set, the maximum number of times
+    /// `Game::update` may be called in a single frame to catch up.
+    ///
+    /// If the game falls further behind than this, the extra time
+    /// is dropped rather than trying to catch up, so that a slow
+    /// update doesn't cause the game to fall further and further behind.
+    ///
+    /// Must be at least 1 when `update_rate` is set, and ignored otherwise.
+    ///
+    /// By default, set to 5
+    pub max_updates_per_frame: u32,
+
+    /// If set, the game sees a screen of this fixed [width, height],
+    /// no matter the size of the window.
+    ///
+    /// The screen is scaled up as much as fits in the window while
+    /// keeping its aspect ratio, and centered in the window. The unused
+    /// space on either side is covered by black bars, drawn on top of
+    /// everything passed to `RenderContext::render` (except the custom
+    /// cursor), so anything drawn outside of the virtual screen is
+    /// hidden. Mouse positions are converted so that they are relative
+    /// to this virtual screen.
+    ///
+    /// NOTE, the characters of a TextGrid are placed by a2d, which has
+    /// no way to move them, so text grids are not centered.
+    ///
+    /// By default, set to None
+    pub virtual_size: Option<[u32; 2]>,
+
+    /// When `virtual_size` is set, only scale the virtual screen
+    /// by whole numbers, so that pixel art stays crisp
+    ///
+    /// By default, set to false
+    pub integer_scaling: bool,
+
+    /// Deadzones and response curves applied to gamepad axes
+    ///
+    /// By default, set to `AxisConfig::default()`
+    pub axis_config: AxisConfig,
+
+    /// Stop calling `Game::update` while the window doesn't have focus
+    /// (see `Game::focus_lost`)
+    ///
+    /// Rendering and input keep going while paused. Time spent paused
+    /// doesn't count towards `AppContext::delta`, so the first update
+    /// after regaining focus doesn't see a huge jump.
+    ///
+    /// By default, set to false
+    pub pause_when_unfocused: bool,
+
+    /// Additional SDL gamepad mappings, in the format used by
+    /// SDL_GameControllerDB (one mapping per line)
+    ///
+    /// These are passed to gilrs when gamepad support starts, along with
+    /// the mappings gilrs comes with. NOTE, gilrs prefers its own mappings,
+    /// so this can only add mappings for gamepads it doesn't know about.
+    ///
+    /// By default, set to empty
+    pub gamepad_mappings: Vec<String>,
+
+    /// Files with additional SDL gamepad mappings
+    /// (e.g. a copy of gamecontrollerdb.txt), treated the same
+    /// as `gamepad_mappings`
+    ///
+    /// Paths are relative to the working directory, and a file
+    /// that can't be read is an error.
+    ///
+    /// By default, set to empty
+    pub gamepad_mapping_files: Vec<PathBuf>,
+
+    /// Mappings previously recorded with `MappingRecorder`
+    ///
+    /// By default, set to empty
+    pub custom_gamepad_mappings: Vec<GamepadMapping>,
+
+    /// Configuration for the window that the game is displayed in
+    ///
+    /// The window is only shown after these options are applied,
+    /// so the game never appears with the default settings
+    pub window: WindowOptions,
 }
 
 impl Default for Options {
@@ -176,6 +450,16 @@ impl Default for Options {
         Self {
             enable_gamepad: true,
             scroll_pixel_factor: 1.0,
+            update_rate: None,
+            max_updates_per_frame: 5,
+            virtual_size: None,
+            integer_scaling: false,
+            axis_config: AxisConfig::default(),
+            pause_when_unfocused: false,
+            gamepad_mappings: Vec::new(),
+            gamepad_mapping_files: Vec::new(),
+            custom_gamepad_mappings: Vec::new(),
+            window: WindowOptions::default(),
         }
     }
 }
@@ -0,0 +1,460 @@
+use crate::anyhow::bail;
+use crate::anyhow::Result;
+use crate::clock::Clock;
+use crate::context::Backend;
+use crate::cursor::CursorState;
+use crate::event::dispatch;
+use crate::game::handle_result;
+use crate::rumble::Rumble;
+use crate::timestep::Timestep;
+use crate::viewport::Viewport;
+use crate::winit::event_loop::ControlFlow;
+use crate::ActionMap;
+use crate::AppContext;
+use crate::Assets;
+use crate::Audio;
+use crate::Game;
+use crate::InputEvent;
+use crate::InputState;
+use crate::Instance;
+use crate::RenderContext;
+use crate::SpriteBatch;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use std::time::Instant;
+
+/// By default, headless games run at this many frames per second
+const DEFAULT_FRAME_RATE: f64 = 60.0;
+
+/// Sounds are played silently, as if at this sample rate
+const AUDIO_SAMPLE_RATE: u32 = 44100;
+
+/// Runs a Game without a window or GPU
+///
+/// This is mainly intended for testing game logic, e.g. in CI,
+/// where opening a window is not possible.
+///
+/// Each frame, the same Game callbacks are called as they would be
+/// in a window (i.e. `update` followed by `render`), but time is
+/// simulated, so that runs are deterministic: every frame is exactly
+/// `1 / frame_rate` seconds after the previous one.
+///
+/// Since there is no GPU, methods on AppContext that create sprite
+/// sheets (e.g. `new_sheet_from_bytes`) return stand-in sheets that are
+/// never drawn, and `RenderContext::render` just records what it was
+/// called with (see `Headless::frames`). Text grids need the font
+/// bundled with a2d, so they can't be created. Sounds are played
+/// silently, but still take the right amount of simulated time to finish.
+///
+/// Input can be fed to the game either immediately with `send`,
+/// or at a specific frame with `schedule`.
+pub struct Headless<G: Game> {
+    game: G,
+    control_flow: ControlFlow,
+    clock: Clock,
+    timestep: Timestep,
+    recorder: Recorder,
+    start: Instant,
+    frame_duration: f64,
+    frame: u64,
+    input: InputState,
+    actions: ActionMap,
+    assets: Assets,
+    audio: Audio,
+    rumble: Rumble,
+    cursor: CursorState,
+    viewport: Viewport,
+    pause_when_unfocused: bool,
+    script: BTreeMap<u64, Vec<InputEvent>>,
+}
+
+impl<G: Game> Headless<G> {
+    /// Creates the game returned by `f`, pretending that the screen
+    /// is of the given [width, height]
+    pub fn new<F: FnOnce(&mut AppContext) -> Result<G>>(size: [u32; 2], f: F) -> Result<Self> {
+        let start = Instant::now();
+        let mut control_flow = ControlFlow::default();
+        let mut clock = Clock::new(start);
+        let mut input = InputState::default();
+        let mut actions = ActionMap::default();
+        let mut assets = Assets::default();
+        let mut audio = Audio::null(AUDIO_SAMPLE_RATE);
+        let rumble = Rumble::default();
+        let mut cursor = CursorState::default();
+        let mut viewport = Viewport::default();
+        let mut recorder = Recorder {
+            scale: [size[0] as f32, size[1] as f32],
+            frame: 0,
+            frames: Vec::new(),
+        };
+
+        let mut actx = AppContext {
+            control_flow: &mut control_flow,
+            backend: Backend::Headless(&mut recorder),
+            clock: &mut clock,
+            input: &mut input,
+            actions: &mut actions,
+            assets: &mut assets,
+            audio: &mut audio,
+            rumble: &rumble,
+            cursor: &mut cursor,
+            viewport: &mut viewport,
+        };
+        let mut game = f(&mut actx)?;
+
+        let options = game.options();
+        actx.input.apply_options(&options);
+        *actx.viewport = Viewport::new(&mut actx, &options)?;
+        let scale = actx.viewport.resize([size[0] as f32, size[1] as f32], 1.0);
+        actx.set_scale(scale);
+        game.resize(&mut actx, size[0], size[1])?;
+
+        let timestep = Timestep::new(options.update_rate, options.max_updates_per_frame)?;
+
+        Ok(Self {
+            game,
+            control_flow,
+            clock,
+            timestep,
+            recorder,
+            start,
+            frame_duration: 1.0 / DEFAULT_FRAME_RATE,
+            frame: 0,
+            input,
+            actions,
+            assets,
+            audio,
+            rumble,
+            cursor,
+            viewport,
+            pause_when_unfocused: options.pause_when_unfocused,
+            script: BTreeMap::new(),
+        })
+    }
+
+    /// Sets how many frames are simulated per second
+    ///
+    /// By default, set to 60
+    pub fn set_frame_rate(&mut self, frame_rate: f64) -> Result<()> {
+        if !(frame_rate > 0.0 && frame_rate.is_finite()) {
+            bail!("Frame rate must be positive, but is {}", frame_rate);
+        }
+        self.frame_duration = 1.0 / frame_rate;
+        Ok(())
+    }
+
+    pub fn game(&self) -> &G {
+        &self.game
+    }
+
+    pub fn game_mut(&mut self) -> &mut G {
+        &mut self.game
+    }
+
+    pub fn into_game(self) -> G {
+        self.game
+    }
+
+    /// Returns true if the game has requested to exit
+    /// (e.g. with `AppContext::exit`)
+    pub fn exited(&self) -> bool {
+        self.control_flow == ControlFlow::Exit
+    }
+
+    /// Returns a record of every call to `RenderContext::render`
+    /// made so far
+    pub fn frames(&self) -> &[RecordedFrame] {
+        &self.recorder.frames
+    }
+
+    /// Returns the number of frames that have been run so far
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Immediately delivers the given input event to the game
+    pub fn send(&mut self, event: InputEvent) -> Result<()> {
+        let mut actx = AppContext {
+            control_flow: &mut self.control_flow,
+            backend: Backend::Headless(&mut self.recorder),
+            clock: &mut self.clock,
+            input: &mut self.input,
+            actions: &mut self.actions,
+            assets: &mut self.assets,
+            audio: &mut self.audio,
+            rumble: &self.rumble,
+            cursor: &mut self.cursor,
+            viewport: &mut self.viewport,
+        };
+        let result = dispatch(&mut self.game, &mut actx, event);
+        handle_result(&mut self.game, &mut actx, result)
+    }
+
+    /// Arranges for the given input event to be delivered at
+    /// the start of the given frame, before any updates
+    ///
+    /// Frames are counted starting from 1, the same as
+    /// `AppContext::frame_count` during render. So e.g. an event
+    /// scheduled for frame 10 is delivered right before the updates
+    /// of the 10th frame
+    ///
+    /// Events scheduled for the same frame are delivered in the
+    /// order they were scheduled. Events scheduled for frames that
+    /// have already been run are never delivered
+    pub fn schedule(&mut self, frame: u64, event: InputEvent) {
+        self.script.entry(frame).or_default().push(event);
+    }
+
+    /// Runs the given number of frames
+    ///
+    /// Stops early if the game exits. If the game exits because
+    /// of an error (see `Game::on_error`), that error is returned
+    pub fn run_frames(&mut self, n: u64) -> Result<()> {
+        for _ in 0..n {
+            if self.exited() {
+                break;
+            }
+            self.run_frame()?;
+        }
+        Ok(())
+    }
+
+    fn run_frame(&mut self) -> Result<()> {
+        self.frame += 1;
+        let now = self.start + Duration::from_secs_f64(self.frame as f64 * self.frame_duration);
+
+        let game = &mut self.game;
+        let timestep = &mut self.timestep;
+        let mut actx = AppContext {
+            control_flow: &mut self.control_flow,
+            backend: Backend::Headless(&mut self.recorder),
+            clock: &mut self.clock,
+            input: &mut self.input,
+            actions: &mut self.actions,
+            assets: &mut self.assets,
+            audio: &mut self.audio,
+            rumble: &self.rumble,
+            cursor: &mut self.cursor,
+            viewport: &mut self.viewport,
+        };
+
+        for event in self.script.remove(&self.frame).unwrap_or_default() {
+            let result = dispatch(game, &mut actx, event);
+            handle_result(game, &mut actx, result)?;
+        }
+
+        actx.audio.advance(self.frame_duration);
+        let result = actx.audio.take_error();
+        handle_result(game, &mut actx, result)?;
+
+        if self.pause_when_unfocused && !actx.input.is_focused() {
+            timestep.pause();
+            actx.clock.pause();
+        } else {
+            let step = timestep.step();
+            for _ in 0..timestep.advance(now) {
+                actx.clock.update(now, step);
+                let result = game.update(&mut actx);
+                actx.input.clear_transitions();
+                handle_result(game, &mut actx, result)?;
+            }
+        }
+
+        actx.clock.frame(now);
+        if let Backend::Headless(recorder) = &mut actx.backend {
+            recorder.frame = actx.clock.frame_count();
+        }
+        let mut rctx = RenderContext {
+            actx: &mut actx,
+            alpha: timestep.alpha(),
+        };
+        let result = game.render(&mut rctx);
+        handle_result(game, &mut actx, result)
+    }
+}
+
+/// A record of a single call to `RenderContext::render`
+/// while running headless
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedFrame {
+    /// The value of `AppContext::frame_count` when render was called
+    pub frame: u64,
+
+    /// The batches passed to render, in order
+    pub batches: Vec<RecordedBatch>,
+}
+
+/// What a single SpriteBatch held when it was passed to render
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedBatch {
+    /// The instances in the batch, i.e. where each sprite would have
+    /// been drawn, what part of the sheet, and how it was colored
+    pub instances: Vec<Instance>,
+}
+
+/// Stands in for Graphics2D when running headless
+pub(crate) struct Recorder {
+    pub(crate) scale: [f32; 2],
+    frame: u64,
+    frames: Vec<RecordedFrame>,
+}
+
+impl Recorder {
+    pub(crate) fn record(&mut self, batches: &[&SpriteBatch]) {
+        self.frames.push(RecordedFrame {
+            frame: self.frame,
+            batches: batches
+                .iter()
+                .map(|batch| RecordedBatch {
+                    instances: batch.instances().to_vec(),
+                })
+                .collect(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Instance;
+    use crate::Key;
+    use crate::KeyInfo;
+
+    struct Sprites {
+        batch: SpriteBatch,
+    }
+
+    impl Game for Sprites {
+        fn update(&mut self, actx: &mut AppContext) -> Result<()> {
+            let x = actx.update_count() as f32;
+            self.batch.get_mut(0).set_dest([[x, 0.0], [x + 1.0, 1.0]]);
+            Ok(())
+        }
+
+        fn render(&mut self, rctx: &mut RenderContext) -> Result<()> {
+            rctx.render(&[&self.batch]);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn render_records_instances() {
+        let mut headless = Headless::new([100, 100], |actx| {
+            let sheet = actx.new_sheet_from_rgba_bytes(2, 2, vec![255; 16])?;
+            assert!(sheet.is_stand_in());
+            let mut batch = actx.new_batch(sheet)?;
+            batch.add(
+                Instance::builder()
+                    .src([[0.0, 0.0], [0.5, 0.5]])
+                    .color_factor([1.0, 0.0, 0.0, 0.5])
+                    .build(),
+            );
+            Ok(Sprites { batch })
+        })
+        .unwrap();
+        headless.run_frames(2).unwrap();
+
+        let frames = headless.frames();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1].frame, 2);
+        assert_eq!(frames[1].batches.len(), 1);
+        let instance = frames[1].batches[0].instances[0];
+        assert_eq!(instance.src(), [[0.0, 0.0], [0.5, 0.5]]);
+        assert_eq!(instance.dest(), [[2.0, 0.0], [3.0, 1.0]]);
+        assert_eq!(instance.color_factor(), [1.0, 0.0, 0.0, 0.5]);
+        assert_ne!(frames[0].batches, frames[1].batches);
+    }
+
+    #[test]
+    fn stand_in_sheets_are_still_checked() {
+        let result = Headless::new([100, 100], |actx| {
+            actx.new_sheet_from_rgba_bytes(2, 2, vec![255; 15])?;
+            Ok(Sprites {
+                batch: actx.new_batch_from_color([1.0, 1.0, 1.0])?,
+            })
+        });
+        assert!(result.is_err());
+        let result = Headless::new([100, 100], |actx| {
+            actx.new_sheet_from_bytes(b"not an image")?;
+            Ok(Sprites {
+                batch: actx.new_batch_from_color([1.0, 1.0, 1.0])?,
+            })
+        });
+        assert!(result.is_err());
+    }
+
+    #[derive(Default)]
+    struct Keys {
+        log: Vec<String>,
+    }
+
+    impl Game for Keys {
+        fn update(&mut self, actx: &mut AppContext) -> Result<()> {
+            let down = actx.input().is_key_down(Key::Left);
+            self.log.push(format!("update {}", down));
+            Ok(())
+        }
+
+        fn render(&mut self, _: &mut RenderContext) -> Result<()> {
+            Ok(())
+        }
+
+        fn key_pressed(&mut self, actx: &mut AppContext, key: Key, _: KeyInfo) -> Result<()> {
+            let down = actx.input().is_key_down(key);
+            self.log.push(format!("press {:?} {}", key, down));
+            Ok(())
+        }
+
+        fn key_released(&mut self, actx: &mut AppContext, key: Key, _: KeyInfo) -> Result<()> {
+            let down = actx.input().is_key_down(key);
+            self.log.push(format!("release {:?} {}", key, down));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn scheduled_input_is_delivered_before_that_frames_updates() {
+        let mut headless = Headless::new([100, 100], |_| Ok(Keys::default())).unwrap();
+        headless.schedule(3, InputEvent::KeyPressed(Key::Left, KeyInfo::default()));
+        headless.schedule(5, InputEvent::KeyReleased(Key::Left, KeyInfo::default()));
+        headless.run_frames(6).unwrap();
+        assert_eq!(
+            headless.game().log,
+            [
+                "update false",
+                "update false",
+                "press Left true",
+                "update true",
+                "update true",
+                "release Left false",
+                "update false",
+                "update false",
+            ]
+        );
+    }
+
+    #[test]
+    fn sent_input_is_delivered_right_away() {
+        let mut headless = Headless::new([100, 100], |_| Ok(Keys::default())).unwrap();
+        headless
+            .send(InputEvent::KeyPressed(Key::Left, KeyInfo::default()))
+            .unwrap();
+        assert_eq!(headless.game().log, ["press Left true"]);
+        headless.run_frames(1).unwrap();
+        assert_eq!(headless.game().log, ["press Left true", "update true"]);
+    }
+
+    #[test]
+    fn frame_rate_must_be_positive() {
+        let mut headless = Headless::new([100, 100], |actx| {
+            Ok(Sprites {
+                batch: actx.new_batch_from_color([1.0, 1.0, 1.0])?,
+            })
+        })
+        .unwrap();
+        assert!(headless.set_frame_rate(0.0).is_err());
+        assert!(headless.set_frame_rate(-30.0).is_err());
+        assert!(headless.set_frame_rate(f64::NAN).is_err());
+        assert!(headless.set_frame_rate(30.0).is_ok());
+    }
+}
@@ -0,0 +1,269 @@
+use crate::Axis;
+use std::collections::HashMap;
+
+/// Deadzones are clamped to at most this, since a deadzone of 1
+/// would leave no range to stretch the rest of the values over
+const MAX_DEADZONE: f32 = 0.99;
+
+/// How the magnitude of an axis is reshaped after the deadzone
+/// is applied
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Curve {
+    Linear,
+
+    /// The magnitude is raised to the given power, so that e.g. with 2,
+    /// small movements give finer control
+    Power(f32),
+}
+
+/// The deadzone and response curve of a single axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AxisResponse {
+    /// Values with a magnitude below this are reported as 0, and
+    /// the remaining range is stretched so that it still reaches 1
+    ///
+    /// Clamped to between 0 and 0.99 when applied
+    pub deadzone: f32,
+
+    pub curve: Curve,
+}
+
+/// One of the two analog sticks on a gamepad
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Stick {
+    Left,
+    Right,
+}
+
+/// How raw gamepad axis values are turned into the values
+/// passed to `Game::gamepad_axis_changed`
+///
+/// Each stick can have a radial deadzone, which treats the stick's
+/// X and Y axes together: the stick is at rest whenever it is within
+/// a circle around its center, and beyond that, the (X, Y) vector
+/// is rescaled and clamped so that its length is at most 1.
+/// After that, every axis has its own (axial) deadzone and
+/// response curve applied.
+///
+/// By default, both sticks have a radial deadzone of 0.15,
+/// the triggers (`Axis::LeftZ` and `Axis::RightZ`) have a
+/// deadzone of 0.05, and all curves are linear.
+///
+/// Set with `Options::axis_config`, or at runtime with
+/// `AppContext::set_axis_config`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AxisConfig {
+    responses: HashMap<Axis, AxisResponse>,
+    radial_deadzones: HashMap<Stick, f32>,
+}
+
+impl Default for AxisConfig {
+    fn default() -> Self {
+        let trigger = AxisResponse {
+            deadzone: 0.05,
+            curve: Curve::Linear,
+        };
+        Self {
+            responses: vec![(Axis::LeftZ, trigger), (Axis::RightZ, trigger)]
+                .into_iter()
+                .collect(),
+            radial_deadzones: vec![(Stick::Left, 0.15), (Stick::Right, 0.15)]
+                .into_iter()
+                .collect(),
+        }
+    }
+}
+
+impl Stick {
+    /// Returns the [x, y] axes of the stick
+    pub fn axes(self) -> [Axis; 2] {
+        match self {
+            Stick::Left => [Axis::LeftStickX, Axis::LeftStickY],
+            Stick::Right => [Axis::RightStickX, Axis::RightStickY],
+        }
+    }
+
+    /// Returns the stick the given axis belongs to, if any
+    pub fn of(axis: Axis) -> Option<Stick> {
+        match axis {
+            Axis::LeftStickX | Axis::LeftStickY => Some(Stick::Left),
+            Axis::RightStickX | Axis::RightStickY => Some(Stick::Right),
+            _ => None,
+        }
+    }
+}
+
+impl AxisResponse {
+    fn apply(&self, value: f32) -> f32 {
+        let deadzone = clamp_deadzone(self.deadzone);
+        let magnitude = value.abs().min(1.0);
+        if magnitude <= deadzone {
+            return 0.0;
+        }
+        let magnitude = (magnitude - deadzone) / (1.0 - deadzone);
+        let magnitude = match self.curve {
+            Curve::Linear => magnitude,
+            Curve::Power(power) => magnitude.powf(power),
+        };
+        magnitude.copysign(value)
+    }
+}
+
+impl AxisConfig {
+    /// Creates a config that passes raw values through unchanged
+    pub fn raw() -> Self {
+        Self {
+            responses: HashMap::new(),
+            radial_deadzones: HashMap::new(),
+        }
+    }
+
+    /// Returns the axial deadzone and response curve of the given axis
+    pub fn response(&self, axis: Axis) -> AxisResponse {
+        self.responses.get(&axis).cloned().unwrap_or(AxisResponse {
+            deadzone: 0.0,
+            curve: Curve::Linear,
+        })
+    }
+
+    pub fn set_response(&mut self, axis: Axis, response: AxisResponse) {
+        self.responses.insert(axis, response);
+    }
+
+    /// Returns the radial deadzone of the given stick, if it has one
+    pub fn radial_deadzone(&self, stick: Stick) -> Option<f32> {
+        self.radial_deadzones.get(&stick).cloned()
+    }
+
+    /// Sets the radial deadzone of the given stick, or removes it
+    /// with None, in which case the stick's axes are
+    /// treated independently
+    ///
+    /// Like axial deadzones, this is clamped to between 0 and 0.99
+    /// when applied
+    pub fn set_radial_deadzone(&mut self, stick: Stick, deadzone: Option<f32>) {
+        match deadzone {
+            Some(deadzone) => self.radial_deadzones.insert(stick, deadzone),
+            None => self.radial_deadzones.remove(&stick),
+        };
+    }
+
+    /// Returns the processed value of `axis`, given its raw value
+    /// and the raw value of the other axis on the same stick
+    pub(crate) fn apply(&self, axis: Axis, value: f32, partner: f32) -> f32 {
+        let radial = Stick::of(axis).and_then(|stick| self.radial_deadzone(stick));
+        let value = match radial {
+            Some(deadzone) => {
+                let deadzone = clamp_deadzone(deadzone);
+                let magnitude = value.hypot(partner);
+                if magnitude <= deadzone {
+                    0.0
+                } else {
+                    let scaled = (magnitude.min(1.0) - deadzone) / (1.0 - deadzone);
+                    value * scaled / magnitude
+                }
+            }
+            None => value.clamp(-1.0, 1.0),
+        };
+        self.response(axis).apply(value)
+    }
+}
+
+fn clamp_deadzone(deadzone: f32) -> f32 {
+    if deadzone.is_nan() {
+        0.0
+    } else {
+        deadzone.clamp(0.0, MAX_DEADZONE)
+    }
+}
+
+/// Returns the other axis on the same stick, if any
+pub(crate) fn partner(axis: Axis) -> Option<Axis> {
+    Stick::of(axis).map(|stick| {
+        let [x, y] = stick.axes();
+        if axis == x {
+            y
+        } else {
+            x
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+    }
+
+    fn axial(deadzone: f32, curve: Curve) -> AxisConfig {
+        let mut config = AxisConfig::raw();
+        config.set_response(Axis::LeftZ, AxisResponse { deadzone, curve });
+        config
+    }
+
+    #[test]
+    fn radial_deadzone_keeps_diagonals_on_the_unit_circle() {
+        let config = AxisConfig::default();
+        let x = config.apply(Axis::LeftStickX, 1.0, 1.0);
+        let y = config.apply(Axis::LeftStickY, 1.0, 1.0);
+        assert_near(x.hypot(y), 1.0);
+        assert_near(x, y);
+
+        assert_eq!(config.apply(Axis::LeftStickX, 0.1, 0.1), 0.0);
+        // half way between the deadzone and the edge
+        let x = config.apply(Axis::LeftStickX, 0.3, 0.4);
+        let y = config.apply(Axis::LeftStickY, 0.4, 0.3);
+        assert_near(x.hypot(y), (0.5 - 0.15) / 0.85);
+        assert_near(x / y, 0.75);
+    }
+
+    #[test]
+    fn axial_deadzone_stretches_what_is_left() {
+        let config = axial(0.2, Curve::Linear);
+        assert_eq!(config.apply(Axis::LeftZ, 0.1, 0.0), 0.0);
+        assert_near(config.apply(Axis::LeftZ, 0.6, 0.0), 0.5);
+        assert_near(config.apply(Axis::LeftZ, -0.6, 0.0), -0.5);
+        assert_near(config.apply(Axis::LeftZ, 1.5, 0.0), 1.0);
+    }
+
+    #[test]
+    fn power_curve_keeps_the_sign() {
+        let config = axial(0.2, Curve::Power(2.0));
+        assert_near(config.apply(Axis::LeftZ, 0.6, 0.0), 0.25);
+        assert_near(config.apply(Axis::LeftZ, -0.6, 0.0), -0.25);
+        assert_near(config.apply(Axis::LeftZ, 1.0, 0.0), 1.0);
+    }
+
+    #[test]
+    fn raw_passes_values_through() {
+        let config = AxisConfig::raw();
+        assert_eq!(config.apply(Axis::LeftStickX, 0.05, 0.0), 0.05);
+        assert_eq!(config.apply(Axis::LeftStickX, -2.0, 0.0), -1.0);
+    }
+
+    #[test]
+    fn out_of_range_deadzones_are_clamped() {
+        for &deadzone in &[1.0, 2.0, -0.5, f32::NAN] {
+            let config = axial(deadzone, Curve::Linear);
+            for &value in &[0.0, 0.5, 1.0] {
+                assert!(config.apply(Axis::LeftZ, value, 0.0).is_finite());
+            }
+
+            let mut config = AxisConfig::raw();
+            config.set_radial_deadzone(Stick::Left, Some(deadzone));
+            for &value in &[0.0, 0.5, 1.0] {
+                assert!(config.apply(Axis::LeftStickX, value, 0.0).is_finite());
+            }
+        }
+        let mut config = AxisConfig::raw();
+        config.set_radial_deadzone(Stick::Left, Some(-0.5));
+        assert_eq!(config.apply(Axis::LeftStickX, 0.0, 0.0), 0.0);
+        assert_near(config.apply(Axis::LeftStickX, 0.5, 0.0), 0.5);
+    }
+}
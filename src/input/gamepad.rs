@@ -0,0 +1,231 @@
+use crate::anyhow::bail;
+use crate::anyhow::Error;
+use crate::gilrs;
+use crate::gilrs::GamepadId;
+use crate::gilrs::Gilrs;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Information about a connected gamepad
+///
+/// Retrieved with `AppContext::gamepad_info`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GamepadInfo {
+    /// The name of the gamepad's SDL mapping if it has one,
+    /// otherwise the name reported by the OS
+    pub name: String,
+
+    /// The name reported by the OS
+    pub os_name: String,
+
+    pub uuid: GamepadUuid,
+
+    pub mapping: MappingSource,
+
+    pub power: PowerInfo,
+
+    /// Whether the gamepad supports rumble (see `AppContext::rumble`)
+    pub ff_supported: bool,
+}
+
+/// Where the layout of a gamepad's buttons and axes comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MappingSource {
+    /// The gamepad is described by an SDL mapping
+    SdlMappings,
+
+    /// The driver provides the standard layout
+    Driver,
+
+    /// Nothing is known about the layout, so most buttons and axes will
+    /// show up as `GamepadButton::Unknown` and `Axis::Unknown`
+    None,
+}
+
+/// The state of a gamepad's power supply
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PowerInfo {
+    /// The state could not be determined
+    Unknown,
+
+    /// The gamepad has no battery
+    Wired,
+
+    /// Running on battery, with the given charge level from 0 to 100
+    Discharging(u8),
+
+    /// Charging, with the given charge level from 0 to 100
+    Charging(u8),
+
+    Charged,
+}
+
+/// The SDL-compatible UUID of a gamepad, which identifies
+/// its make and model
+///
+/// Unlike `DeviceId`, this stays the same across reconnects and runs
+/// of the game, so it can be used to save per-controller settings.
+/// Note that two gamepads of the same model have the same UUID.
+///
+/// Formats as 32 lowercase hex digits, the same format used by
+/// SDL_GameControllerDB, and can be parsed back with `str::parse`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GamepadUuid([u8; 16]);
+
+impl GamepadInfo {
+    pub(crate) fn from_gilrs(gamepad: &gilrs::Gamepad) -> Self {
+        Self {
+            name: gamepad.name().to_owned(),
+            os_name: gamepad.os_name().to_owned(),
+            uuid: GamepadUuid(gamepad.uuid()),
+            mapping: MappingSource::from_gilrs(gamepad.mapping_source()),
+            power: PowerInfo::from_gilrs(gamepad.power_info()),
+            ff_supported: gamepad.is_ff_supported(),
+        }
+    }
+
+    /// Returns true if the gamepad's layout comes from an SDL mapping
+    pub fn uses_sdl_mapping(&self) -> bool {
+        self.mapping == MappingSource::SdlMappings
+    }
+
+    /// Returns the USB vendor ID of the gamepad, if known
+    pub fn vendor_id(&self) -> Option<u16> {
+        self.uuid.vendor_id()
+    }
+
+    /// Returns the USB product ID of the gamepad, if known
+    pub fn product_id(&self) -> Option<u16> {
+        self.uuid.product_id()
+    }
+}
+
+/// Compares the info of every connected gamepad against what was
+/// last reported, and returns what changed
+///
+/// Gamepads that are no longer connected are returned with None.
+/// Runs on the thread that owns Gilrs.
+pub(crate) fn poll_info(
+    gilrs: &Gilrs,
+    known: &mut HashMap<GamepadId, GamepadInfo>,
+) -> Vec<(GamepadId, Option<GamepadInfo>)> {
+    let mut changes = Vec::new();
+    let connected: HashMap<_, _> = gilrs
+        .gamepads()
+        .map(|(id, gamepad)| (id, GamepadInfo::from_gilrs(&gamepad)))
+        .collect();
+    known.retain(|id, _| {
+        let keep = connected.contains_key(id);
+        if !keep {
+            changes.push((*id, None));
+        }
+        keep
+    });
+    for (id, info) in connected {
+        if known.get(&id) != Some(&info) {
+            known.insert(id, info.clone());
+            changes.push((id, Some(info)));
+        }
+    }
+    changes
+}
+
+impl MappingSource {
+    fn from_gilrs(source: gilrs::MappingSource) -> Self {
+        match source {
+            gilrs::MappingSource::SdlMappings => MappingSource::SdlMappings,
+            gilrs::MappingSource::Driver => MappingSource::Driver,
+            gilrs::MappingSource::None => MappingSource::None,
+        }
+    }
+}
+
+impl PowerInfo {
+    fn from_gilrs(info: gilrs::PowerInfo) -> Self {
+        match info {
+            gilrs::PowerInfo::Unknown => PowerInfo::Unknown,
+            gilrs::PowerInfo::Wired => PowerInfo::Wired,
+            gilrs::PowerInfo::Discharging(level) => PowerInfo::Discharging(level),
+            gilrs::PowerInfo::Charging(level) => PowerInfo::Charging(level),
+            gilrs::PowerInfo::Charged => PowerInfo::Charged,
+        }
+    }
+}
+
+impl GamepadUuid {
+    pub fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    /// Returns the vendor ID encoded in the UUID, if it has one
+    ///
+    /// Like SDL, this only trusts UUIDs laid out as
+    /// [bus, vendor, product, version] with the padding between
+    /// them zeroed (e.g. XInput devices on Windows don't have one)
+    pub fn vendor_id(&self) -> Option<u16> {
+        self.ids().map(|(vendor, _)| vendor)
+    }
+
+    /// Returns the product ID encoded in the UUID, if it has one
+    pub fn product_id(&self) -> Option<u16> {
+        self.ids().map(|(_, product)| product)
+    }
+
+    fn ids(&self) -> Option<(u16, u16)> {
+        let b = &self.0;
+        if b[6..8] != [0, 0] || b[10..12] != [0, 0] {
+            return None;
+        }
+        let vendor = u16::from_le_bytes([b[4], b[5]]);
+        let product = u16::from_le_bytes([b[8], b[9]]);
+        if vendor == 0 && product == 0 {
+            None
+        } else {
+            Some((vendor, product))
+        }
+    }
+}
+
+impl fmt::Display for GamepadUuid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for GamepadUuid {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        if s.len() != 32 || !s.chars().all(|c| c.is_ascii_hexdigit()) {
+            bail!("Invalid gamepad UUID {:?}", s);
+        }
+        let mut bytes = [0; 16];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16)?;
+        }
+        Ok(Self(bytes))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for GamepadUuid {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for GamepadUuid {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
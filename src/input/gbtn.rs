@@ -4,6 +4,7 @@ use crate::gilrs;
 ///
 /// Follows the same model as gilrs
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GamepadButton {
     // Action Pad
     South,
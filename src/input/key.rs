@@ -1,10 +1,25 @@
+use crate::anyhow::bail;
+use crate::anyhow::Error;
 use crate::winit::event::VirtualKeyCode;
+use std::fmt;
+use std::str::FromStr;
 
 /// A key on the keyboard
 ///
 /// Currently just mirrors the keys recognized by winit
 ///
+/// A Key is logical, i.e. it's what the key means in the current
+/// keyboard layout (e.g. the key right of Tab is `Key::Q` on QWERTY
+/// layouts, but `Key::A` on AZERTY). To refer to a key by where it is
+/// on the keyboard instead, use its scancode (see `KeyInfo::scancode`,
+/// and `Input::Scancode` for actions).
+///
+/// Displays as the name of the variant (e.g. "Key1", "Escape"),
+/// which can be parsed back with `str::parse`, for saving key bindings.
+/// For showing a key to players, use `label`.
+///
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Key {
     /// The '1' key over the letters.
     Key1,
@@ -365,6 +380,253 @@ impl Key {
         }
     }
 
+    /// Returns a human readable name for the key,
+    /// e.g. "1" for `Key::Key1`, or "Left Ctrl" for `Key::LControl`
+    ///
+    /// The names are always in English, and come from a fixed table
+    /// rather than the platform, so they aren't localized.
+    pub fn label(self) -> &'static str {
+        match self {
+            Key::Key1 => "1",
+            Key::Key2 => "2",
+            Key::Key3 => "3",
+            Key::Key4 => "4",
+            Key::Key5 => "5",
+            Key::Key6 => "6",
+            Key::Key7 => "7",
+            Key::Key8 => "8",
+            Key::Key9 => "9",
+            Key::Key0 => "0",
+            Key::Escape => "Esc",
+            Key::Snapshot => "Print Screen",
+            Key::Scroll => "Scroll Lock",
+            Key::PageDown => "Page Down",
+            Key::PageUp => "Page Up",
+            Key::Left => "Left Arrow",
+            Key::Up => "Up Arrow",
+            Key::Right => "Right Arrow",
+            Key::Down => "Down Arrow",
+            Key::Return => "Enter",
+            Key::Numlock => "Num Lock",
+            Key::Add => "Numpad +",
+            Key::Subtract => "Numpad -",
+            Key::Multiply => "Numpad *",
+            Key::Divide => "Numpad /",
+            Key::Decimal => "Numpad .",
+            Key::NumpadComma => "Numpad ,",
+            Key::NumpadEnter => "Numpad Enter",
+            Key::NumpadEquals => "Numpad =",
+            Key::Apostrophe => "'",
+            Key::Backslash => "\\",
+            Key::Comma => ",",
+            Key::Equals => "=",
+            Key::Grave => "`",
+            Key::LBracket => "[",
+            Key::RBracket => "]",
+            Key::Minus => "-",
+            Key::Period => ".",
+            Key::Semicolon => ";",
+            Key::Slash => "/",
+            Key::Colon => ":",
+            Key::At => "@",
+            Key::Caret => "^",
+            Key::Underline => "_",
+            Key::Capital => "Caps Lock",
+            Key::LAlt => "Left Alt",
+            Key::RAlt => "Right Alt",
+            Key::LControl => "Left Ctrl",
+            Key::RControl => "Right Ctrl",
+            Key::LShift => "Left Shift",
+            Key::RShift => "Right Shift",
+            Key::LWin => "Left Super",
+            Key::RWin => "Right Super",
+            Key::Apps => "Menu",
+            Key::PlayPause => "Play/Pause",
+            Key::NextTrack => "Next Track",
+            Key::PrevTrack => "Previous Track",
+            Key::VolumeDown => "Volume Down",
+            Key::VolumeUp => "Volume Up",
+            Key::Numpad0 => "Numpad 0",
+            Key::Numpad1 => "Numpad 1",
+            Key::Numpad2 => "Numpad 2",
+            Key::Numpad3 => "Numpad 3",
+            Key::Numpad4 => "Numpad 4",
+            Key::Numpad5 => "Numpad 5",
+            Key::Numpad6 => "Numpad 6",
+            Key::Numpad7 => "Numpad 7",
+            Key::Numpad8 => "Numpad 8",
+            Key::Numpad9 => "Numpad 9",
+            _ => self.name(),
+        }
+    }
+
+    /// Returns the name of the variant
+    fn name(self) -> &'static str {
+        match self {
+            Key::Key1 => "Key1",
+            Key::Key2 => "Key2",
+            Key::Key3 => "Key3",
+            Key::Key4 => "Key4",
+            Key::Key5 => "Key5",
+            Key::Key6 => "Key6",
+            Key::Key7 => "Key7",
+            Key::Key8 => "Key8",
+            Key::Key9 => "Key9",
+            Key::Key0 => "Key0",
+            Key::A => "A",
+            Key::B => "B",
+            Key::C => "C",
+            Key::D => "D",
+            Key::E => "E",
+            Key::F => "F",
+            Key::G => "G",
+            Key::H => "H",
+            Key::I => "I",
+            Key::J => "J",
+            Key::K => "K",
+            Key::L => "L",
+            Key::M => "M",
+            Key::N => "N",
+            Key::O => "O",
+            Key::P => "P",
+            Key::Q => "Q",
+            Key::R => "R",
+            Key::S => "S",
+            Key::T => "T",
+            Key::U => "U",
+            Key::V => "V",
+            Key::W => "W",
+            Key::X => "X",
+            Key::Y => "Y",
+            Key::Z => "Z",
+            Key::Escape => "Escape",
+            Key::F1 => "F1",
+            Key::F2 => "F2",
+            Key::F3 => "F3",
+            Key::F4 => "F4",
+            Key::F5 => "F5",
+            Key::F6 => "F6",
+            Key::F7 => "F7",
+            Key::F8 => "F8",
+            Key::F9 => "F9",
+            Key::F10 => "F10",
+            Key::F11 => "F11",
+            Key::F12 => "F12",
+            Key::F13 => "F13",
+            Key::F14 => "F14",
+            Key::F15 => "F15",
+            Key::F16 => "F16",
+            Key::F17 => "F17",
+            Key::F18 => "F18",
+            Key::F19 => "F19",
+            Key::F20 => "F20",
+            Key::F21 => "F21",
+            Key::F22 => "F22",
+            Key::F23 => "F23",
+            Key::F24 => "F24",
+            Key::Snapshot => "Snapshot",
+            Key::Scroll => "Scroll",
+            Key::Pause => "Pause",
+            Key::Insert => "Insert",
+            Key::Home => "Home",
+            Key::Delete => "Delete",
+            Key::End => "End",
+            Key::PageDown => "PageDown",
+            Key::PageUp => "PageUp",
+            Key::Left => "Left",
+            Key::Up => "Up",
+            Key::Right => "Right",
+            Key::Down => "Down",
+            Key::Backspace => "Backspace",
+            Key::Return => "Return",
+            Key::Space => "Space",
+            Key::Compose => "Compose",
+            Key::Caret => "Caret",
+            Key::Numlock => "Numlock",
+            Key::Numpad0 => "Numpad0",
+            Key::Numpad1 => "Numpad1",
+            Key::Numpad2 => "Numpad2",
+            Key::Numpad3 => "Numpad3",
+            Key::Numpad4 => "Numpad4",
+            Key::Numpad5 => "Numpad5",
+            Key::Numpad6 => "Numpad6",
+            Key::Numpad7 => "Numpad7",
+            Key::Numpad8 => "Numpad8",
+            Key::Numpad9 => "Numpad9",
+            Key::AbntC1 => "AbntC1",
+            Key::AbntC2 => "AbntC2",
+            Key::Add => "Add",
+            Key::Apostrophe => "Apostrophe",
+            Key::Apps => "Apps",
+            Key::At => "At",
+            Key::Ax => "Ax",
+            Key::Backslash => "Backslash",
+            Key::Calculator => "Calculator",
+            Key::Capital => "Capital",
+            Key::Colon => "Colon",
+            Key::Comma => "Comma",
+            Key::Convert => "Convert",
+            Key::Decimal => "Decimal",
+            Key::Divide => "Divide",
+            Key::Equals => "Equals",
+            Key::Grave => "Grave",
+            Key::Kana => "Kana",
+            Key::Kanji => "Kanji",
+            Key::LAlt => "LAlt",
+            Key::LBracket => "LBracket",
+            Key::LControl => "LControl",
+            Key::LShift => "LShift",
+            Key::LWin => "LWin",
+            Key::Mail => "Mail",
+            Key::MediaSelect => "MediaSelect",
+            Key::MediaStop => "MediaStop",
+            Key::Minus => "Minus",
+            Key::Multiply => "Multiply",
+            Key::Mute => "Mute",
+            Key::MyComputer => "MyComputer",
+            Key::NavigateForward => "NavigateForward",
+            Key::NavigateBackward => "NavigateBackward",
+            Key::NextTrack => "NextTrack",
+            Key::NoConvert => "NoConvert",
+            Key::NumpadComma => "NumpadComma",
+            Key::NumpadEnter => "NumpadEnter",
+            Key::NumpadEquals => "NumpadEquals",
+            Key::OEM102 => "OEM102",
+            Key::Period => "Period",
+            Key::PlayPause => "PlayPause",
+            Key::Power => "Power",
+            Key::PrevTrack => "PrevTrack",
+            Key::RAlt => "RAlt",
+            Key::RBracket => "RBracket",
+            Key::RControl => "RControl",
+            Key::RShift => "RShift",
+            Key::RWin => "RWin",
+            Key::Semicolon => "Semicolon",
+            Key::Slash => "Slash",
+            Key::Sleep => "Sleep",
+            Key::Stop => "Stop",
+            Key::Subtract => "Subtract",
+            Key::Sysrq => "Sysrq",
+            Key::Tab => "Tab",
+            Key::Underline => "Underline",
+            Key::Unlabeled => "Unlabeled",
+            Key::VolumeDown => "VolumeDown",
+            Key::VolumeUp => "VolumeUp",
+            Key::Wake => "Wake",
+            Key::WebBack => "WebBack",
+            Key::WebFavorites => "WebFavorites",
+            Key::WebForward => "WebForward",
+            Key::WebHome => "WebHome",
+            Key::WebRefresh => "WebRefresh",
+            Key::WebSearch => "WebSearch",
+            Key::WebStop => "WebStop",
+            Key::Yen => "Yen",
+            Key::Copy => "Copy",
+            Key::Paste => "Paste",
+            Key::Cut => "Cut",
+        }
+    }
+
     pub const LIST: &'static [Key] = &[
         Key::Key1,
         Key::Key2,
@@ -529,3 +791,45 @@ impl Key {
         Key::Cut,
     ];
 }
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Key {
+    type Err = Error;
+
+    /// Parses the name of a key, ignoring case
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match Key::LIST
+            .iter()
+            .find(|key| key.name().eq_ignore_ascii_case(s))
+        {
+            Some(key) => Ok(*key),
+            None => bail!("Unrecognized key {:?}", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_key_round_trips_through_its_name() {
+        for key in Key::LIST {
+            let name = key.to_string();
+            assert_eq!(name.parse::<Key>().unwrap(), *key, "{}", name);
+            assert_eq!(name.to_uppercase().parse::<Key>().unwrap(), *key);
+            assert!(!key.label().is_empty(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn unknown_names_are_rejected() {
+        assert!("".parse::<Key>().is_err());
+        assert!("NotAKey".parse::<Key>().is_err());
+    }
+}
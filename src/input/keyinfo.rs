@@ -0,0 +1,57 @@
+use crate::winit::event::ModifiersState;
+
+/// The modifier keys that are held down
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+
+    /// The Windows key, or Command on macOS
+    pub logo: bool,
+}
+
+impl Modifiers {
+    pub(crate) fn from_winit(state: ModifiersState) -> Self {
+        Self {
+            shift: state.shift(),
+            ctrl: state.ctrl(),
+            alt: state.alt(),
+            logo: state.logo(),
+        }
+    }
+
+    /// Returns true if no modifiers are held down
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns true if the platform's usual modifier for shortcuts
+    /// is held down, i.e. Command on macOS, and Ctrl everywhere else
+    pub fn command(&self) -> bool {
+        if cfg!(target_os = "macos") {
+            self.logo
+        } else {
+            self.ctrl
+        }
+    }
+}
+
+/// Details about a key event, passed along with it
+/// (e.g. to `Game::key_pressed`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct KeyInfo {
+    /// The platform specific code of the physical key, which doesn't
+    /// depend on the keyboard layout
+    pub scancode: u32,
+
+    /// The modifiers held down when the key was pressed or released
+    pub modifiers: Modifiers,
+
+    /// True if this press was generated by the key being held down,
+    /// rather than by the key actually being pressed
+    ///
+    /// Always false for releases
+    pub is_repeat: bool,
+}
@@ -0,0 +1,450 @@
+use crate::anyhow::bail;
+use crate::anyhow::Error;
+use crate::Axis;
+use crate::DeviceId;
+use crate::GamepadButton;
+use crate::GamepadInfo;
+use crate::GamepadUuid;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// How far a raw axis has to move from where it started before
+/// `MappingRecorder` counts it as input
+const AXIS_THRESHOLD: f32 = 0.5;
+
+/// A button or axis that a raw input can be mapped to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MappingTarget {
+    Button(GamepadButton),
+    Axis(Axis),
+}
+
+/// A button or axis on a gamepad, as identified by the
+/// platform specific code gilrs reports for it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RawInput {
+    Button(u32),
+    Axis(u32),
+}
+
+/// A gamepad layout recorded with `MappingRecorder`
+///
+/// This is applied by yagl on top of whatever gilrs reports, so it
+/// works even for gamepads without an SDL mapping. Raw inputs that
+/// aren't part of the mapping are left as they are.
+///
+/// Since the raw codes are platform specific, so is the mapping.
+/// It can be saved as a string with `to_string` and parsed back with
+/// `str::parse`. The format resembles SDL_GameControllerDB
+/// (e.g. "<uuid>,<name>,a:b304,leftx:a0,"), but the numbers are raw
+/// codes rather than SDL indices, so it can't be used as an SDL mapping
+/// (i.e. it doesn't belong in `Options::gamepad_mappings`).
+///
+/// Set with `Options::custom_gamepad_mappings`, or at runtime with
+/// `AppContext::set_gamepad_mapping`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GamepadMapping {
+    uuid: GamepadUuid,
+    name: String,
+    entries: Vec<(RawInput, MappingTarget)>,
+}
+
+impl GamepadMapping {
+    pub fn new(uuid: GamepadUuid, name: &str) -> Self {
+        Self {
+            uuid,
+            name: name.replace(',', " "),
+            entries: Vec::new(),
+        }
+    }
+
+    /// The UUID of the gamepads this mapping applies to
+    pub fn uuid(&self) -> GamepadUuid {
+        self.uuid
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Maps the given raw input to the given target, replacing
+    /// anything either of them was mapped to before
+    pub fn insert(&mut self, raw: RawInput, target: MappingTarget) {
+        self.entries.retain(|(r, t)| *r != raw && *t != target);
+        self.entries.push((raw, target));
+    }
+
+    /// Returns what the given raw input is mapped to, if anything
+    pub fn get(&self, raw: RawInput) -> Option<MappingTarget> {
+        self.entries
+            .iter()
+            .find(|(r, _)| *r == raw)
+            .map(|(_, target)| *target)
+    }
+
+    /// Returns every mapped raw input along with its target,
+    /// in the order they were inserted
+    pub fn entries(&self) -> &[(RawInput, MappingTarget)] {
+        &self.entries
+    }
+}
+
+impl fmt::Display for GamepadMapping {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{},", self.uuid, self.name)?;
+        for (raw, target) in &self.entries {
+            let raw = match raw {
+                RawInput::Button(code) => format!("b{}", code),
+                RawInput::Axis(code) => format!("a{}", code),
+            };
+            write!(f, "{}:{},", target_name(*target), raw)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for GamepadMapping {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut parts = s.trim().split(',');
+        let uuid = parts.next().unwrap_or("").parse()?;
+        let name = match parts.next() {
+            Some(name) => name,
+            None => bail!("Gamepad mapping {:?} has no name", s),
+        };
+        let mut mapping = Self::new(uuid, name);
+        for part in parts.filter(|part| !part.is_empty()) {
+            let (target, raw) = match part.find(':') {
+                Some(i) => (&part[..i], &part[i + 1..]),
+                None => bail!("Invalid gamepad mapping entry {:?}", part),
+            };
+            let target = match target_from_name(target) {
+                Some(target) => target,
+                None => bail!("Unrecognized gamepad mapping target {:?}", target),
+            };
+            let raw = match (raw.get(..1), raw.get(1..).map(str::parse)) {
+                (Some("b"), Some(Ok(code))) => RawInput::Button(code),
+                (Some("a"), Some(Ok(code))) => RawInput::Axis(code),
+                _ => bail!("Invalid raw gamepad input {:?}", raw),
+            };
+            mapping.insert(raw, target);
+        }
+        Ok(mapping)
+    }
+}
+
+/// The names used in mapping strings, which follow SDL where possible
+const TARGET_NAMES: &[(&str, MappingTarget)] = &[
+    ("a", MappingTarget::Button(GamepadButton::South)),
+    ("b", MappingTarget::Button(GamepadButton::East)),
+    ("x", MappingTarget::Button(GamepadButton::West)),
+    ("y", MappingTarget::Button(GamepadButton::North)),
+    ("c", MappingTarget::Button(GamepadButton::C)),
+    ("z", MappingTarget::Button(GamepadButton::Z)),
+    ("back", MappingTarget::Button(GamepadButton::Select)),
+    ("guide", MappingTarget::Button(GamepadButton::Mode)),
+    ("start", MappingTarget::Button(GamepadButton::Start)),
+    ("leftstick", MappingTarget::Button(GamepadButton::LeftThumb)),
+    (
+        "rightstick",
+        MappingTarget::Button(GamepadButton::RightThumb),
+    ),
+    (
+        "leftshoulder",
+        MappingTarget::Button(GamepadButton::LeftTrigger),
+    ),
+    (
+        "rightshoulder",
+        MappingTarget::Button(GamepadButton::RightTrigger),
+    ),
+    ("dpup", MappingTarget::Button(GamepadButton::DPadUp)),
+    ("dpdown", MappingTarget::Button(GamepadButton::DPadDown)),
+    ("dpleft", MappingTarget::Button(GamepadButton::DPadLeft)),
+    ("dpright", MappingTarget::Button(GamepadButton::DPadRight)),
+    (
+        "lefttrigger",
+        MappingTarget::Button(GamepadButton::LeftTrigger2),
+    ),
+    (
+        "righttrigger",
+        MappingTarget::Button(GamepadButton::RightTrigger2),
+    ),
+    ("unknown", MappingTarget::Button(GamepadButton::Unknown)),
+    ("leftx", MappingTarget::Axis(Axis::LeftStickX)),
+    ("lefty", MappingTarget::Axis(Axis::LeftStickY)),
+    ("rightx", MappingTarget::Axis(Axis::RightStickX)),
+    ("righty", MappingTarget::Axis(Axis::RightStickY)),
+    ("leftz", MappingTarget::Axis(Axis::LeftZ)),
+    ("rightz", MappingTarget::Axis(Axis::RightZ)),
+    ("dpadx", MappingTarget::Axis(Axis::DPadX)),
+    ("dpady", MappingTarget::Axis(Axis::DPadY)),
+    ("unknownaxis", MappingTarget::Axis(Axis::Unknown)),
+];
+
+fn target_name(target: MappingTarget) -> &'static str {
+    TARGET_NAMES
+        .iter()
+        .find(|(_, t)| *t == target)
+        .map(|(name, _)| *name)
+        .unwrap()
+}
+
+fn target_from_name(name: &str) -> Option<MappingTarget> {
+    TARGET_NAMES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, target)| *target)
+}
+
+/// Builds a `GamepadMapping` by asking the player to press each
+/// button and move each axis in turn (i.e. a "map your controller" screen)
+///
+/// Started with `AppContext::record_gamepad_mapping`. While recording,
+/// input from the gamepad is consumed by the recorder instead of being
+/// passed to the Game. The game should show a prompt for `current`, and
+/// call `AppContext::finish_gamepad_mapping` once `is_finished`
+/// returns true.
+///
+/// Buttons can be mapped to raw buttons or axes (e.g. for triggers that
+/// report as axes), and axes can only be mapped to raw axes.
+#[derive(Debug, Clone)]
+pub struct MappingRecorder {
+    dev: DeviceId,
+    mapping: GamepadMapping,
+    targets: Vec<MappingTarget>,
+    next: usize,
+
+    /// The first value seen for each raw axis, so that only
+    /// deliberate movement is recorded
+    axis_rest: HashMap<u32, f32>,
+}
+
+impl MappingRecorder {
+    /// The targets recorded by default, in the order they are asked for
+    pub const STANDARD_TARGETS: &'static [MappingTarget] = &[
+        MappingTarget::Button(GamepadButton::South),
+        MappingTarget::Button(GamepadButton::East),
+        MappingTarget::Button(GamepadButton::West),
+        MappingTarget::Button(GamepadButton::North),
+        MappingTarget::Button(GamepadButton::Select),
+        MappingTarget::Button(GamepadButton::Mode),
+        MappingTarget::Button(GamepadButton::Start),
+        MappingTarget::Button(GamepadButton::LeftThumb),
+        MappingTarget::Button(GamepadButton::RightThumb),
+        MappingTarget::Button(GamepadButton::LeftTrigger),
+        MappingTarget::Button(GamepadButton::RightTrigger),
+        MappingTarget::Button(GamepadButton::DPadUp),
+        MappingTarget::Button(GamepadButton::DPadDown),
+        MappingTarget::Button(GamepadButton::DPadLeft),
+        MappingTarget::Button(GamepadButton::DPadRight),
+        MappingTarget::Axis(Axis::LeftStickX),
+        MappingTarget::Axis(Axis::LeftStickY),
+        MappingTarget::Axis(Axis::RightStickX),
+        MappingTarget::Axis(Axis::RightStickY),
+        MappingTarget::Button(GamepadButton::LeftTrigger2),
+        MappingTarget::Button(GamepadButton::RightTrigger2),
+    ];
+
+    pub(crate) fn new(dev: DeviceId, info: &GamepadInfo, targets: Vec<MappingTarget>) -> Self {
+        Self {
+            dev,
+            mapping: GamepadMapping::new(info.uuid, &info.name),
+            targets,
+            next: 0,
+            axis_rest: HashMap::new(),
+        }
+    }
+
+    /// The gamepad being recorded
+    pub fn device(&self) -> DeviceId {
+        self.dev
+    }
+
+    /// Returns the button or axis the player should press or move next,
+    /// or None once every target has been recorded or skipped
+    pub fn current(&self) -> Option<MappingTarget> {
+        self.targets.get(self.next).cloned()
+    }
+
+    /// Returns the number of targets recorded or skipped so far,
+    /// and the total number of targets
+    pub fn progress(&self) -> (usize, usize) {
+        (self.next, self.targets.len())
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.targets.len()
+    }
+
+    /// Moves on to the next target without recording the current one
+    /// (e.g. because the gamepad doesn't have it)
+    pub fn skip(&mut self) {
+        self.next = (self.next + 1).min(self.targets.len());
+    }
+
+    /// The mapping recorded so far
+    pub fn mapping(&self) -> &GamepadMapping {
+        &self.mapping
+    }
+
+    pub(crate) fn into_mapping(self) -> GamepadMapping {
+        self.mapping
+    }
+
+    /// Records a raw input from the gamepad, if it's a deliberate press
+    /// or movement that fits the current target
+    pub(crate) fn record(&mut self, raw: RawInput, value: f32) {
+        let target = match self.current() {
+            Some(target) => target,
+            None => return,
+        };
+        let pressed = match raw {
+            RawInput::Button(_) => value > AXIS_THRESHOLD,
+            RawInput::Axis(code) => {
+                let rest = *self.axis_rest.entry(code).or_insert(value);
+                (value - rest).abs() > AXIS_THRESHOLD
+            }
+        };
+        let fits = !matches!((target, raw), (MappingTarget::Axis(_), RawInput::Button(_)));
+        let taken = self.mapping.get(raw).is_some();
+        if pressed && fits && !taken {
+            self.mapping.insert(raw, target);
+            self.next += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MappingSource;
+    use crate::PowerInfo;
+
+    #[test]
+    fn round_trip() {
+        let uuid = GamepadUuid::from_bytes([0x12; 16]);
+        let mut mapping = GamepadMapping::new(uuid, "Some, Pad");
+        for (i, (_, target)) in TARGET_NAMES.iter().enumerate() {
+            let raw = match i % 2 {
+                0 => RawInput::Button(i as u32 * 7),
+                _ => RawInput::Axis(i as u32 * 7),
+            };
+            mapping.insert(raw, *target);
+        }
+        assert_eq!(mapping.entries().len(), TARGET_NAMES.len());
+
+        let printed = mapping.to_string();
+        let parsed: GamepadMapping = printed.parse().unwrap();
+        assert_eq!(parsed, mapping);
+        assert_eq!(parsed.name(), "Some  Pad");
+        assert_eq!(parsed.to_string(), printed);
+        assert_eq!(
+            parsed.get(RawInput::Button(0)),
+            Some(MappingTarget::Button(GamepadButton::South))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_strings() {
+        let uuid = GamepadUuid::from_bytes([0x12; 16]);
+        let bad = [
+            "not a uuid,Pad,a:b0,".to_owned(),
+            format!("{}", uuid),
+            format!("{},Pad,a-b0,", uuid),
+            format!("{},Pad,north:b0,", uuid),
+            format!("{},Pad,a:c0,", uuid),
+            format!("{},Pad,a:b,", uuid),
+            format!("{},Pad,leftx:a-1,", uuid),
+        ];
+        for s in &bad {
+            assert!(s.parse::<GamepadMapping>().is_err(), "{:?}", s);
+        }
+        let ok = format!("{},Pad,", uuid).parse::<GamepadMapping>().unwrap();
+        assert!(ok.entries().is_empty());
+    }
+
+    fn recorder(targets: &[MappingTarget]) -> MappingRecorder {
+        let info = GamepadInfo {
+            name: "Pad".to_owned(),
+            os_name: "Pad".to_owned(),
+            uuid: GamepadUuid::from_bytes([0x12; 16]),
+            mapping: MappingSource::None,
+            power: PowerInfo::Wired,
+            ff_supported: false,
+        };
+        MappingRecorder::new(DeviceId::synthetic(0), &info, targets.to_vec())
+    }
+
+    const SOUTH: MappingTarget = MappingTarget::Button(GamepadButton::South);
+    const EAST: MappingTarget = MappingTarget::Button(GamepadButton::East);
+    const LEFT_X: MappingTarget = MappingTarget::Axis(Axis::LeftStickX);
+
+    #[test]
+    fn records_buttons_and_axes_in_order() {
+        let mut recorder = recorder(&[SOUTH, LEFT_X]);
+        recorder.record(RawInput::Button(3), 0.2);
+        assert_eq!(recorder.current(), Some(SOUTH));
+        recorder.record(RawInput::Button(3), 1.0);
+        assert_eq!(recorder.current(), Some(LEFT_X));
+        recorder.record(RawInput::Axis(0), 0.0);
+        recorder.record(RawInput::Axis(0), 0.3);
+        assert_eq!(recorder.current(), Some(LEFT_X));
+        recorder.record(RawInput::Axis(0), -0.8);
+        assert!(recorder.is_finished());
+        assert_eq!(recorder.progress(), (2, 2));
+        assert_eq!(
+            recorder.into_mapping().entries(),
+            [(RawInput::Button(3), SOUTH), (RawInput::Axis(0), LEFT_X)]
+        );
+    }
+
+    #[test]
+    fn skipped_targets_are_left_out() {
+        let mut recorder = recorder(&[SOUTH, EAST]);
+        recorder.skip();
+        recorder.record(RawInput::Button(5), 1.0);
+        assert!(recorder.is_finished());
+        recorder.skip();
+        recorder.record(RawInput::Button(6), 1.0);
+        assert_eq!(recorder.progress(), (2, 2));
+        assert_eq!(recorder.mapping().entries(), [(RawInput::Button(5), EAST)]);
+    }
+
+    #[test]
+    fn inputs_are_only_recorded_once() {
+        let mut recorder = recorder(&[SOUTH, EAST]);
+        recorder.record(RawInput::Button(1), 1.0);
+        recorder.record(RawInput::Button(1), 1.0);
+        assert_eq!(recorder.current(), Some(EAST));
+        recorder.record(RawInput::Button(2), 1.0);
+        assert_eq!(
+            recorder.mapping().entries(),
+            [(RawInput::Button(1), SOUTH), (RawInput::Button(2), EAST)]
+        );
+    }
+
+    #[test]
+    fn buttons_can_be_axes_but_not_the_other_way_around() {
+        let trigger = MappingTarget::Button(GamepadButton::LeftTrigger2);
+        let mut recorder = recorder(&[trigger, LEFT_X]);
+        // triggers often rest at -1
+        recorder.record(RawInput::Axis(2), -1.0);
+        recorder.record(RawInput::Axis(2), 1.0);
+        assert_eq!(recorder.current(), Some(LEFT_X));
+        recorder.record(RawInput::Button(0), 1.0);
+        assert_eq!(recorder.current(), Some(LEFT_X));
+        assert_eq!(recorder.mapping().entries(), [(RawInput::Axis(2), trigger)]);
+    }
+
+    #[test]
+    fn axes_resting_off_center_are_not_input() {
+        let mut recorder = recorder(&[LEFT_X]);
+        recorder.record(RawInput::Axis(4), 0.9);
+        recorder.record(RawInput::Axis(4), 0.9);
+        assert_eq!(recorder.current(), Some(LEFT_X));
+        recorder.record(RawInput::Axis(4), 0.1);
+        assert!(recorder.is_finished());
+    }
+}
@@ -3,6 +3,8 @@ use crate::winit;
 /// A button on a mouse
 ///
 /// Basically follows winit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MouseButton {
     Left,
     Right,
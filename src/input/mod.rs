@@ -1,15 +1,38 @@
 use crate::gilrs::GamepadId;
 use crate::winit::event::DeviceId as WinitDeviceId;
 
+pub(crate) use gamepad::poll_info;
+
 mod axis;
+mod deadzone;
+mod gamepad;
 mod gbtn;
 mod key;
+mod keyinfo;
+mod mapping;
 mod mbtn;
+mod slots;
+mod state;
 
 pub use axis::Axis;
+pub use deadzone::AxisConfig;
+pub use deadzone::AxisResponse;
+pub use deadzone::Curve;
+pub use deadzone::Stick;
+pub use gamepad::GamepadInfo;
+pub use gamepad::GamepadUuid;
+pub use gamepad::MappingSource;
+pub use gamepad::PowerInfo;
 pub use gbtn::GamepadButton;
 pub use key::Key;
+pub use keyinfo::KeyInfo;
+pub use keyinfo::Modifiers;
+pub use mapping::GamepadMapping;
+pub use mapping::MappingRecorder;
+pub use mapping::MappingTarget;
+pub use mapping::RawInput;
 pub use mbtn::MouseButton;
+pub use state::InputState;
 
 /// An ID that identifies an external device
 /// Used for uniquely identifying the device that provided
@@ -21,6 +44,26 @@ pub struct DeviceId(Enum);
 enum Enum {
     Winit(WinitDeviceId),
     Gilrs(GamepadId),
+    Synthetic(u32),
+}
+
+impl DeviceId {
+    /// Creates an ID for a device that doesn't actually exist
+    ///
+    /// This is useful for feeding synthetic input to a Game
+    /// (e.g. with `Headless::schedule`). Synthetic IDs with the
+    /// same number are equal to each other, but never equal to
+    /// the ID of a real device
+    pub fn synthetic(id: u32) -> DeviceId {
+        DeviceId(Enum::Synthetic(id))
+    }
+
+    pub(crate) fn gilrs_id(self) -> Option<GamepadId> {
+        match self.0 {
+            Enum::Gilrs(id) => Some(id),
+            _ => None,
+        }
+    }
 }
 
 impl From<WinitDeviceId> for DeviceId {
@@ -0,0 +1,87 @@
+use crate::DeviceId;
+use crate::GamepadUuid;
+
+/// Assigns gamepads to players, so that e.g. player 2 stays player 2
+/// when their gamepad is unplugged and plugged back in
+///
+/// A disconnected gamepad keeps its slot reserved. When a gamepad
+/// connects, it gets the slot it had before, or else the slot reserved
+/// by a disconnected gamepad of the same model (since platforms don't
+/// always give a reconnected gamepad the same DeviceId), or else the
+/// lowest free slot.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PlayerSlots {
+    slots: Vec<Option<Slot>>,
+}
+
+#[derive(Debug, Clone)]
+struct Slot {
+    dev: DeviceId,
+    uuid: Option<GamepadUuid>,
+    connected: bool,
+}
+
+impl PlayerSlots {
+    /// Assigns a slot to a gamepad that just connected, and returns it
+    pub fn connect(&mut self, dev: DeviceId, uuid: Option<GamepadUuid>) -> usize {
+        let index = self
+            .position(|slot| slot.dev == dev)
+            .or_else(|| {
+                uuid.and_then(|uuid| {
+                    self.position(|slot| !slot.connected && slot.uuid == Some(uuid))
+                })
+            })
+            .or_else(|| self.slots.iter().position(Option::is_none))
+            .unwrap_or_else(|| {
+                self.slots.push(None);
+                self.slots.len() - 1
+            });
+        self.slots[index] = Some(Slot {
+            dev,
+            uuid,
+            connected: true,
+        });
+        index
+    }
+
+    /// Marks a gamepad as disconnected, keeping its slot reserved
+    pub fn disconnect(&mut self, dev: DeviceId) {
+        if let Some(index) = self.position(|slot| slot.dev == dev) {
+            if let Some(slot) = &mut self.slots[index] {
+                slot.connected = false;
+            }
+        }
+    }
+
+    /// Frees a slot, so that any gamepad can take it
+    pub fn release(&mut self, index: usize) {
+        if let Some(slot) = self.slots.get_mut(index) {
+            *slot = None;
+        }
+    }
+
+    /// Returns the slot of the given gamepad, whether or not it's
+    /// currently connected
+    pub fn slot_of(&self, dev: DeviceId) -> Option<usize> {
+        self.position(|slot| slot.dev == dev)
+    }
+
+    /// Returns the gamepad in the given slot, if it's connected
+    pub fn device_in(&self, index: usize) -> Option<DeviceId> {
+        match self.slots.get(index) {
+            Some(Some(slot)) if slot.connected => Some(slot.dev),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of slots, including free and reserved ones
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn position(&self, f: impl Fn(&Slot) -> bool) -> Option<usize> {
+        self.slots
+            .iter()
+            .position(|slot| slot.as_ref().map(&f).unwrap_or(false))
+    }
+}
@@ -0,0 +1,630 @@
+use super::deadzone::partner;
+use super::slots::PlayerSlots;
+use crate::Axis;
+use crate::AxisConfig;
+use crate::DeviceId;
+use crate::GamepadButton;
+use crate::GamepadInfo;
+use crate::GamepadMapping;
+use crate::GamepadUuid;
+use crate::InputEvent;
+use crate::Key;
+use crate::KeyInfo;
+use crate::MappingRecorder;
+use crate::MappingTarget;
+use crate::Modifiers;
+use crate::MouseButton;
+use crate::Options;
+use crate::RawInput;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::hash::Hash;
+
+/// A snapshot of the current state of all input devices
+///
+/// This allows input to be polled (e.g. from `Game::update`)
+/// instead of being handled in callbacks.
+///
+/// The `was_*_pressed` and `was_*_released` methods report changes
+/// since the previous call to `Game::update`. So every press is seen
+/// by exactly one update, even when `Options::update_rate` is set.
+///
+/// Retrieved with `AppContext::input`
+#[derive(Debug, Clone, Default)]
+pub struct InputState {
+    keys: Buttons<Key>,
+    scancodes: Buttons<u32>,
+    modifiers: Modifiers,
+
+    /// The key each scancode produced the last time it was pressed
+    last_keys: HashMap<u32, Key>,
+    mouse_buttons: Buttons<MouseButton>,
+    mouse_pos: [f32; 2],
+
+    /// Stored inverted, so that the window starts out focused
+    unfocused: bool,
+    cursor_inside: bool,
+    suspended: bool,
+    gamepads: HashMap<DeviceId, GamepadState>,
+    gamepad_infos: HashMap<DeviceId, GamepadInfo>,
+    gamepad_mappings: HashMap<GamepadUuid, GamepadMapping>,
+    mapping_recorder: Option<MappingRecorder>,
+    player_slots: PlayerSlots,
+    axis_config: AxisConfig,
+}
+
+#[derive(Debug, Clone, Default)]
+struct GamepadState {
+    buttons: Buttons<GamepadButton>,
+    button_values: HashMap<GamepadButton, f32>,
+
+    /// Axis values after deadzones are applied
+    axes: HashMap<Axis, f32>,
+
+    /// Axis values as reported by the device
+    raw_axes: HashMap<Axis, f32>,
+}
+
+impl InputState {
+    /// Returns true if the given key is currently held down
+    pub fn is_key_down(&self, key: Key) -> bool {
+        self.keys.is_down(key)
+    }
+
+    /// Returns true if the given key was pressed since the previous update
+    ///
+    /// Key repeats do not count as presses
+    pub fn was_key_pressed(&self, key: Key) -> bool {
+        self.keys.was_pressed(key)
+    }
+
+    /// Returns true if the given key was released since the previous update
+    pub fn was_key_released(&self, key: Key) -> bool {
+        self.keys.was_released(key)
+    }
+
+    /// Returns true if the key with the given scancode
+    /// (see `KeyInfo::scancode`) is currently held down
+    pub fn is_scancode_down(&self, scancode: u32) -> bool {
+        self.scancodes.is_down(scancode)
+    }
+
+    /// Returns the key that the physical key with the given scancode
+    /// produced the last time it was pressed
+    ///
+    /// winit has no way to look up the keyboard layout, so this is only
+    /// known for keys that have been pressed, and may be out of date if
+    /// the layout changed since.
+    pub fn last_key_at(&self, scancode: u32) -> Option<Key> {
+        self.last_keys.get(&scancode).cloned()
+    }
+
+    /// Returns the label (see `Key::label`) of the key that the physical
+    /// key with the given scancode produced the last time it was pressed
+    /// (see `last_key_at`)
+    pub fn last_label_at(&self, scancode: u32) -> Option<&'static str> {
+        self.last_key_at(scancode).map(Key::label)
+    }
+
+    /// Returns the scancode of the physical key that last produced
+    /// the given key when pressed, if any (see `last_key_at`)
+    pub fn last_scancode_of(&self, key: Key) -> Option<u32> {
+        self.last_keys
+            .iter()
+            .find(|(_, k)| **k == key)
+            .map(|(scancode, _)| *scancode)
+    }
+
+    /// Returns the modifiers held down as of the last key event
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+
+    /// Returns the last known position of the mouse
+    pub fn mouse_pos(&self) -> [f32; 2] {
+        self.mouse_pos
+    }
+
+    /// Returns true if the window has keyboard focus
+    /// (see `Game::focus_lost`)
+    pub fn is_focused(&self) -> bool {
+        !self.unfocused
+    }
+
+    /// Returns true if the mouse cursor is over the window
+    pub fn is_cursor_inside(&self) -> bool {
+        self.cursor_inside
+    }
+
+    /// Returns true if the game is not visible (see `Game::suspended`)
+    pub fn is_suspended(&self) -> bool {
+        self.suspended
+    }
+
+    /// Returns true if the given mouse button is currently held down
+    pub fn is_mouse_button_down(&self, button: MouseButton) -> bool {
+        self.mouse_buttons.is_down(button)
+    }
+
+    /// Returns true if the given mouse button was pressed since
+    /// the previous update
+    pub fn was_mouse_button_pressed(&self, button: MouseButton) -> bool {
+        self.mouse_buttons.was_pressed(button)
+    }
+
+    /// Returns true if the given mouse button was released since
+    /// the previous update
+    pub fn was_mouse_button_released(&self, button: MouseButton) -> bool {
+        self.mouse_buttons.was_released(button)
+    }
+
+    /// Returns the IDs of all gamepads that have provided any input
+    pub fn gamepads(&self) -> impl Iterator<Item = DeviceId> + '_ {
+        self.gamepads.keys().cloned()
+    }
+
+    /// Returns the IDs of all gamepads that are currently connected
+    ///
+    /// Unlike `gamepads`, this only includes real devices,
+    /// and includes gamepads that haven't provided any input yet
+    pub fn connected_gamepads(&self) -> impl Iterator<Item = DeviceId> + '_ {
+        self.gamepad_infos.keys().cloned()
+    }
+
+    /// Returns information about the given gamepad, if it is connected
+    pub fn gamepad_info(&self, dev: DeviceId) -> Option<&GamepadInfo> {
+        self.gamepad_infos.get(&dev)
+    }
+
+    /// Records new information about a gamepad, or with None,
+    /// that it is no longer connected
+    pub(crate) fn set_gamepad_info(&mut self, dev: DeviceId, info: Option<GamepadInfo>) {
+        match info {
+            Some(info) => self.gamepad_infos.insert(dev, info),
+            None => self.gamepad_infos.remove(&dev),
+        };
+    }
+
+    /// Returns the player slot of the given gamepad, starting from 0
+    /// for the first player
+    ///
+    /// Gamepads get a slot when they connect, and keep it while
+    /// disconnected, so a player who reconnects their gamepad gets their
+    /// old slot back. If it was disconnected, it may also be taken over
+    /// by another gamepad of the same model. Slots can be freed with
+    /// `AppContext::release_player_slot`.
+    pub fn player_slot(&self, dev: DeviceId) -> Option<usize> {
+        self.player_slots.slot_of(dev)
+    }
+
+    /// Returns the gamepad in the given player slot, if it's connected
+    pub fn player_gamepad(&self, slot: usize) -> Option<DeviceId> {
+        self.player_slots.device_in(slot)
+    }
+
+    /// Returns the number of player slots that have been handed out,
+    /// including those of disconnected gamepads
+    pub fn player_slot_count(&self) -> usize {
+        self.player_slots.len()
+    }
+
+    pub(crate) fn release_player_slot(&mut self, slot: usize) {
+        self.player_slots.release(slot);
+    }
+
+    /// Returns the custom mapping for gamepads with the given UUID,
+    /// if there is one
+    pub fn gamepad_mapping(&self, uuid: GamepadUuid) -> Option<&GamepadMapping> {
+        self.gamepad_mappings.get(&uuid)
+    }
+
+    pub(crate) fn set_gamepad_mapping(&mut self, mapping: GamepadMapping) {
+        self.gamepad_mappings.insert(mapping.uuid(), mapping);
+    }
+
+    pub(crate) fn remove_gamepad_mapping(&mut self, uuid: GamepadUuid) {
+        self.gamepad_mappings.remove(&uuid);
+    }
+
+    /// Returns the mapping recorder, if a mapping is being recorded
+    pub fn mapping_recorder(&self) -> Option<&MappingRecorder> {
+        self.mapping_recorder.as_ref()
+    }
+
+    pub(crate) fn mapping_recorder_mut(&mut self) -> &mut Option<MappingRecorder> {
+        &mut self.mapping_recorder
+    }
+
+    /// Passes a raw input to the mapping recorder, and returns true
+    /// if it was consumed by it
+    pub(crate) fn record_raw(&mut self, dev: DeviceId, raw: RawInput, value: f32) -> bool {
+        match &mut self.mapping_recorder {
+            Some(recorder) if recorder.device() == dev => {
+                recorder.record(raw, value);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns what the custom mapping of the given gamepad
+    /// maps the raw input to, if anything
+    pub(crate) fn remap(&self, dev: DeviceId, raw: RawInput) -> Option<MappingTarget> {
+        let uuid = self.gamepad_info(dev)?.uuid;
+        self.gamepad_mapping(uuid)?.get(raw)
+    }
+
+    /// Returns true if the given button is currently held down
+    /// on the given gamepad
+    pub fn is_gamepad_button_down(&self, dev: DeviceId, button: GamepadButton) -> bool {
+        self.gamepads
+            .get(&dev)
+            .map(|gamepad| gamepad.buttons.is_down(button))
+            .unwrap_or(false)
+    }
+
+    /// Returns true if the given button was pressed on the given
+    /// gamepad since the previous update
+    pub fn was_gamepad_button_pressed(&self, dev: DeviceId, button: GamepadButton) -> bool {
+        self.gamepads
+            .get(&dev)
+            .map(|gamepad| gamepad.buttons.was_pressed(button))
+            .unwrap_or(false)
+    }
+
+    /// Returns true if the given button was released on the given
+    /// gamepad since the previous update
+    pub fn was_gamepad_button_released(&self, dev: DeviceId, button: GamepadButton) -> bool {
+        self.gamepads
+            .get(&dev)
+            .map(|gamepad| gamepad.buttons.was_released(button))
+            .unwrap_or(false)
+    }
+
+    /// Returns how far the given button is pressed on the given gamepad,
+    /// between 0 and 1
+    ///
+    /// For analog buttons (e.g. `GamepadButton::LeftTrigger2` on most
+    /// gamepads) this is the last value reported with
+    /// `Game::gamepad_button_changed`. Digital buttons are either 0 or 1.
+    pub fn gamepad_button_value(&self, dev: DeviceId, button: GamepadButton) -> f32 {
+        let gamepad = match self.gamepads.get(&dev) {
+            Some(gamepad) => gamepad,
+            None => return 0.0,
+        };
+        match gamepad.button_values.get(&button) {
+            Some(value) => *value,
+            None if gamepad.buttons.is_down(button) => 1.0,
+            None => 0.0,
+        }
+    }
+
+    /// Returns the last known value of the given axis on the
+    /// given gamepad, or 0 if no value is known
+    ///
+    /// This is the value after deadzones are applied (see `AxisConfig`)
+    pub fn gamepad_axis(&self, dev: DeviceId, axis: Axis) -> f32 {
+        self.gamepads
+            .get(&dev)
+            .and_then(|gamepad| gamepad.axes.get(&axis).cloned())
+            .unwrap_or(0.0)
+    }
+
+    /// Returns the last known value of the given axis on the
+    /// given gamepad, as reported by the device
+    pub fn raw_gamepad_axis(&self, dev: DeviceId, axis: Axis) -> f32 {
+        self.gamepads
+            .get(&dev)
+            .and_then(|gamepad| gamepad.raw_axes.get(&axis).cloned())
+            .unwrap_or(0.0)
+    }
+
+    /// Returns how raw axis values are processed
+    pub fn axis_config(&self) -> &AxisConfig {
+        &self.axis_config
+    }
+
+    pub(crate) fn set_axis_config(&mut self, axis_config: AxisConfig) {
+        self.axis_config = axis_config;
+    }
+
+    /// Applies the input related parts of the Game's options
+    pub(crate) fn apply_options(&mut self, options: &Options) {
+        self.set_axis_config(options.axis_config.clone());
+        for mapping in &options.custom_gamepad_mappings {
+            self.set_gamepad_mapping(mapping.clone());
+        }
+    }
+
+    /// Turns an event from a device into the events the Game sees
+    ///
+    /// This marks key repeats, and applies deadzones to axis events.
+    /// Since moving one axis of a stick with a radial deadzone can change
+    /// the processed value of the other, this may return an event for
+    /// both axes. Axes whose processed value did not change are left out.
+    pub(crate) fn process(&mut self, event: InputEvent) -> Vec<InputEvent> {
+        match event {
+            InputEvent::KeyPressed(key, info) => {
+                let is_repeat = self.keys.is_down(key);
+                vec![InputEvent::KeyPressed(key, KeyInfo { is_repeat, ..info })]
+            }
+            InputEvent::KeyScancodePressed(info) => {
+                let is_repeat = self.scancodes.is_down(info.scancode);
+                vec![InputEvent::KeyScancodePressed(KeyInfo {
+                    is_repeat,
+                    ..info
+                })]
+            }
+            InputEvent::GamepadAxisChanged(dev, axis, value) => self.process_axis(dev, axis, value),
+            InputEvent::GamepadDisconnected(dev) => {
+                let mut events = self.release_gamepad(dev);
+                events.push(InputEvent::GamepadDisconnected(dev));
+                events
+            }
+            InputEvent::FocusLost => {
+                let mut events = self.release_all();
+                events.push(InputEvent::FocusLost);
+                events
+            }
+            event => vec![event],
+        }
+    }
+
+    /// Returns events that release every button held down on the
+    /// given gamepad, for when it disconnects
+    fn release_gamepad(&self, dev: DeviceId) -> Vec<InputEvent> {
+        match self.gamepads.get(&dev) {
+            Some(gamepad) => gamepad
+                .buttons
+                .down
+                .iter()
+                .map(|button| InputEvent::GamepadButtonReleased(dev, *button))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns events that release every key and mouse button
+    /// that is held down
+    fn release_all(&self) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+        let mut keys: Vec<_> = self.keys.down.iter().cloned().collect();
+        let mut scancodes: Vec<_> = self.scancodes.down.iter().cloned().collect();
+        scancodes.sort();
+        for scancode in scancodes {
+            let info = KeyInfo {
+                scancode,
+                ..KeyInfo::default()
+            };
+            match self.last_key_at(scancode).filter(|key| keys.contains(key)) {
+                Some(key) => {
+                    keys.retain(|k| *k != key);
+                    events.push(InputEvent::KeyReleased(key, info));
+                }
+                None => events.push(InputEvent::KeyScancodeReleased(info)),
+            }
+        }
+
+        // Keys whose scancode is shared with another key
+        // (e.g. synthetic presses, which tend to all use 0)
+        for key in keys {
+            events.push(InputEvent::KeyReleased(key, KeyInfo::default()));
+        }
+        for button in &self.mouse_buttons.down {
+            events.push(InputEvent::MouseButtonReleased(*button));
+        }
+        events
+    }
+
+    fn process_axis(&mut self, dev: DeviceId, axis: Axis, value: f32) -> Vec<InputEvent> {
+        let gamepad = self.gamepads.entry(dev).or_default();
+        gamepad.raw_axes.insert(axis, value);
+        let raw = |axis: Option<Axis>| {
+            axis.and_then(|axis| gamepad.raw_axes.get(&axis).cloned())
+                .unwrap_or(0.0)
+        };
+        let mut events = Vec::new();
+        for axis in std::iter::once(axis).chain(partner(axis)) {
+            let value = self
+                .axis_config
+                .apply(axis, raw(Some(axis)), raw(partner(axis)));
+            if gamepad.axes.get(&axis).cloned().unwrap_or(0.0) != value {
+                events.push(InputEvent::GamepadAxisChanged(dev, axis, value));
+            }
+        }
+        events
+    }
+
+    /// Updates the state to reflect the given event
+    pub(crate) fn handle(&mut self, event: &InputEvent) {
+        match *event {
+            InputEvent::Char(_) | InputEvent::Scroll(_) | InputEvent::MouseMotion(_) => {}
+            InputEvent::KeyPressed(key, info) => {
+                self.keys.press(key);
+                self.press_scancode(info);
+                self.last_keys.insert(info.scancode, key);
+            }
+            InputEvent::KeyReleased(key, info) => {
+                self.keys.release(key);
+                self.release_scancode(info);
+            }
+            InputEvent::KeyScancodePressed(info) => self.press_scancode(info),
+            InputEvent::KeyScancodeReleased(info) => self.release_scancode(info),
+            InputEvent::MouseMoved(pos) => self.mouse_pos = pos,
+            InputEvent::MouseButtonPressed(button) => self.mouse_buttons.press(button),
+            InputEvent::MouseButtonReleased(button) => self.mouse_buttons.release(button),
+            InputEvent::FocusGained => self.unfocused = false,
+            InputEvent::FocusLost => {
+                self.unfocused = true;
+                self.modifiers = Modifiers::default();
+            }
+            InputEvent::CursorEntered => self.cursor_inside = true,
+            InputEvent::CursorLeft => self.cursor_inside = false,
+            InputEvent::Suspended => self.suspended = true,
+            InputEvent::Resumed => self.suspended = false,
+            InputEvent::GamepadConnected(dev) => {
+                self.gamepads.entry(dev).or_default();
+                let uuid = self.gamepad_info(dev).map(|info| info.uuid);
+                self.player_slots.connect(dev, uuid);
+            }
+            InputEvent::GamepadDisconnected(dev) => {
+                self.gamepads.remove(&dev);
+                self.player_slots.disconnect(dev);
+            }
+            InputEvent::GamepadButtonPressed(dev, button) => {
+                self.gamepads.entry(dev).or_default().buttons.press(button)
+            }
+            InputEvent::GamepadButtonReleased(dev, button) => self
+                .gamepads
+                .entry(dev)
+                .or_default()
+                .buttons
+                .release(button),
+            InputEvent::GamepadButtonChanged(dev, button, value) => {
+                self.gamepads
+                    .entry(dev)
+                    .or_default()
+                    .button_values
+                    .insert(button, value);
+            }
+            InputEvent::GamepadAxisChanged(dev, axis, value) => {
+                self.gamepads
+                    .entry(dev)
+                    .or_default()
+                    .axes
+                    .insert(axis, value);
+            }
+        }
+    }
+
+    fn press_scancode(&mut self, info: KeyInfo) {
+        self.scancodes.press(info.scancode);
+        self.modifiers = info.modifiers;
+    }
+
+    fn release_scancode(&mut self, info: KeyInfo) {
+        self.scancodes.release(info.scancode);
+        self.modifiers = info.modifiers;
+    }
+
+    /// Should be called after each call to `Game::update`, so that
+    /// each press and release is only reported once
+    pub(crate) fn clear_transitions(&mut self) {
+        self.keys.clear_transitions();
+        self.scancodes.clear_transitions();
+        self.mouse_buttons.clear_transitions();
+        for gamepad in self.gamepads.values_mut() {
+            gamepad.buttons.clear_transitions();
+        }
+    }
+}
+
+/// Tracks which of a set of buttons are held down,
+/// and which were pressed or released recently
+#[derive(Debug, Clone)]
+struct Buttons<B: Eq + Hash> {
+    down: HashSet<B>,
+    pressed: HashSet<B>,
+    released: HashSet<B>,
+}
+
+impl<B: Eq + Hash> Default for Buttons<B> {
+    fn default() -> Self {
+        Self {
+            down: HashSet::new(),
+            pressed: HashSet::new(),
+            released: HashSet::new(),
+        }
+    }
+}
+
+impl<B: Copy + Eq + Hash> Buttons<B> {
+    fn is_down(&self, button: B) -> bool {
+        self.down.contains(&button)
+    }
+
+    fn was_pressed(&self, button: B) -> bool {
+        self.pressed.contains(&button)
+    }
+
+    fn was_released(&self, button: B) -> bool {
+        self.released.contains(&button)
+    }
+
+    fn press(&mut self, button: B) {
+        // If the button is already down, this is a repeat
+        if self.down.insert(button) {
+            self.pressed.insert(button);
+        }
+    }
+
+    fn release(&mut self, button: B) {
+        if self.down.remove(&button) {
+            self.released.insert(button);
+        }
+    }
+
+    fn clear_transitions(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn send(input: &mut InputState, event: InputEvent) -> Vec<InputEvent> {
+        let events = input.process(event);
+        for event in &events {
+            input.handle(event);
+        }
+        events
+    }
+
+    #[test]
+    fn disconnect_releases_held_buttons() {
+        let mut input = InputState::default();
+        let dev = DeviceId::synthetic(0);
+        send(&mut input, InputEvent::GamepadConnected(dev));
+        send(
+            &mut input,
+            InputEvent::GamepadButtonPressed(dev, GamepadButton::South),
+        );
+        let events = send(&mut input, InputEvent::GamepadDisconnected(dev));
+        assert_eq!(
+            events,
+            vec![
+                InputEvent::GamepadButtonReleased(dev, GamepadButton::South),
+                InputEvent::GamepadDisconnected(dev),
+            ]
+        );
+        assert!(!input.is_gamepad_button_down(dev, GamepadButton::South));
+    }
+
+    #[test]
+    fn focus_loss_releases_held_keys() {
+        let mut input = InputState::default();
+        let info = KeyInfo {
+            scancode: 30,
+            ..KeyInfo::default()
+        };
+        send(&mut input, InputEvent::KeyPressed(Key::A, info));
+        send(
+            &mut input,
+            InputEvent::MouseButtonPressed(MouseButton::Left),
+        );
+        let events = send(&mut input, InputEvent::FocusLost);
+        assert_eq!(
+            events,
+            vec![
+                InputEvent::KeyReleased(Key::A, info),
+                InputEvent::MouseButtonReleased(MouseButton::Left),
+                InputEvent::FocusLost,
+            ]
+        );
+        assert!(!input.is_key_down(Key::A));
+        assert!(!input.is_scancode_down(30));
+        assert!(!input.is_focused());
+    }
+}
@@ -1,33 +1,115 @@
 extern crate a2d;
 extern crate anyhow;
+#[cfg(feature = "audio")]
+extern crate cpal;
 extern crate futures;
 extern crate gilrs;
+extern crate image;
+#[cfg(feature = "ogg")]
+extern crate lewton;
+extern crate rusttype;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "aseprite")]
+extern crate serde_json;
 
 use a2d::winit;
 
+mod action;
+mod animation;
+mod asset;
+mod atlas;
+mod audio;
+mod camera;
+mod clock;
 mod context;
+mod cursor;
+mod event;
+mod font;
 mod game;
+mod headless;
 mod input;
+#[cfg(feature = "hot-reload")]
+mod reload;
+mod rumble;
+mod scene;
+mod sprite;
+mod timestep;
+mod viewport;
 mod window;
 
+pub use action::ActionKind;
+pub use action::ActionMap;
+pub use action::Binding;
+pub use action::Input;
+pub use animation::Animation;
+pub use animation::Animator;
+pub use animation::Frame;
+pub use animation::PlayMode;
+pub use asset::Asset;
+pub use asset::AssetSource;
+pub use asset::Assets;
+pub use asset::Data;
+pub use asset::Handle;
+pub use atlas::Atlas;
+pub use atlas::AtlasBuilder;
+pub use atlas::AtlasPage;
+pub use atlas::PackedAtlas;
+pub use atlas::Region;
+pub use audio::Audio;
+pub use audio::Bus;
+pub use audio::PlayOptions;
+pub use audio::Playback;
+pub use audio::Sound;
+pub use camera::Camera2D;
 pub use context::AppContext;
 pub use context::RenderContext;
+pub use cursor::CursorGrab;
+pub use cursor::CursorIcon;
+pub use cursor::CustomCursor;
+pub use event::InputEvent;
+pub use font::Font;
+pub use font::FontSheet;
 pub use game::Game;
 pub use game::Options;
+pub use headless::Headless;
+pub use headless::RecordedBatch;
+pub use headless::RecordedFrame;
 pub use input::Axis;
+pub use input::AxisConfig;
+pub use input::AxisResponse;
+pub use input::Curve;
 pub use input::DeviceId;
 pub use input::GamepadButton;
+pub use input::GamepadInfo;
+pub use input::GamepadMapping;
+pub use input::GamepadUuid;
+pub use input::InputState;
 pub use input::Key;
+pub use input::KeyInfo;
+pub use input::MappingRecorder;
+pub use input::MappingSource;
+pub use input::MappingTarget;
+pub use input::Modifiers;
 pub use input::MouseButton;
+pub use input::PowerInfo;
+pub use input::RawInput;
+pub use input::Stick;
+pub use scene::Scene;
+pub use scene::SceneContext;
+pub use scene::SceneStack;
+pub use sprite::Instance;
+pub use sprite::InstanceBuilder;
+pub use sprite::SpriteBatch;
+pub use sprite::SpriteSheet;
+pub use sprite::TextGrid;
 pub use window::run;
+pub use window::Fullscreen;
 pub use window::Window;
+pub use window::WindowOptions;
 
 // re-exported from a2d
 pub use a2d::Color;
 pub use a2d::Dimensions;
-pub use a2d::Instance;
 pub use a2d::Point;
 pub use a2d::Rect;
-pub use a2d::SpriteBatch;
-pub use a2d::SpriteSheet;
-pub use a2d::TextGrid;
@@ -0,0 +1,163 @@
+use crate::anyhow::Context;
+use crate::anyhow::Result;
+use crate::AppContext;
+use crate::Data;
+use crate::Font;
+use crate::Game;
+use crate::Sound;
+use crate::SpriteSheet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// Files loaded through the asset manager, along with the time they
+/// were last modified, shared with the thread that watches them
+#[derive(Default, Clone)]
+pub(crate) struct WatchList {
+    files: Arc<Mutex<Vec<Watched>>>,
+}
+
+struct Watched {
+    path: String,
+    file: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl WatchList {
+    pub fn add(&self, path: &str, file: PathBuf) {
+        let mut files = self.files.lock().unwrap();
+        if files.iter().all(|watched| watched.path != path) {
+            let modified = modified(&file);
+            files.push(Watched {
+                path: path.to_owned(),
+                file,
+                modified,
+            });
+        }
+    }
+
+    /// Returns the asset paths of all files that changed
+    /// since the last call
+    pub fn changed(&self) -> Vec<String> {
+        let mut changed = Vec::new();
+        for watched in self.files.lock().unwrap().iter_mut() {
+            let modified = modified(&watched.file);
+            if modified.is_some() && modified != watched.modified {
+                watched.modified = modified;
+                changed.push(watched.path.clone());
+            }
+        }
+        changed
+    }
+}
+
+fn modified(file: &PathBuf) -> Option<SystemTime> {
+    std::fs::metadata(file).and_then(|m| m.modified()).ok()
+}
+
+/// Reloads the asset at the given path, and lets the game know
+/// whether that worked
+pub(crate) fn reload<G: Game>(game: &mut G, actx: &mut AppContext, path: &str) -> Result<()> {
+    match reload_asset(actx, path) {
+        Ok(()) => game.asset_reloaded(actx, path),
+        Err(error) => game.asset_reload_failed(actx, path, error),
+    }
+}
+
+/// Replaces the asset at the given path with what's on disk now,
+/// keeping the old version if that fails
+fn reload_asset(actx: &mut AppContext, path: &str) -> Result<()> {
+    if let Some(handle) = actx.assets.find::<SpriteSheet>(path) {
+        let bytes = actx.assets.read(path)?;
+        let sheet = actx
+            .new_sheet_from_bytes(&bytes)
+            .with_context(|| format!("Failed to reload sprite sheet {:?}", path))?;
+        // swapped in place, so that existing batches pick it up
+        actx.assets.get(handle).replace(&sheet);
+    }
+    if let Some(handle) = actx.assets.find::<Data>(path) {
+        let bytes = actx.assets.read(path)?;
+        actx.assets.replace(handle, Data::new(bytes).into());
+    }
+    if let Some(handle) = actx.assets.find::<Sound>(path) {
+        let bytes = actx.assets.read(path)?;
+        let sound = if actx.assets.get(handle).is_streamed() {
+            Sound::streamed_from_bytes(bytes)
+        } else {
+            Sound::from_bytes(&bytes)
+        };
+        let sound = sound.with_context(|| format!("Failed to reload sound {:?}", path))?;
+        actx.assets.replace(handle, sound.into());
+    }
+    if let Some(handle) = actx.assets.find::<Font>(path) {
+        let bytes = actx.assets.read(path)?;
+        let font =
+            Font::from_bytes(bytes).with_context(|| format!("Failed to reload font {:?}", path))?;
+        actx.assets.replace(handle, font.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetSource;
+    use crate::Headless;
+    use crate::RenderContext;
+
+    #[derive(Default)]
+    struct Log {
+        reloaded: Vec<String>,
+        failed: Vec<String>,
+    }
+
+    impl Game for Log {
+        fn update(&mut self, _: &mut AppContext) -> Result<()> {
+            Ok(())
+        }
+
+        fn render(&mut self, _: &mut RenderContext) -> Result<()> {
+            Ok(())
+        }
+
+        fn asset_reloaded(&mut self, _: &mut AppContext, path: &str) -> Result<()> {
+            self.reloaded.push(path.to_owned());
+            Ok(())
+        }
+
+        fn asset_reload_failed(
+            &mut self,
+            _: &mut AppContext,
+            path: &str,
+            _: anyhow::Error,
+        ) -> Result<()> {
+            self.failed.push(path.to_owned());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failed_reloads_keep_the_old_asset() {
+        Headless::new([1, 1], |actx| {
+            let mut log = Log::default();
+            actx.assets
+                .set_source(AssetSource::embedded(&[("a.txt", b"old")]));
+            let handle = actx.assets.load_data("a.txt")?;
+
+            actx.assets
+                .set_source(AssetSource::embedded(&[("a.txt", b"new")]));
+            reload(&mut log, actx, "a.txt")?;
+            assert_eq!(actx.assets.get(handle).bytes(), b"new");
+
+            actx.assets.set_source(AssetSource::embedded(&[]));
+            reload(&mut log, actx, "a.txt")?;
+            assert_eq!(actx.assets.get(handle).bytes(), b"new");
+
+            assert_eq!(log.reloaded, ["a.txt"]);
+            assert_eq!(log.failed, ["a.txt"]);
+            Ok(log)
+        })
+        .unwrap();
+    }
+}
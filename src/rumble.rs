@@ -0,0 +1,162 @@
+use crate::gilrs::ff::BaseEffect;
+use crate::gilrs::ff::BaseEffectType;
+use crate::gilrs::ff::Effect;
+use crate::gilrs::ff::EffectBuilder;
+use crate::gilrs::ff::Repeat;
+use crate::gilrs::ff::Replay;
+use crate::gilrs::ff::Ticks;
+use crate::gilrs::GamepadId;
+use crate::gilrs::Gilrs;
+use crate::DeviceId;
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
+
+/// A request for the thread that owns Gilrs
+pub(crate) enum RumbleCommand {
+    Start {
+        id: GamepadId,
+        strong: u16,
+        weak: u16,
+        duration: Ticks,
+    },
+    Stop(GamepadId),
+}
+
+/// Sends rumble requests to the gilrs thread
+///
+/// If nothing is listening (i.e. when gamepads are disabled or
+/// when running headless), requests are silently dropped
+#[derive(Default)]
+pub(crate) struct Rumble {
+    sender: Option<Sender<RumbleCommand>>,
+}
+
+impl Rumble {
+    pub fn connect(&mut self) -> Receiver<RumbleCommand> {
+        let (sender, receiver) = mpsc::channel();
+        self.sender = Some(sender);
+        receiver
+    }
+
+    pub fn start(&self, dev: DeviceId, strong: f32, weak: f32, duration: f64) {
+        let duration = match ticks(duration) {
+            Some(duration) => duration,
+            None => return self.stop(dev),
+        };
+        if let Some(id) = dev.gilrs_id() {
+            self.send(RumbleCommand::Start {
+                id,
+                strong: magnitude(strong),
+                weak: magnitude(weak),
+                duration,
+            });
+        }
+    }
+
+    pub fn stop(&self, dev: DeviceId) {
+        if let Some(id) = dev.gilrs_id() {
+            self.send(RumbleCommand::Stop(id));
+        }
+    }
+
+    fn send(&self, command: RumbleCommand) {
+        if let Some(sender) = &self.sender {
+            // if the gilrs thread is gone, there's nothing to rumble anyway
+            let _ = sender.send(command);
+        }
+    }
+}
+
+fn magnitude(value: f32) -> u16 {
+    (value.clamp(0.0, 1.0) * u16::MAX as f32) as u16
+}
+
+/// Converts a duration in seconds to gilrs ticks, or None if it's
+/// too short to play at all
+///
+/// gilrs divides by the length of an effect, so an effect must
+/// last at least one tick
+fn ticks(duration: f64) -> Option<Ticks> {
+    if !(duration > 0.0 && duration.is_finite()) {
+        return None;
+    }
+    let ms = (duration * 1000.0).ceil().min(u32::MAX as f64) as u32;
+    Some(Ticks::from_ms(ms))
+}
+
+/// Carries out a rumble request on the gilrs thread
+///
+/// Gamepads without force feedback support are ignored,
+/// as are any errors from gilrs.
+pub(crate) fn apply(
+    gilrs: &mut Gilrs,
+    effects: &mut HashMap<GamepadId, Effect>,
+    command: RumbleCommand,
+) {
+    match command {
+        RumbleCommand::Start {
+            id,
+            strong,
+            weak,
+            duration,
+        } => {
+            // dropping the previous effect also stops it
+            effects.remove(&id);
+            let supported = gilrs
+                .connected_gamepad(id)
+                .map(|gamepad| gamepad.is_ff_supported())
+                .unwrap_or(false);
+            if !supported {
+                return;
+            }
+            let scheduling = Replay {
+                play_for: duration,
+                ..Replay::default()
+            };
+            let effect = EffectBuilder::new()
+                .add_effect(BaseEffect {
+                    kind: BaseEffectType::Strong { magnitude: strong },
+                    scheduling,
+                    ..BaseEffect::default()
+                })
+                .add_effect(BaseEffect {
+                    kind: BaseEffectType::Weak { magnitude: weak },
+                    scheduling,
+                    ..BaseEffect::default()
+                })
+                .repeat(Repeat::For(duration))
+                .gamepads(&[id])
+                .finish(gilrs);
+            if let Ok(effect) = effect {
+                if effect.play().is_ok() {
+                    effects.insert(id, effect);
+                }
+            }
+        }
+        RumbleCommand::Stop(id) => {
+            effects.remove(&id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_duration_does_not_play() {
+        assert_eq!(ticks(0.0), None);
+        assert_eq!(ticks(-1.0), None);
+        assert_eq!(ticks(f64::NAN), None);
+        assert_eq!(ticks(f64::INFINITY), None);
+    }
+
+    #[test]
+    fn short_duration_lasts_at_least_one_tick() {
+        let duration = ticks(0.000_001).unwrap();
+        assert!(duration > Ticks::default());
+        assert_eq!(ticks(0.5), Some(Ticks::from_ms(500)));
+    }
+}
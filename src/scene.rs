@@ -0,0 +1,838 @@
+use crate::anyhow::Result;
+use crate::AppContext;
+use crate::Axis;
+use crate::DeviceId;
+use crate::Game;
+use crate::GamepadButton;
+use crate::Key;
+use crate::KeyInfo;
+use crate::MouseButton;
+use crate::Options;
+use crate::RenderContext;
+use crate::SpriteBatch;
+use std::ops::Deref;
+use std::ops::DerefMut;
+
+/// A single screen or state of a game (e.g. title screen,
+/// gameplay, pause menu), managed by a SceneStack
+///
+/// The methods here mirror the ones on Game, except that they
+/// receive a SceneContext, which allows switching between scenes.
+///
+/// Input is only delivered to the scene on top of the stack.
+#[allow(unused_variables)]
+pub trait Scene: 'static {
+    /// Called when this scene is added to the stack
+    fn on_enter(&mut self, sctx: &mut SceneContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when this scene is removed from the stack
+    fn on_exit(&mut self, sctx: &mut SceneContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when another scene is pushed on top of this one
+    fn on_pause(&mut self, sctx: &mut SceneContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when this scene is on top of the stack again,
+    /// after the scene above it was popped
+    fn on_resume(&mut self, sctx: &mut SceneContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// If true, the scene below this one keeps getting updated
+    /// while this one is on top of it
+    ///
+    /// By default, false
+    fn update_below(&self) -> bool {
+        false
+    }
+
+    /// If true, the scene below this one keeps getting rendered
+    /// while this one is on top of it (e.g. for a pause menu that
+    /// is drawn over the paused game)
+    ///
+    /// By default, false
+    fn render_below(&self) -> bool {
+        false
+    }
+
+    /// See `Game::update`
+    fn update(&mut self, sctx: &mut SceneContext) -> Result<()>;
+
+    /// Called when drawing on the screen is requested
+    ///
+    /// Since more than one scene may be drawn, rather than calling
+    /// `RenderContext::render` directly, scenes should add the
+    /// batches they want drawn to `batches`.
+    /// Scenes higher up in the stack are drawn on top.
+    ///
+    /// `alpha` is the same as `RenderContext::alpha`
+    fn render<'a>(
+        &'a mut self,
+        sctx: &mut SceneContext,
+        alpha: f32,
+        batches: &mut Vec<&'a SpriteBatch>,
+    ) -> Result<()>;
+
+    /// See `Game::on_error`
+    fn on_error(&mut self, sctx: &mut SceneContext, error: anyhow::Error) -> Result<()> {
+        Err(error)
+    }
+
+    /// See `Game::resize`
+    ///
+    /// Unlike the other methods, this is called for every scene
+    /// in the stack, not just the one on top
+    fn resize(&mut self, sctx: &mut SceneContext, width: u32, height: u32) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::asset_reloaded`
+    ///
+    /// Like `resize`, this is called for every scene in the stack
+    fn asset_reloaded(&mut self, sctx: &mut SceneContext, path: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::asset_reload_failed`
+    ///
+    /// Like `asset_reloaded`, this is called for every scene in the
+    /// stack. The error is ignored unless one of them returns it
+    fn asset_reload_failed(
+        &mut self,
+        sctx: &mut SceneContext,
+        path: &str,
+        error: &anyhow::Error,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::focus_gained`
+    ///
+    /// Like `resize`, this is called for every scene in the stack,
+    /// and so are the other window state methods below
+    fn focus_gained(&mut self, sctx: &mut SceneContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::focus_lost`
+    fn focus_lost(&mut self, sctx: &mut SceneContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::cursor_entered`
+    fn cursor_entered(&mut self, sctx: &mut SceneContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::cursor_left`
+    fn cursor_left(&mut self, sctx: &mut SceneContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::suspended`
+    ///
+    /// Not to be confused with `on_pause`, which is about
+    /// the scene stack rather than the window
+    fn suspended(&mut self, sctx: &mut SceneContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::resumed`
+    fn resumed(&mut self, sctx: &mut SceneContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::char`
+    fn char(&mut self, sctx: &mut SceneContext, ch: char) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::key_pressed`
+    ///
+    /// Unlike with Game, there is no default behavior for Escape
+    fn key_pressed(&mut self, sctx: &mut SceneContext, key: Key, info: KeyInfo) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::key_released`
+    fn key_released(&mut self, sctx: &mut SceneContext, key: Key, info: KeyInfo) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::key_scancode_pressed`
+    fn key_scancode_pressed(&mut self, sctx: &mut SceneContext, info: KeyInfo) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::key_scancode_released`
+    fn key_scancode_released(&mut self, sctx: &mut SceneContext, info: KeyInfo) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::mouse_moved`
+    fn mouse_moved(&mut self, sctx: &mut SceneContext, pos: [f32; 2]) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::mouse_button_pressed`
+    fn mouse_button_pressed(
+        &mut self,
+        sctx: &mut SceneContext,
+        pos: [f32; 2],
+        button: MouseButton,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::mouse_button_released`
+    fn mouse_button_released(
+        &mut self,
+        sctx: &mut SceneContext,
+        pos: [f32; 2],
+        button: MouseButton,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::mouse_motion`
+    fn mouse_motion(&mut self, sctx: &mut SceneContext, delta: [f32; 2]) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::scroll`
+    fn scroll(&mut self, sctx: &mut SceneContext, pos: [f32; 2], delta: [f32; 2]) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::gamepad_connected`
+    fn gamepad_connected(&mut self, sctx: &mut SceneContext, dev: DeviceId) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::gamepad_disconnected`
+    fn gamepad_disconnected(&mut self, sctx: &mut SceneContext, dev: DeviceId) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::gamepad_button_pressed`
+    fn gamepad_button_pressed(
+        &mut self,
+        sctx: &mut SceneContext,
+        dev: DeviceId,
+        button: GamepadButton,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::gamepad_button_released`
+    fn gamepad_button_released(
+        &mut self,
+        sctx: &mut SceneContext,
+        dev: DeviceId,
+        button: GamepadButton,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::gamepad_button_changed`
+    fn gamepad_button_changed(
+        &mut self,
+        sctx: &mut SceneContext,
+        dev: DeviceId,
+        button: GamepadButton,
+        value: f32,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::gamepad_axis_changed`
+    fn gamepad_axis_changed(
+        &mut self,
+        sctx: &mut SceneContext,
+        dev: DeviceId,
+        axis: Axis,
+        value: f32,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::action_pressed`
+    fn action_pressed(&mut self, sctx: &mut SceneContext, action: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::action_released`
+    fn action_released(&mut self, sctx: &mut SceneContext, action: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// See `Game::action_value`
+    fn action_value(&mut self, sctx: &mut SceneContext, action: &str, value: f32) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Passed to Scene methods in place of an AppContext
+///
+/// Derefs to the AppContext, and additionally allows scenes
+/// to request transitions to other scenes.
+///
+/// Transitions are applied once the current Scene method returns,
+/// in the order they were requested
+pub struct SceneContext<'a, 'b> {
+    actx: &'a mut AppContext<'b>,
+    transitions: &'a mut Vec<Transition>,
+}
+
+enum Transition {
+    Push(Box<dyn Scene>),
+    Pop,
+    Replace(Box<dyn Scene>),
+}
+
+impl<'a, 'b> SceneContext<'a, 'b> {
+    pub fn actx(&mut self) -> &mut AppContext<'b> {
+        self.actx
+    }
+
+    /// Pushes a new scene on top of the stack
+    ///
+    /// The current top scene is paused (see `Scene::on_pause`)
+    pub fn push<S: Scene>(&mut self, scene: S) {
+        self.transitions.push(Transition::Push(Box::new(scene)));
+    }
+
+    /// Removes the scene on top of the stack
+    ///
+    /// If this leaves the stack empty, the game exits
+    pub fn pop(&mut self) {
+        self.transitions.push(Transition::Pop);
+    }
+
+    /// Replaces the scene on top of the stack with a new one
+    pub fn replace<S: Scene>(&mut self, scene: S) {
+        self.transitions.push(Transition::Replace(Box::new(scene)));
+    }
+}
+
+impl<'a, 'b> Deref for SceneContext<'a, 'b> {
+    type Target = AppContext<'b>;
+
+    fn deref(&self) -> &AppContext<'b> {
+        self.actx
+    }
+}
+
+impl<'a, 'b> DerefMut for SceneContext<'a, 'b> {
+    fn deref_mut(&mut self) -> &mut AppContext<'b> {
+        self.actx
+    }
+}
+
+/// A Game made of a stack of Scenes
+///
+/// Only the scene on top of the stack receives input, but
+/// scenes below it may also be updated and rendered
+/// (see `Scene::update_below` and `Scene::render_below`).
+///
+/// When the last scene is popped, the game exits
+pub struct SceneStack {
+    scenes: Vec<Box<dyn Scene>>,
+    options: Options,
+}
+
+impl SceneStack {
+    /// Creates a new stack with the given scene at the bottom
+    pub fn new<S: Scene>(actx: &mut AppContext, scene: S) -> Result<Self> {
+        let mut stack = Self {
+            scenes: Vec::new(),
+            options: Options::default(),
+        };
+        stack.apply(actx, vec![Transition::Push(Box::new(scene))])?;
+        Ok(stack)
+    }
+
+    /// Sets the options returned from `Game::options`
+    pub fn with_options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Returns the number of scenes in the stack
+    pub fn len(&self) -> usize {
+        self.scenes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scenes.is_empty()
+    }
+
+    /// Calls `f` with the scene on top of the stack, and then
+    /// applies any transitions it requested
+    fn with_top<F>(&mut self, actx: &mut AppContext, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut dyn Scene, &mut SceneContext) -> Result<()>,
+    {
+        let mut transitions = Vec::new();
+        let result = match self.scenes.last_mut() {
+            Some(scene) => f(
+                scene.as_mut(),
+                &mut SceneContext {
+                    actx,
+                    transitions: &mut transitions,
+                },
+            ),
+            None => Ok(()),
+        };
+        self.apply(actx, transitions)?;
+        result
+    }
+
+    /// Calls `f` with every scene in the stack, from the bottom up,
+    /// and then applies any transitions they requested
+    fn with_all<F>(&mut self, actx: &mut AppContext, mut f: F) -> Result<()>
+    where
+        F: FnMut(&mut dyn Scene, &mut SceneContext) -> Result<()>,
+    {
+        let mut transitions = Vec::new();
+        let mut sctx = SceneContext {
+            actx,
+            transitions: &mut transitions,
+        };
+        let result = self
+            .scenes
+            .iter_mut()
+            .try_for_each(|scene| f(scene.as_mut(), &mut sctx));
+        self.apply(actx, transitions)?;
+        result
+    }
+
+    /// Returns the index of the lowest scene for which `pred` holds
+    /// for every scene above it
+    fn lowest_visible<F: Fn(&dyn Scene) -> bool>(&self, pred: F) -> usize {
+        let mut start = self.scenes.len().saturating_sub(1);
+        while start > 0 && pred(self.scenes[start].as_ref()) {
+            start -= 1;
+        }
+        start
+    }
+
+    fn apply(&mut self, actx: &mut AppContext, mut transitions: Vec<Transition>) -> Result<()> {
+        // Enter and exit hooks may themselves request transitions,
+        // so we keep going until there are none left
+        while !transitions.is_empty() {
+            let mut next = Vec::new();
+            let mut sctx = SceneContext {
+                actx,
+                transitions: &mut next,
+            };
+            for transition in transitions {
+                match transition {
+                    Transition::Push(mut scene) => {
+                        if let Some(top) = self.scenes.last_mut() {
+                            top.on_pause(&mut sctx)?;
+                        }
+                        scene.on_enter(&mut sctx)?;
+                        self.scenes.push(scene);
+                    }
+                    Transition::Pop => {
+                        if let Some(mut scene) = self.scenes.pop() {
+                            scene.on_exit(&mut sctx)?;
+                        }
+                        if let Some(top) = self.scenes.last_mut() {
+                            top.on_resume(&mut sctx)?;
+                        }
+                    }
+                    Transition::Replace(mut scene) => {
+                        if let Some(mut old) = self.scenes.pop() {
+                            old.on_exit(&mut sctx)?;
+                        }
+                        scene.on_enter(&mut sctx)?;
+                        self.scenes.push(scene);
+                    }
+                }
+            }
+            transitions = next;
+        }
+        if self.scenes.is_empty() {
+            actx.exit();
+        }
+        Ok(())
+    }
+}
+
+impl Game for SceneStack {
+    fn options(&self) -> Options {
+        self.options.clone()
+    }
+
+    fn update(&mut self, actx: &mut AppContext) -> Result<()> {
+        let start = self.lowest_visible(|scene| scene.update_below());
+        let mut transitions = Vec::new();
+        let mut sctx = SceneContext {
+            actx,
+            transitions: &mut transitions,
+        };
+        let result = self.scenes[start..]
+            .iter_mut()
+            .try_for_each(|scene| scene.update(&mut sctx));
+        self.apply(actx, transitions)?;
+        result
+    }
+
+    fn render(&mut self, rctx: &mut RenderContext) -> Result<()> {
+        let start = self.lowest_visible(|scene| scene.render_below());
+        let alpha = rctx.alpha();
+        let mut transitions = Vec::new();
+        let mut batches = Vec::new();
+        let mut sctx = SceneContext {
+            actx: rctx.actx(),
+            transitions: &mut transitions,
+        };
+        let result = self.scenes[start..]
+            .iter_mut()
+            .try_for_each(|scene| scene.render(&mut sctx, alpha, &mut batches));
+        rctx.render(&batches);
+        self.apply(rctx.actx(), transitions)?;
+        result
+    }
+
+    fn on_error(&mut self, actx: &mut AppContext, error: anyhow::Error) -> Result<()> {
+        if self.scenes.is_empty() {
+            Err(error)
+        } else {
+            self.with_top(actx, |scene, sctx| scene.on_error(sctx, error))
+        }
+    }
+
+    fn resize(&mut self, actx: &mut AppContext, width: u32, height: u32) -> Result<()> {
+        self.with_all(actx, |scene, sctx| scene.resize(sctx, width, height))
+    }
+
+    fn asset_reloaded(&mut self, actx: &mut AppContext, path: &str) -> Result<()> {
+        self.with_all(actx, |scene, sctx| scene.asset_reloaded(sctx, path))
+    }
+
+    fn asset_reload_failed(
+        &mut self,
+        actx: &mut AppContext,
+        path: &str,
+        error: anyhow::Error,
+    ) -> Result<()> {
+        self.with_all(actx, |scene, sctx| {
+            scene.asset_reload_failed(sctx, path, &error)
+        })
+    }
+
+    fn focus_gained(&mut self, actx: &mut AppContext) -> Result<()> {
+        self.with_all(actx, |scene, sctx| scene.focus_gained(sctx))
+    }
+
+    fn focus_lost(&mut self, actx: &mut AppContext) -> Result<()> {
+        self.with_all(actx, |scene, sctx| scene.focus_lost(sctx))
+    }
+
+    fn cursor_entered(&mut self, actx: &mut AppContext) -> Result<()> {
+        self.with_all(actx, |scene, sctx| scene.cursor_entered(sctx))
+    }
+
+    fn cursor_left(&mut self, actx: &mut AppContext) -> Result<()> {
+        self.with_all(actx, |scene, sctx| scene.cursor_left(sctx))
+    }
+
+    fn suspended(&mut self, actx: &mut AppContext) -> Result<()> {
+        self.with_all(actx, |scene, sctx| scene.suspended(sctx))
+    }
+
+    fn resumed(&mut self, actx: &mut AppContext) -> Result<()> {
+        self.with_all(actx, |scene, sctx| scene.resumed(sctx))
+    }
+
+    fn char(&mut self, actx: &mut AppContext, ch: char) -> Result<()> {
+        self.with_top(actx, |scene, sctx| scene.char(sctx, ch))
+    }
+
+    fn key_pressed(&mut self, actx: &mut AppContext, key: Key, info: KeyInfo) -> Result<()> {
+        self.with_top(actx, |scene, sctx| scene.key_pressed(sctx, key, info))
+    }
+
+    fn key_released(&mut self, actx: &mut AppContext, key: Key, info: KeyInfo) -> Result<()> {
+        self.with_top(actx, |scene, sctx| scene.key_released(sctx, key, info))
+    }
+
+    fn key_scancode_pressed(&mut self, actx: &mut AppContext, info: KeyInfo) -> Result<()> {
+        self.with_top(actx, |scene, sctx| scene.key_scancode_pressed(sctx, info))
+    }
+
+    fn key_scancode_released(&mut self, actx: &mut AppContext, info: KeyInfo) -> Result<()> {
+        self.with_top(actx, |scene, sctx| scene.key_scancode_released(sctx, info))
+    }
+
+    fn mouse_moved(&mut self, actx: &mut AppContext, pos: [f32; 2]) -> Result<()> {
+        self.with_top(actx, |scene, sctx| scene.mouse_moved(sctx, pos))
+    }
+
+    fn mouse_button_pressed(
+        &mut self,
+        actx: &mut AppContext,
+        pos: [f32; 2],
+        button: MouseButton,
+    ) -> Result<()> {
+        self.with_top(actx, |scene, sctx| {
+            scene.mouse_button_pressed(sctx, pos, button)
+        })
+    }
+
+    fn mouse_button_released(
+        &mut self,
+        actx: &mut AppContext,
+        pos: [f32; 2],
+        button: MouseButton,
+    ) -> Result<()> {
+        self.with_top(actx, |scene, sctx| {
+            scene.mouse_button_released(sctx, pos, button)
+        })
+    }
+
+    fn mouse_motion(&mut self, actx: &mut AppContext, delta: [f32; 2]) -> Result<()> {
+        self.with_top(actx, |scene, sctx| scene.mouse_motion(sctx, delta))
+    }
+
+    fn scroll(&mut self, actx: &mut AppContext, pos: [f32; 2], delta: [f32; 2]) -> Result<()> {
+        self.with_top(actx, |scene, sctx| scene.scroll(sctx, pos, delta))
+    }
+
+    fn gamepad_connected(&mut self, actx: &mut AppContext, dev: DeviceId) -> Result<()> {
+        self.with_top(actx, |scene, sctx| scene.gamepad_connected(sctx, dev))
+    }
+
+    fn gamepad_disconnected(&mut self, actx: &mut AppContext, dev: DeviceId) -> Result<()> {
+        self.with_top(actx, |scene, sctx| scene.gamepad_disconnected(sctx, dev))
+    }
+
+    fn gamepad_button_pressed(
+        &mut self,
+        actx: &mut AppContext,
+        dev: DeviceId,
+        button: GamepadButton,
+    ) -> Result<()> {
+        self.with_top(actx, |scene, sctx| {
+            scene.gamepad_button_pressed(sctx, dev, button)
+        })
+    }
+
+    fn gamepad_button_released(
+        &mut self,
+        actx: &mut AppContext,
+        dev: DeviceId,
+        button: GamepadButton,
+    ) -> Result<()> {
+        self.with_top(actx, |scene, sctx| {
+            scene.gamepad_button_released(sctx, dev, button)
+        })
+    }
+
+    fn gamepad_button_changed(
+        &mut self,
+        actx: &mut AppContext,
+        dev: DeviceId,
+        button: GamepadButton,
+        value: f32,
+    ) -> Result<()> {
+        self.with_top(actx, |scene, sctx| {
+            scene.gamepad_button_changed(sctx, dev, button, value)
+        })
+    }
+
+    fn gamepad_axis_changed(
+        &mut self,
+        actx: &mut AppContext,
+        dev: DeviceId,
+        axis: Axis,
+        value: f32,
+    ) -> Result<()> {
+        self.with_top(actx, |scene, sctx| {
+            scene.gamepad_axis_changed(sctx, dev, axis, value)
+        })
+    }
+
+    fn action_pressed(&mut self, actx: &mut AppContext, action: &str) -> Result<()> {
+        self.with_top(actx, |scene, sctx| scene.action_pressed(sctx, action))
+    }
+
+    fn action_released(&mut self, actx: &mut AppContext, action: &str) -> Result<()> {
+        self.with_top(actx, |scene, sctx| scene.action_released(sctx, action))
+    }
+
+    fn action_value(&mut self, actx: &mut AppContext, action: &str, value: f32) -> Result<()> {
+        self.with_top(actx, |scene, sctx| scene.action_value(sctx, action, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Headless;
+    use crate::InputEvent;
+    use std::cell::Cell;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+    /// Logs its hooks, and on 'p', 'o' and 'r' pushes, pops
+    /// or replaces itself with a scene named after the next char
+    struct Logged {
+        name: char,
+        log: Log,
+        next: Option<char>,
+
+        /// How many chars the scene was sent
+        chars: Rc<Cell<u32>>,
+    }
+
+    impl Logged {
+        fn new(name: char, log: &Log) -> Self {
+            Self {
+                name,
+                log: log.clone(),
+                next: None,
+                chars: Rc::default(),
+            }
+        }
+
+        fn note(&self, what: &str) {
+            self.log
+                .borrow_mut()
+                .push(format!("{} {}", self.name, what));
+        }
+    }
+
+    impl Scene for Logged {
+        fn on_enter(&mut self, _: &mut SceneContext) -> Result<()> {
+            self.note("enter");
+            Ok(())
+        }
+
+        fn on_exit(&mut self, _: &mut SceneContext) -> Result<()> {
+            self.note("exit");
+            Ok(())
+        }
+
+        fn on_pause(&mut self, _: &mut SceneContext) -> Result<()> {
+            self.note("pause");
+            Ok(())
+        }
+
+        fn on_resume(&mut self, _: &mut SceneContext) -> Result<()> {
+            self.note("resume");
+            Ok(())
+        }
+
+        fn update(&mut self, _: &mut SceneContext) -> Result<()> {
+            Ok(())
+        }
+
+        fn render<'a>(
+            &'a mut self,
+            _: &mut SceneContext,
+            _: f32,
+            _: &mut Vec<&'a SpriteBatch>,
+        ) -> Result<()> {
+            Ok(())
+        }
+
+        fn char(&mut self, sctx: &mut SceneContext, ch: char) -> Result<()> {
+            self.chars.set(self.chars.get() + 1);
+            match (self.next.take(), ch) {
+                (Some('p'), name) => sctx.push(Logged::new(name, &self.log)),
+                (Some('r'), name) => sctx.replace(Logged::new(name, &self.log)),
+                (None, 'o') => sctx.pop(),
+                (None, ch) => self.next = Some(ch),
+                _ => {}
+            }
+            Ok(())
+        }
+    }
+
+    fn stack() -> (Headless<SceneStack>, Log) {
+        let log = Log::default();
+        let first = Logged::new('a', &log);
+        let headless = Headless::new([100, 100], |actx| SceneStack::new(actx, first)).unwrap();
+        (headless, log)
+    }
+
+    fn type_str(headless: &mut Headless<SceneStack>, s: &str) {
+        for ch in s.chars() {
+            headless.send(InputEvent::Char(ch)).unwrap();
+        }
+    }
+
+    fn take(log: &Log) -> Vec<String> {
+        log.borrow_mut().drain(..).collect()
+    }
+
+    #[test]
+    fn push_pauses_and_pop_resumes() {
+        let (mut headless, log) = stack();
+        assert_eq!(take(&log), ["a enter"]);
+
+        type_str(&mut headless, "pb");
+        assert_eq!(take(&log), ["a pause", "b enter"]);
+        assert_eq!(headless.game().len(), 2);
+
+        type_str(&mut headless, "o");
+        assert_eq!(take(&log), ["b exit", "a resume"]);
+        assert_eq!(headless.game().len(), 1);
+        assert!(!headless.exited());
+    }
+
+    #[test]
+    fn replace_exits_before_entering() {
+        let (mut headless, log) = stack();
+        type_str(&mut headless, "pbrc");
+        take(&log);
+        type_str(&mut headless, "rd");
+        assert_eq!(take(&log), ["c exit", "d enter"]);
+        assert_eq!(headless.game().len(), 2);
+
+        // the scene below was never resumed, so popping
+        // the replacement is what resumes it
+        type_str(&mut headless, "o");
+        assert_eq!(take(&log), ["d exit", "a resume"]);
+    }
+
+    #[test]
+    fn input_only_reaches_the_top() {
+        let log = Log::default();
+        let first = Logged::new('a', &log);
+        let chars = first.chars.clone();
+        let mut headless = Headless::new([100, 100], |actx| SceneStack::new(actx, first)).unwrap();
+
+        type_str(&mut headless, "pb");
+        assert_eq!(chars.get(), 2);
+        type_str(&mut headless, "pc");
+        take(&log);
+        type_str(&mut headless, "oo");
+        assert_eq!(take(&log), ["c exit", "b resume", "b exit", "a resume"]);
+        assert_eq!(chars.get(), 2);
+
+        type_str(&mut headless, "x");
+        assert_eq!(chars.get(), 3);
+    }
+
+    #[test]
+    fn popping_the_last_scene_exits() {
+        let (mut headless, log) = stack();
+        take(&log);
+        type_str(&mut headless, "o");
+        assert_eq!(take(&log), ["a exit"]);
+        assert!(headless.game().is_empty());
+        assert!(headless.exited());
+    }
+}
@@ -0,0 +1,396 @@
+use crate::a2d;
+use crate::a2d::Graphics2D;
+use crate::anyhow::Result;
+use crate::Color;
+use crate::Rect;
+use std::cell::Cell;
+use std::cell::Ref;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// An image that sprites are drawn from
+///
+/// Created with e.g. `AppContext::new_sheet_from_bytes`.
+///
+/// When running headless, there's no GPU to put the image on,
+/// so a stand-in sheet is created instead. It can be used the same
+/// way as any other sheet, but is never drawn.
+pub struct SpriteSheet {
+    texture: RefCell<Option<Rc<a2d::SpriteSheet>>>,
+
+    /// Bumped whenever the texture is swapped out (see `replace`),
+    /// so that batches know to pick up the new one
+    generation: Cell<u64>,
+}
+
+impl SpriteSheet {
+    /// Decodes an image file, in any format supported by the `image` crate
+    pub(crate) fn from_bytes(graphics: Option<&mut Graphics2D>, bytes: &[u8]) -> Result<Rc<Self>> {
+        match graphics {
+            Some(graphics) => Ok(Self::wrap(a2d::SpriteSheet::from_bytes(graphics, bytes)?)),
+            None => {
+                image::load_from_memory(bytes)?;
+                Ok(Self::stand_in())
+            }
+        }
+    }
+
+    pub(crate) fn from_colors<C, V>(
+        graphics: Option<&mut Graphics2D>,
+        width: u32,
+        height: u32,
+        colors: V,
+    ) -> Result<Rc<Self>>
+    where
+        C: Into<Color>,
+        V: IntoIterator<Item = C>,
+    {
+        match graphics {
+            Some(graphics) => Ok(Self::wrap(a2d::SpriteSheet::from_colors(
+                graphics, width, height, colors,
+            )?)),
+            None => {
+                let count = colors.into_iter().count();
+                if count != (width * height) as usize {
+                    anyhow::bail!(
+                        "Expected {} colors for a {}x{} sprite sheet, but got {}",
+                        width * height,
+                        width,
+                        height,
+                        count
+                    );
+                }
+                Ok(Self::stand_in())
+            }
+        }
+    }
+
+    pub(crate) fn from_color<C: Into<Color>>(
+        graphics: Option<&mut Graphics2D>,
+        color: C,
+    ) -> Result<Rc<Self>> {
+        match graphics {
+            Some(graphics) => Ok(Self::wrap(a2d::SpriteSheet::from_color(graphics, color)?)),
+            None => Ok(Self::stand_in()),
+        }
+    }
+
+    pub(crate) fn from_rgba_bytes(
+        graphics: Option<&mut Graphics2D>,
+        width: u32,
+        height: u32,
+        bytes: Vec<u8>,
+    ) -> Result<Rc<Self>> {
+        if bytes.len() != (width * height * 4) as usize {
+            anyhow::bail!(
+                "Expected {} bytes of rgba data for a {}x{} sprite sheet, but got {}",
+                width * height * 4,
+                width,
+                height,
+                bytes.len()
+            );
+        }
+        match graphics {
+            Some(graphics) => Ok(Self::wrap(a2d::SpriteSheet::from_rgba_bytes(
+                graphics, width, height, bytes,
+            )?)),
+            None => Ok(Self::stand_in()),
+        }
+    }
+
+    pub(crate) fn wrap(texture: Rc<a2d::SpriteSheet>) -> Rc<Self> {
+        Rc::new(Self {
+            texture: RefCell::new(Some(texture)),
+            generation: Cell::new(0),
+        })
+    }
+
+    fn stand_in() -> Rc<Self> {
+        Rc::new(Self {
+            texture: RefCell::new(None),
+            generation: Cell::new(0),
+        })
+    }
+
+    /// Returns true if this sheet was created while running headless,
+    /// and so is never drawn
+    pub fn is_stand_in(&self) -> bool {
+        self.texture.borrow().is_none()
+    }
+
+    /// Swaps in the image of another sheet, so that every batch
+    /// drawing from this one draws the new image from now on
+    #[cfg(feature = "hot-reload")]
+    pub(crate) fn replace(&self, other: &SpriteSheet) {
+        *self.texture.borrow_mut() = other.texture();
+        self.generation.set(self.generation.get() + 1);
+    }
+
+    fn texture(&self) -> Option<Rc<a2d::SpriteSheet>> {
+        self.texture.borrow().clone()
+    }
+}
+
+/// A single sprite in a SpriteBatch
+///
+/// `src` is the part of the sheet that is drawn, as fractions of the
+/// sheet's size, and `dest` is where it's drawn on the screen.
+/// Both are [upper_left, lower_right] rects.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Instance {
+    src: [[f32; 2]; 2],
+    dest: [[f32; 2]; 2],
+    color_factor: [f32; 4],
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Self {
+            src: [[0.0, 0.0], [1.0, 1.0]],
+            dest: [[0.0, 0.0], [1.0, 1.0]],
+            color_factor: [1.0, 1.0, 1.0, 1.0],
+        }
+    }
+}
+
+/// Builds an Instance, starting from `Instance::default()`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InstanceBuilder {
+    instance: Instance,
+}
+
+impl Instance {
+    pub fn builder() -> InstanceBuilder {
+        InstanceBuilder::default()
+    }
+
+    pub fn src(&self) -> [[f32; 2]; 2] {
+        self.src
+    }
+
+    /// By default, set to the whole sheet (i.e. [[0, 0], [1, 1]])
+    pub fn set_src<R: Into<Rect>>(&mut self, src: R) {
+        self.src = corners(src.into());
+    }
+
+    pub fn dest(&self) -> [[f32; 2]; 2] {
+        self.dest
+    }
+
+    /// By default, set to [[0, 0], [1, 1]]
+    pub fn set_dest<R: Into<Rect>>(&mut self, dest: R) {
+        self.dest = corners(dest.into());
+    }
+
+    pub fn color_factor(&self) -> [f32; 4] {
+        self.color_factor
+    }
+
+    /// Multiplied with the color of every pixel of the sprite
+    ///
+    /// By default, set to white (i.e. [1, 1, 1, 1])
+    pub fn set_color_factor<C: Into<Color>>(&mut self, color: C) {
+        self.color_factor = color.into().0;
+    }
+
+    /// Copies this onto an a2d instance, moved by `offset`
+    fn apply(&self, instance: &mut a2d::Instance, offset: [f32; 2]) {
+        let [[x1, y1], [x2, y2]] = self.dest;
+        let [dx, dy] = offset;
+        instance.set_src(self.src);
+        instance.set_dest([[x1 + dx, y1 + dy], [x2 + dx, y2 + dy]]);
+        instance.set_color_factor(self.color_factor);
+    }
+}
+
+impl InstanceBuilder {
+    pub fn src<R: Into<Rect>>(mut self, src: R) -> Self {
+        self.instance.set_src(src);
+        self
+    }
+
+    pub fn dest<R: Into<Rect>>(mut self, dest: R) -> Self {
+        self.instance.set_dest(dest);
+        self
+    }
+
+    pub fn color_factor<C: Into<Color>>(mut self, color: C) -> Self {
+        self.instance.set_color_factor(color);
+        self
+    }
+
+    pub fn build(self) -> Instance {
+        self.instance
+    }
+}
+
+/// Sprites drawn from the same sheet, which are drawn together
+/// with `RenderContext::render`
+pub struct SpriteBatch {
+    sheet: Rc<SpriteSheet>,
+    instances: Vec<Instance>,
+
+    /// What was last handed to a2d, built lazily when drawing
+    drawn: RefCell<Option<Drawn>>,
+
+    /// Set for the batch of a TextGrid, which a2d fills in itself
+    grid: Option<Rc<RefCell<a2d::TextGrid>>>,
+}
+
+struct Drawn {
+    batch: a2d::SpriteBatch,
+    instances: Vec<Instance>,
+    offset: [f32; 2],
+    generation: u64,
+}
+
+impl SpriteBatch {
+    pub fn new(sheet: Rc<SpriteSheet>) -> Self {
+        Self {
+            sheet,
+            instances: Vec::new(),
+            drawn: RefCell::new(None),
+            grid: None,
+        }
+    }
+
+    pub fn sheet(&self) -> &Rc<SpriteSheet> {
+        &self.sheet
+    }
+
+    pub fn add(&mut self, instance: Instance) {
+        self.instances.push(instance);
+    }
+
+    pub fn get(&self, index: usize) -> &Instance {
+        &self.instances[index]
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> &mut Instance {
+        &mut self.instances[index]
+    }
+
+    /// Returns every instance, in the order they were added
+    ///
+    /// The characters of a TextGrid are placed by a2d, so they
+    /// aren't included here.
+    pub fn instances(&self) -> &[Instance] {
+        &self.instances
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    /// Removes every instance
+    pub fn clear(&mut self) {
+        self.instances.clear();
+    }
+
+    /// Returns the a2d batch to draw, bringing it up to date with
+    /// the instances, or None if the sheet is a stand-in
+    ///
+    /// Every instance is moved by `offset` (see `Viewport::offset`).
+    /// The characters of a TextGrid are placed by a2d, so they can't be.
+    fn prepare(&self, offset: [f32; 2]) -> Option<Ref<'_, a2d::SpriteBatch>> {
+        if let Some(grid) = &self.grid {
+            return Some(Ref::map(grid.borrow(), |grid| grid.batch()));
+        }
+        let texture = self.sheet.texture()?;
+        let generation = self.sheet.generation.get();
+        {
+            let mut drawn = self.drawn.borrow_mut();
+            let stale = match &*drawn {
+                Some(drawn) => {
+                    drawn.generation != generation
+                        || drawn.offset != offset
+                        || drawn.instances.len() > self.instances.len()
+                }
+                None => true,
+            };
+            if stale {
+                *drawn = Some(Drawn {
+                    batch: a2d::SpriteBatch::new(texture),
+                    instances: Vec::new(),
+                    offset,
+                    generation,
+                });
+            }
+            // only instances that changed since the last time
+            // are handed to a2d again
+            let drawn = drawn.as_mut().unwrap();
+            for (i, instance) in self.instances.iter().enumerate() {
+                match drawn.instances.get(i) {
+                    Some(old) if old == instance => {}
+                    Some(_) => {
+                        instance.apply(drawn.batch.get_mut(i), offset);
+                        drawn.instances[i] = *instance;
+                    }
+                    None => {
+                        let mut new = a2d::Instance::builder().build();
+                        instance.apply(&mut new, offset);
+                        drawn.batch.add(new);
+                        drawn.instances.push(*instance);
+                    }
+                }
+            }
+        }
+        Some(Ref::map(self.drawn.borrow(), |drawn| {
+            &drawn.as_ref().unwrap().batch
+        }))
+    }
+}
+
+/// A grid of characters drawn with the courier font bundled with a2d,
+/// created with `AppContext::new_text_grid`
+pub struct TextGrid {
+    grid: Rc<RefCell<a2d::TextGrid>>,
+    batch: SpriteBatch,
+}
+
+impl TextGrid {
+    pub(crate) fn new(grid: a2d::TextGrid, sheet: Rc<SpriteSheet>) -> Self {
+        let grid = Rc::new(RefCell::new(grid));
+        let mut batch = SpriteBatch::new(sheet);
+        batch.grid = Some(grid.clone());
+        Self { grid, batch }
+    }
+
+    /// Returns the rect the character at the given [row, column]
+    /// is drawn in
+    pub fn rect_for_coord(&self, coord: [u32; 2]) -> Rect {
+        self.grid.borrow().rect_for_coord(coord)
+    }
+
+    /// Sets the character at the given [row, column]
+    pub fn write_ch(&mut self, coord: [u32; 2], ch: char) {
+        self.grid.borrow_mut().write_ch(coord, ch);
+    }
+
+    /// Returns the batch to pass to `RenderContext::render`
+    pub fn batch(&self) -> &SpriteBatch {
+        &self.batch
+    }
+}
+
+/// Draws the given batches, each moved by its offset,
+/// skipping any with stand-in sheets
+pub(crate) fn draw(graphics: &mut Graphics2D, batches: &[(&SpriteBatch, [f32; 2])]) {
+    let prepared: Vec<_> = batches
+        .iter()
+        .filter_map(|(batch, offset)| batch.prepare(*offset))
+        .collect();
+    let batches: Vec<&a2d::SpriteBatch> = prepared.iter().map(|batch| &**batch).collect();
+    graphics.render(&batches);
+}
+
+fn corners(rect: Rect) -> [[f32; 2]; 2] {
+    [
+        [rect.upper_left.x, rect.upper_left.y],
+        [rect.lower_right.x, rect.lower_right.y],
+    ]
+}
@@ -0,0 +1,168 @@
+use crate::anyhow::bail;
+use crate::anyhow::Result;
+use std::time::Instant;
+
+/// Keeps track of how many times `Game::update` should be called
+/// each frame.
+///
+/// Without a fixed rate, update is simply called once per frame.
+///
+/// With a fixed rate, elapsed time is accumulated and update is called
+/// once for every full step that fits in the accumulator. Whatever is
+/// left over is exposed as `alpha` so that rendering can interpolate
+/// between the previous and current state.
+pub(crate) struct Timestep {
+    step: Option<f64>,
+    max_steps: u32,
+    accumulator: f64,
+    last: Option<Instant>,
+}
+
+impl Timestep {
+    /// Fails if the rate is not a positive number, or if there is a
+    /// rate but the maximum number of steps per frame is 0, since the
+    /// game would never be updated
+    ///
+    /// Without a rate, the maximum is ignored.
+    pub fn new(rate: Option<f64>, max_steps: u32) -> Result<Self> {
+        if let Some(rate) = rate {
+            if !(rate > 0.0 && rate.is_finite()) {
+                bail!("Options::update_rate must be positive, but is {}", rate);
+            }
+            if max_steps == 0 {
+                bail!("Options::max_updates_per_frame must be at least 1");
+            }
+        }
+        Ok(Self {
+            step: rate.map(|rate| 1.0 / rate),
+            max_steps,
+            accumulator: 0.0,
+            last: None,
+        })
+    }
+
+    /// Returns the length of a single step in seconds,
+    /// or None if updates are not done at a fixed rate
+    pub fn step(&self) -> Option<f64> {
+        self.step
+    }
+
+    /// Advances the clock and returns the number of times
+    /// `Game::update` should be called this frame
+    pub fn advance(&mut self, now: Instant) -> u32 {
+        let step = match self.step {
+            Some(step) => step,
+            None => {
+                self.last = Some(now);
+                return 1;
+            }
+        };
+
+        // On the very first frame, we run exactly one step
+        let elapsed = match self.last.replace(now) {
+            Some(last) => now.duration_since(last).as_secs_f64(),
+            None => step,
+        };
+
+        self.accumulator += elapsed;
+        let mut steps = 0;
+        while self.accumulator >= step && steps < self.max_steps {
+            self.accumulator -= step;
+            steps += 1;
+        }
+
+        // If we're still behind after the maximum number of steps,
+        // drop the excess time instead of trying to catch up
+        // (otherwise we could fall further and further behind)
+        if self.accumulator >= step {
+            self.accumulator %= step;
+        }
+
+        steps
+    }
+
+    /// Forgets about the time since the last step, so that updates
+    /// resume as if on the very first frame (e.g. after being paused)
+    pub fn pause(&mut self) {
+        self.accumulator = 0.0;
+        self.last = None;
+    }
+
+    /// How far along we are between the last step and the next,
+    /// as a fraction between 0 and 1.
+    ///
+    /// Always 1 if updates are not done at a fixed rate
+    pub fn alpha(&self) -> f32 {
+        match self.step {
+            Some(step) => (self.accumulator / step) as f32,
+            None => 1.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn after(start: Instant, secs: f64) -> Instant {
+        start + Duration::from_secs_f64(secs)
+    }
+
+    #[test]
+    fn variable_rate_updates_once_per_frame() {
+        let start = Instant::now();
+        let mut timestep = Timestep::new(None, 5).unwrap();
+        assert_eq!(timestep.advance(start), 1);
+        assert_eq!(timestep.advance(after(start, 10.0)), 1);
+        assert_eq!(timestep.alpha(), 1.0);
+    }
+
+    #[test]
+    fn fixed_rate_accumulates_leftover_time() {
+        let start = Instant::now();
+        let mut timestep = Timestep::new(Some(10.0), 5).unwrap();
+        assert_eq!(timestep.advance(start), 1);
+
+        // a quarter of a step isn't enough for another update
+        assert_eq!(timestep.advance(after(start, 0.025)), 0);
+        assert!((timestep.alpha() - 0.25).abs() < 1e-4);
+
+        // but it carries over to the next frame
+        assert_eq!(timestep.advance(after(start, 0.225)), 2);
+        assert!((timestep.alpha() - 0.25).abs() < 1e-4);
+    }
+
+    #[test]
+    fn fixed_rate_drops_time_past_the_limit() {
+        let start = Instant::now();
+        let mut timestep = Timestep::new(Some(10.0), 3).unwrap();
+        assert_eq!(timestep.advance(start), 1);
+        assert_eq!(timestep.advance(after(start, 1.03)), 3);
+        assert!((timestep.alpha() - 0.3).abs() < 1e-4);
+
+        // the dropped time isn't caught up on later
+        assert_eq!(timestep.advance(after(start, 1.11)), 1);
+        assert!((timestep.alpha() - 0.1).abs() < 1e-4);
+    }
+
+    #[test]
+    fn pause_restarts_from_a_single_step() {
+        let start = Instant::now();
+        let mut timestep = Timestep::new(Some(10.0), 5).unwrap();
+        assert_eq!(timestep.advance(start), 1);
+        timestep.pause();
+        assert_eq!(timestep.advance(after(start, 60.0)), 1);
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+
+    #[test]
+    fn invalid_options_are_rejected() {
+        assert!(Timestep::new(Some(0.0), 5).is_err());
+        assert!(Timestep::new(Some(-60.0), 5).is_err());
+        assert!(Timestep::new(Some(f64::NAN), 5).is_err());
+        assert!(Timestep::new(Some(f64::INFINITY), 5).is_err());
+        assert!(Timestep::new(Some(60.0), 0).is_err());
+        assert!(Timestep::new(None, 0).is_ok());
+    }
+}
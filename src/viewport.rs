@@ -0,0 +1,202 @@
+use crate::anyhow::Result;
+use crate::AppContext;
+use crate::Instance;
+use crate::Options;
+use crate::SpriteBatch;
+
+/// Keeps track of how coordinates the game sees map
+/// to physical pixels in the window
+///
+/// Without a virtual size, the game sees logical pixels
+/// (i.e. physical pixels divided by the window's scale factor).
+///
+/// With a virtual size, the game sees a fixed size screen,
+/// scaled up as much as fits in the window while keeping its
+/// aspect ratio, and centered in the window. The space left over
+/// on either side is covered by black bars.
+pub(crate) struct Viewport {
+    size: Option<[f32; 2]>,
+    integer_scaling: bool,
+    factor: f32,
+
+    /// Where the upper left corner of the virtual screen is,
+    /// in the same units as the game's coordinates
+    offset: [f32; 2],
+
+    /// The [left, right, top, bottom] bars, when there's a virtual size
+    bars: Option<SpriteBatch>,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self {
+            size: None,
+            integer_scaling: false,
+            factor: 1.0,
+            offset: [0.0, 0.0],
+            bars: None,
+        }
+    }
+}
+
+impl Viewport {
+    pub fn new(actx: &mut AppContext, options: &Options) -> Result<Self> {
+        let size = options
+            .virtual_size
+            .map(|[width, height]| [width as f32, height as f32]);
+        let bars = match size {
+            Some(_) => {
+                let mut bars = actx.new_batch_from_color([0.0, 0.0, 0.0])?;
+                for _ in 0..4 {
+                    bars.add(Instance::builder().build());
+                }
+                Some(bars)
+            }
+            None => None,
+        };
+        Ok(Self {
+            size,
+            integer_scaling: options.integer_scaling,
+            bars,
+            ..Self::default()
+        })
+    }
+
+    /// Updates the viewport for a new window size in physical pixels,
+    /// and returns the scale that should be set on the graphics
+    pub fn resize(&mut self, physical_size: [f32; 2], scale_factor: f32) -> [f32; 2] {
+        let [width, height] = physical_size;
+        self.factor = match self.size {
+            None => scale_factor,
+            Some([vwidth, vheight]) => {
+                let factor = (width / vwidth).min(height / vheight);
+                if self.integer_scaling {
+                    factor.floor().max(1.0)
+                } else {
+                    factor
+                }
+            }
+        };
+        let scale = [width / self.factor, height / self.factor];
+        if let (Some(size), Some(bars)) = (self.size, &mut self.bars) {
+            self.offset = [(scale[0] - size[0]) / 2.0, (scale[1] - size[1]) / 2.0];
+            // with integer scaling, the virtual screen can be larger
+            // than the window, in which case there are no bars
+            let [x1, y1] = [self.offset[0].max(0.0), self.offset[1].max(0.0)];
+            let [x2, y2] = [scale[0] - x1, scale[1] - y1];
+            let rects = [
+                [[0.0, 0.0], [x1, scale[1]]],
+                [[x2, 0.0], [scale[0], scale[1]]],
+                [[x1, 0.0], [x2, y1]],
+                [[x1, y2], [x2, scale[1]]],
+            ];
+            for (i, rect) in rects.iter().enumerate() {
+                bars.get_mut(i).set_dest(*rect);
+            }
+        }
+        scale
+    }
+
+    /// Converts a position in physical pixels to the coordinates
+    /// the game sees
+    pub fn to_game(&self, pos: [f32; 2]) -> [f32; 2] {
+        [
+            pos[0] / self.factor - self.offset[0],
+            pos[1] / self.factor - self.offset[1],
+        ]
+    }
+
+    /// Returns how far everything the game draws is moved,
+    /// so that the virtual screen is centered
+    pub fn offset(&self) -> [f32; 2] {
+        self.offset
+    }
+
+    /// Returns the batch that covers the window outside of the
+    /// virtual screen, drawn on top of the game
+    pub fn bars(&self) -> Option<&SpriteBatch> {
+        self.bars.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Headless;
+    use crate::RenderContext;
+
+    struct Empty;
+
+    impl crate::Game for Empty {
+        fn update(&mut self, _: &mut AppContext) -> Result<()> {
+            Ok(())
+        }
+
+        fn render(&mut self, _: &mut RenderContext) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn viewport(size: [u32; 2], integer_scaling: bool) -> Viewport {
+        let options = Options {
+            virtual_size: Some(size),
+            integer_scaling,
+            ..Options::default()
+        };
+        let mut viewport = None;
+        Headless::new([1, 1], |actx| {
+            viewport = Some(Viewport::new(actx, &options)?);
+            Ok(Empty)
+        })
+        .unwrap();
+        viewport.unwrap()
+    }
+
+    fn bar_rects(viewport: &Viewport) -> Vec<[[f32; 2]; 2]> {
+        let bars = viewport.bars().unwrap();
+        bars.instances().iter().map(Instance::dest).collect()
+    }
+
+    #[test]
+    fn pillarbox_is_centered() {
+        let mut viewport = viewport([320, 180], false);
+        assert_eq!(viewport.resize([800.0, 360.0], 1.0), [400.0, 180.0]);
+        assert_eq!(viewport.offset(), [40.0, 0.0]);
+        assert_eq!(
+            bar_rects(&viewport),
+            [
+                [[0.0, 0.0], [40.0, 180.0]],
+                [[360.0, 0.0], [400.0, 180.0]],
+                [[40.0, 0.0], [360.0, 0.0]],
+                [[40.0, 180.0], [360.0, 180.0]],
+            ]
+        );
+        assert_eq!(viewport.to_game([80.0, 2.0]), [0.0, 1.0]);
+    }
+
+    #[test]
+    fn integer_scaling_letterboxes_both_ways() {
+        let mut viewport = viewport([320, 180], true);
+        assert_eq!(viewport.resize([990.0, 600.0], 1.0), [330.0, 200.0]);
+        assert_eq!(viewport.offset(), [5.0, 10.0]);
+        assert_eq!(
+            bar_rects(&viewport),
+            [
+                [[0.0, 0.0], [5.0, 200.0]],
+                [[325.0, 0.0], [330.0, 200.0]],
+                [[5.0, 0.0], [325.0, 10.0]],
+                [[5.0, 190.0], [325.0, 200.0]],
+            ]
+        );
+        assert_eq!(viewport.to_game([15.0, 30.0]), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn no_virtual_size_means_no_offset() {
+        let mut viewport = Viewport::default();
+        assert_eq!(viewport.resize([800.0, 600.0], 2.0), [400.0, 300.0]);
+        assert_eq!(viewport.offset(), [0.0, 0.0]);
+        assert!(viewport.bars().is_none());
+        assert_eq!(viewport.to_game([100.0, 50.0]), [50.0, 25.0]);
+    }
+}
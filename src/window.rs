@@ -1,25 +1,65 @@
 use crate::a2d::Graphics2D;
+use crate::anyhow::bail;
+use crate::anyhow::Context;
 use crate::anyhow::Result;
+use crate::clock::Clock;
+use crate::context::Backend;
+use crate::cursor::CursorState;
+use crate::event::dispatch;
 use crate::futures::executor::block_on;
+use crate::game::handle_result;
 use crate::gilrs;
+use crate::gilrs::ev::filter::axis_dpad_to_button;
+use crate::gilrs::ev::filter::Filter;
+use crate::gilrs::GamepadId;
 use crate::gilrs::Gilrs;
+use crate::gilrs::GilrsBuilder;
+use crate::rumble::Rumble;
+use crate::rumble::RumbleCommand;
+use crate::timestep::Timestep;
+use crate::viewport::Viewport;
+use crate::winit::dpi::LogicalSize;
+use crate::winit::window::Fullscreen as WinitFullscreen;
+use crate::winit::window::Icon;
+use crate::winit::window::Window as WinitWindow;
 use crate::winit::{
-    event::{ElementState, Event, KeyboardInput, MouseScrollDelta, TouchPhase, WindowEvent},
-    event_loop::{ControlFlow, EventLoop, EventLoopProxy},
+    event::{
+        DeviceEvent, ElementState, Event, KeyboardInput, MouseScrollDelta, TouchPhase, WindowEvent,
+    },
+    event_loop::{ControlFlow, EventLoop, EventLoopClosed, EventLoopProxy},
+    platform::desktop::EventLoopExtDesktop,
     window::WindowBuilder,
 };
+use crate::ActionMap;
 use crate::AppContext;
+use crate::Assets;
+use crate::Audio;
 use crate::Axis;
+use crate::CursorGrab;
 use crate::DeviceId;
 use crate::Game;
 use crate::GamepadButton;
+use crate::GamepadInfo;
+use crate::InputEvent;
+use crate::InputState;
 use crate::Key;
+use crate::KeyInfo;
+use crate::MappingTarget;
+use crate::Modifiers;
 use crate::MouseButton;
 use crate::Options;
+use crate::RawInput;
 use crate::RenderContext;
+use std::collections::HashMap;
+use std::sync::mpsc::Receiver;
+use std::time::Instant;
 
-pub fn run<G: Game, F: FnOnce(&mut AppContext) -> Result<G>>(f: F) -> ! {
-    let window = block_on(Window::new()).unwrap();
+/// Creates a new window and runs the game returned by `f` in it
+///
+/// Returns once the game exits. If the game exited because
+/// of an error (see `Game::on_error`), that error is returned
+pub fn run<G: Game, F: FnOnce(&mut AppContext) -> Result<G>>(f: F) -> Result<()> {
+    let window = block_on(Window::new())?;
     window.run(f)
 }
 
@@ -32,8 +72,12 @@ pub struct Window {
 impl Window {
     pub async fn new() -> Result<Self> {
         let event_loop = EventLoop::with_user_event();
-        let window = WindowBuilder::new().build(&event_loop).unwrap();
-        let graphics = Graphics2D::from_winit_window(&window).await.unwrap();
+        // The window is only shown once the game's WindowOptions
+        // have been applied (see Window::run)
+        let window = WindowBuilder::new()
+            .with_visible(false)
+            .build(&event_loop)?;
+        let graphics = Graphics2D::from_winit_window(&window).await?;
         Ok(Self {
             event_loop,
             window,
@@ -41,191 +85,571 @@ impl Window {
         })
     }
 
-    pub fn run<G: Game, F: FnOnce(&mut AppContext) -> Result<G>>(self, f: F) -> ! {
-        let event_loop = self.event_loop;
+    pub fn run<G: Game, F: FnOnce(&mut AppContext) -> Result<G>>(self, f: F) -> Result<()> {
+        let mut event_loop = self.event_loop;
         let window = self.window;
         let mut graphics = self.graphics;
-        let mut scale_factor: f64 = 1.0;
-        let mut mouse_pos: [f32; 2] = [0.0, 0.0];
+        let mut scale_factor = window.scale_factor();
+        let mut clock = Clock::new(Instant::now());
+        let mut input = InputState::default();
+        let mut actions = ActionMap::default();
+        let mut assets = Assets::default();
+        let mut audio = Audio::new();
+        let mut rumble = Rumble::default();
+        let rumble_commands = rumble.connect();
+        let mut cursor = CursorState::default();
+        let mut viewport = Viewport::default();
+        let mut error: Option<anyhow::Error> = None;
 
         let (mut game, options) = {
             let mut actx = AppContext {
-                graphics: &mut graphics,
+                backend: Backend::Window(&window, &mut graphics),
                 control_flow: &mut ControlFlow::default(),
+                clock: &mut clock,
+                input: &mut input,
+                actions: &mut actions,
+                assets: &mut assets,
+                audio: &mut audio,
+                rumble: &rumble,
+                cursor: &mut cursor,
+                viewport: &mut viewport,
             };
 
-            let size = window.inner_size();
-            actx.set_scale([size.width as f32, size.height as f32]);
+            let size = window.inner_size().to_logical::<f32>(scale_factor);
+            actx.set_scale([size.width, size.height]);
 
-            let game = f(&mut actx).unwrap();
+            let mut game = f(&mut actx)?;
 
             if *actx.control_flow != ControlFlow::default() {
-                panic!(
+                bail!(
                     "Tried to modify control flow on init: {:?}",
                     actx.control_flow
                 );
             }
 
             let options = game.options();
+            actx.input.apply_options(&options);
+            options.window.apply(&window)?;
+            window.set_visible(true);
+
+            *actx.viewport = Viewport::new(&mut actx, &options)?;
+            on_resize(&mut actx, &mut game, scale_factor, window.inner_size())?;
 
             if options.enable_gamepad {
+                let mut mappings = options.gamepad_mappings.join("\n");
+                for path in &options.gamepad_mapping_files {
+                    let text = std::fs::read_to_string(path).with_context(|| {
+                        format!("Failed to read gamepad mappings from {:?}", path)
+                    })?;
+                    mappings.push('\n');
+                    mappings.push_str(&text);
+                }
                 let proxy = event_loop.create_proxy();
-                spawn_gilrs_listener_thread(proxy);
+                spawn_gilrs_listener_thread(proxy, rumble_commands, mappings);
             }
 
+            #[cfg(feature = "hot-reload")]
+            spawn_asset_watcher_thread(actx.assets.watch_list().clone(), event_loop.create_proxy());
+
             (game, options)
         };
 
         let Options {
             enable_gamepad: _,
             scroll_pixel_factor,
+            update_rate,
+            max_updates_per_frame,
+            window: _,
+            virtual_size: _,
+            integer_scaling: _,
+            axis_config: _,
+            gamepad_mappings: _,
+            gamepad_mapping_files: _,
+            custom_gamepad_mappings: _,
+            pause_when_unfocused,
         } = options;
 
-        event_loop.run(move |event, _, control_flow| {
+        let mut timestep = Timestep::new(update_rate, max_updates_per_frame)?;
+        let mut modifiers = Modifiers::default();
+        let mut audio_time = Instant::now();
+
+        event_loop.run_return(|event, _, control_flow| {
             let mut actx = AppContext {
-                graphics: &mut graphics,
+                backend: Backend::Window(&window, &mut graphics),
                 control_flow,
+                clock: &mut clock,
+                input: &mut input,
+                actions: &mut actions,
+                assets: &mut assets,
+                audio: &mut audio,
+                rumble: &rumble,
+                cursor: &mut cursor,
+                viewport: &mut viewport,
             };
-            match event {
+            let result = match event {
                 Event::RedrawRequested(_window_id) => {
-                    let mut rctx = RenderContext { actx: &mut actx };
-                    game.render(&mut rctx).unwrap();
+                    actx.clock.frame(Instant::now());
+                    let mut rctx = RenderContext {
+                        actx: &mut actx,
+                        alpha: timestep.alpha(),
+                    };
+                    game.render(&mut rctx)
                 }
                 Event::MainEventsCleared => {
-                    game.update(&mut actx).unwrap();
+                    let now = Instant::now();
+                    actx.audio
+                        .advance(now.duration_since(audio_time).as_secs_f64());
+                    audio_time = now;
+                    // an audio error is reported in place of this frame's updates
+                    let result = actx.audio.take_error().and_then(|()| {
+                        if pause_when_unfocused && !actx.input.is_focused() {
+                            timestep.pause();
+                            actx.clock.pause();
+                            Ok(())
+                        } else {
+                            let step = timestep.step();
+                            (0..timestep.advance(now)).try_for_each(|_| {
+                                actx.clock.update(now, step);
+                                let result = game.update(&mut actx);
+                                actx.input.clear_transitions();
+                                result
+                            })
+                        }
+                    });
                     window.request_redraw();
+                    result
+                }
+                Event::Suspended => set_suspended(&mut game, &mut actx, true),
+                Event::Resumed => set_suspended(&mut game, &mut actx, false),
+                Event::UserEvent(OtherEvent::GamepadInfo(id, info)) => {
+                    actx.input.set_gamepad_info(id.into(), info);
+                    Ok(())
+                }
+                Event::UserEvent(OtherEvent::GilrsFailed(error)) => {
+                    Err(anyhow::anyhow!("Failed to set up gamepads: {}", error))
+                }
+                Event::UserEvent(OtherEvent::Gilrs(event)) => input_from_gilrs(event, actx.input)
+                    .into_iter()
+                    .try_for_each(|event| dispatch(&mut game, &mut actx, event)),
+                #[cfg(feature = "hot-reload")]
+                Event::UserEvent(OtherEvent::AssetChanged(path)) => {
+                    crate::reload::reload(&mut game, &mut actx, &path)
                 }
-                Event::UserEvent(other) => match other {
-                    OtherEvent::Gilrs(gilrs::Event {
-                        id,
-                        event,
-                        time: _time,
-                    }) => {
-                        let id: DeviceId = id.into();
-                        match event {
-                            gilrs::EventType::ButtonPressed(button, _) => {
-                                let button = GamepadButton::from_gilrs(button).unwrap();
-                                game.gamepad_button_pressed(&mut actx, id, button).unwrap();
-                            }
-                            gilrs::EventType::ButtonReleased(button, _) => {
-                                let button = GamepadButton::from_gilrs(button).unwrap();
-                                game.gamepad_button_released(&mut actx, id, button).unwrap();
-                            }
-                            gilrs::EventType::Connected => {
-                                game.gamepad_connected(&mut actx, id).unwrap();
-                            }
-                            gilrs::EventType::Disconnected => {
-                                game.gamepad_connected(&mut actx, id).unwrap();
-                            }
-                            gilrs::EventType::AxisChanged(axis, value, _) => {
-                                let axis = Axis::from_gilrs(axis);
-                                game.gamepad_axis_changed(&mut actx, id, axis, value)
-                                    .unwrap();
-                            }
-                            gilrs::EventType::Dropped
-                            | gilrs::EventType::ButtonChanged(..)
-                            | gilrs::EventType::ButtonRepeated(..) => {}
-                        }
-                    }
-                },
                 Event::WindowEvent {
                     ref event,
                     window_id,
                 } if window_id == window.id() => match event {
                     WindowEvent::CloseRequested => {
                         actx.exit();
-                    }
-                    WindowEvent::KeyboardInput {
-                        input,
-                        device_id: _,
-                        is_synthetic: _,
-                    } => match input {
-                        KeyboardInput {
-                            state,
-                            virtual_keycode: Some(keycode),
-                            ..
-                        } => {
-                            if let Some(key) = Key::from_winit(*keycode) {
-                                match state {
-                                    ElementState::Pressed => {
-                                        game.key_pressed(&mut actx, key).unwrap();
-                                    }
-                                    ElementState::Released => {
-                                        game.key_released(&mut actx, key).unwrap();
-                                    }
-                                }
-                            }
-                        }
-                        _ => {}
-                    },
-                    WindowEvent::CursorMoved {
-                        device_id: _,
-                        position,
-                        ..
-                    } => {
-                        let position = position.to_logical(scale_factor);
-                        mouse_pos = [position.x, position.y];
-                        game.mouse_moved(&mut actx, mouse_pos).unwrap();
-                    }
-                    WindowEvent::MouseWheel {
-                        device_id: _,
-                        delta,
-                        phase,
-                        ..
-                    } => match phase {
-                        TouchPhase::Moved => {
-                            let dxdy = match delta {
-                                MouseScrollDelta::LineDelta(dx, dy) => [*dx, *dy],
-                                MouseScrollDelta::PixelDelta(
-                                    crate::winit::dpi::LogicalPosition { x, y },
-                                ) => [
-                                    *x as f32 * scroll_pixel_factor,
-                                    *y as f32 * scroll_pixel_factor,
-                                ],
-                            };
-                            game.scroll(&mut actx, mouse_pos, dxdy).unwrap();
-                        }
-                        _ => {}
-                    },
-                    WindowEvent::MouseInput {
-                        device_id: _,
-                        state,
-                        button,
-                        ..
-                    } => {
-                        let button = MouseButton::from_winit(*button);
-                        match state {
-                            ElementState::Pressed => {
-                                game.mouse_button_pressed(&mut actx, mouse_pos, button)
-                                    .unwrap();
-                            }
-                            ElementState::Released => {
-                                game.mouse_button_released(&mut actx, mouse_pos, button)
-                                    .unwrap();
-                            }
-                        }
-                    }
-                    WindowEvent::ReceivedCharacter(ch) => {
-                        game.char(&mut actx, *ch).unwrap();
+                        Ok(())
                     }
                     WindowEvent::Resized(physical_size) => {
-                        on_resize(&mut actx, &mut game, scale_factor, *physical_size).unwrap();
+                        on_resize(&mut actx, &mut game, scale_factor, *physical_size).and_then(
+                            |_| {
+                                // winit has no event for being minimized, but on
+                                // Windows, the window is resized to 0x0
+                                let minimized =
+                                    physical_size.width == 0 || physical_size.height == 0;
+                                set_suspended(&mut game, &mut actx, minimized)
+                            },
+                        )
                     }
                     WindowEvent::ScaleFactorChanged {
                         scale_factor: new_scale_factor,
                         new_inner_size: physical_size,
                     } => {
                         scale_factor = *new_scale_factor;
-                        on_resize(&mut actx, &mut game, scale_factor, **physical_size).unwrap();
+                        on_resize(&mut actx, &mut game, scale_factor, **physical_size)
+                    }
+                    WindowEvent::ModifiersChanged(state) => {
+                        modifiers = Modifiers::from_winit(*state);
+                        Ok(())
+                    }
+                    WindowEvent::Focused(true) => {
+                        dispatch(&mut game, &mut actx, InputEvent::FocusGained)
+                    }
+                    WindowEvent::Focused(false) => {
+                        // not every platform reports the modifiers being
+                        // released when focus is lost
+                        modifiers = Modifiers::default();
+                        dispatch(&mut game, &mut actx, InputEvent::FocusLost)
+                    }
+                    // while locked, the cursor is repeatedly moved back to
+                    // the middle of the window, which isn't interesting
+                    WindowEvent::CursorMoved { .. } if actx.cursor.grab() == CursorGrab::Locked => {
+                        Ok(())
+                    }
+                    event => {
+                        match input_from_winit(event, actx.viewport, modifiers, scroll_pixel_factor)
+                        {
+                            Some(event) => dispatch(&mut game, &mut actx, event),
+                            None => Ok(()),
+                        }
                     }
-                    _ => {}
                 },
-                _ => {}
+                // device events keep coming in while another window has
+                // focus, and the cursor shouldn't be pulled back into the game
+                Event::DeviceEvent {
+                    event: DeviceEvent::MouseMotion { delta: (dx, dy) },
+                    ..
+                } if actx.input.is_focused() => {
+                    let result = dispatch(
+                        &mut game,
+                        &mut actx,
+                        InputEvent::MouseMotion([dx as f32, dy as f32]),
+                    );
+                    actx.cursor.recenter(Some(&window));
+                    result
+                }
+                _ => Ok(()),
+            };
+            if let Err(e) = handle_result(&mut game, &mut actx, result) {
+                // If there's more than one error, we keep the first
+                // one, since the others are likely caused by it
+                if error.is_none() {
+                    error = Some(e);
+                }
             }
         });
+
+        match error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Configuration for the window a game is displayed in
+///
+/// Sizes are in logical pixels.
+///
+/// NOTE, the present mode (i.e. vsync) is currently chosen by a2d
+/// and cannot be configured here
+#[derive(Debug, Clone)]
+pub struct WindowOptions {
+    /// The title of the window
+    ///
+    /// By default, set to "yagl"
+    pub title: String,
+
+    /// The initial [width, height] of the inside of the window
+    ///
+    /// By default, set to None, which lets the platform decide
+    pub size: Option<[u32; 2]>,
+
+    /// The minimum [width, height] the window can be resized to
+    ///
+    /// By default, set to None
+    pub min_size: Option<[u32; 2]>,
+
+    /// The maximum [width, height] the window can be resized to
+    ///
+    /// By default, set to None
+    pub max_size: Option<[u32; 2]>,
+
+    /// Whether the user can resize the window
+    ///
+    /// By default, set to true
+    pub resizable: bool,
+
+    /// Whether the window has a title bar and borders
+    ///
+    /// By default, set to true
+    pub decorations: bool,
+
+    /// Whether the window should always be shown above other windows
+    ///
+    /// By default, set to false
+    pub always_on_top: bool,
+
+    /// If set, the window starts out in the given fullscreen mode
+    ///
+    /// This can be changed later with `AppContext::set_fullscreen`
+    ///
+    /// By default, set to None
+    pub fullscreen: Option<Fullscreen>,
+
+    /// The bytes of an image file to use as the window icon
+    ///
+    /// Like `AppContext::new_sheet_from_bytes`, the data is parsed
+    /// with the `load_from_memory` function in the `image` crate.
+    ///
+    /// By default, set to None
+    pub icon: Option<Vec<u8>>,
+}
+
+impl Default for WindowOptions {
+    fn default() -> Self {
+        Self {
+            title: "yagl".to_owned(),
+            size: None,
+            min_size: None,
+            max_size: None,
+            resizable: true,
+            decorations: true,
+            always_on_top: false,
+            fullscreen: None,
+            icon: None,
+        }
+    }
+}
+
+impl WindowOptions {
+    fn apply(&self, window: &WinitWindow) -> Result<()> {
+        window.set_title(&self.title);
+        if let Some([width, height]) = self.size {
+            window.set_inner_size(LogicalSize::new(width, height));
+        }
+        window.set_min_inner_size(self.min_size.map(|[w, h]| LogicalSize::new(w, h)));
+        window.set_max_inner_size(self.max_size.map(|[w, h]| LogicalSize::new(w, h)));
+        window.set_resizable(self.resizable);
+        window.set_decorations(self.decorations);
+        window.set_always_on_top(self.always_on_top);
+        if let Some(bytes) = &self.icon {
+            let image = crate::image::load_from_memory(bytes)?.to_rgba8();
+            let (width, height) = image.dimensions();
+            window.set_window_icon(Some(Icon::from_rgba(image.into_raw(), width, height)?));
+        }
+        window.set_fullscreen(self.fullscreen.and_then(|f| f.to_winit(window)));
+        Ok(())
+    }
+}
+
+/// The ways a window can cover the whole screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Fullscreen {
+    /// The window is resized to cover the monitor it's on,
+    /// without changing the monitor's video mode
+    Borderless,
+
+    /// The monitor the window is on is switched to its
+    /// highest resolution video mode, which the window then
+    /// has exclusive use of
+    Exclusive,
+}
+
+impl Fullscreen {
+    pub(crate) fn to_winit(self, window: &WinitWindow) -> Option<WinitFullscreen> {
+        let monitor = window.current_monitor();
+        match self {
+            Fullscreen::Borderless => Some(WinitFullscreen::Borderless(monitor)),
+            Fullscreen::Exclusive => monitor
+                .video_modes()
+                .max_by_key(|mode| {
+                    let size = mode.size();
+                    (size.width * size.height, mode.refresh_rate())
+                })
+                .map(WinitFullscreen::Exclusive),
+        }
+    }
+
+    pub(crate) fn from_winit(fullscreen: Option<WinitFullscreen>) -> Option<Fullscreen> {
+        match fullscreen? {
+            WinitFullscreen::Borderless(_) => Some(Fullscreen::Borderless),
+            WinitFullscreen::Exclusive(_) => Some(Fullscreen::Exclusive),
+        }
     }
 }
 
+/// Converts a gilrs event into the equivalent InputEvents
+///
+/// Raw input is first offered to the mapping recorder, and then
+/// translated with the gamepad's custom mapping, if it has one
+fn input_from_gilrs(event: gilrs::Event, input: &mut InputState) -> Vec<InputEvent> {
+    let gilrs::Event {
+        id,
+        event,
+        time: _time,
+    } = event;
+    let id: DeviceId = id.into();
+    if let Some((raw, value)) = raw_from_gilrs(event) {
+        if input.record_raw(id, raw, value) {
+            return Vec::new();
+        }
+        if let Some(target) = input.remap(id, raw) {
+            return remap_gilrs(input, id, event, target);
+        }
+    }
+    input_from_gilrs_mapped(id, event).into_iter().collect()
+}
+
+/// Returns the raw input a gilrs event came from, along with its value
+fn raw_from_gilrs(event: gilrs::EventType) -> Option<(RawInput, f32)> {
+    match event {
+        gilrs::EventType::ButtonPressed(_, code) => Some((RawInput::Button(code.into_u32()), 1.0)),
+        gilrs::EventType::ButtonReleased(_, code) => Some((RawInput::Button(code.into_u32()), 0.0)),
+        gilrs::EventType::ButtonChanged(_, value, code) => {
+            Some((RawInput::Button(code.into_u32()), value))
+        }
+        gilrs::EventType::AxisChanged(_, value, code) => {
+            Some((RawInput::Axis(code.into_u32()), value))
+        }
+        _ => None,
+    }
+}
+
+/// Converts a gilrs event into InputEvents for the button or axis
+/// a custom mapping says it should be
+fn remap_gilrs(
+    input: &InputState,
+    id: DeviceId,
+    event: gilrs::EventType,
+    target: MappingTarget,
+) -> Vec<InputEvent> {
+    match (target, event) {
+        (MappingTarget::Button(button), gilrs::EventType::ButtonPressed(..)) => {
+            vec![InputEvent::GamepadButtonPressed(id, button)]
+        }
+        (MappingTarget::Button(button), gilrs::EventType::ButtonReleased(..)) => {
+            vec![InputEvent::GamepadButtonReleased(id, button)]
+        }
+        (MappingTarget::Button(button), gilrs::EventType::ButtonChanged(_, value, _)) => {
+            vec![InputEvent::GamepadButtonChanged(id, button, value)]
+        }
+        (MappingTarget::Button(button), gilrs::EventType::AxisChanged(_, value, _)) => {
+            // axes used as buttons are assumed to rest at -1, like triggers,
+            // and are pressed and released at the same points gilrs uses
+            let value = ((value + 1.0) / 2.0).clamp(0.0, 1.0);
+            let mut events = vec![InputEvent::GamepadButtonChanged(id, button, value)];
+            let down = input.is_gamepad_button_down(id, button);
+            if !down && value >= 0.75 {
+                events.push(InputEvent::GamepadButtonPressed(id, button));
+            } else if down && value <= 0.65 {
+                events.push(InputEvent::GamepadButtonReleased(id, button));
+            }
+            events
+        }
+        (MappingTarget::Axis(axis), gilrs::EventType::AxisChanged(_, value, _)) => {
+            vec![InputEvent::GamepadAxisChanged(id, axis, value)]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Converts a gilrs event into the equivalent InputEvent, if there is one,
+/// using the buttons and axes gilrs assigned to it
+fn input_from_gilrs_mapped(id: DeviceId, event: gilrs::EventType) -> Option<InputEvent> {
+    match event {
+        gilrs::EventType::ButtonPressed(button, _) => {
+            let button = GamepadButton::from_gilrs(button).unwrap();
+            Some(InputEvent::GamepadButtonPressed(id, button))
+        }
+        gilrs::EventType::ButtonReleased(button, _) => {
+            let button = GamepadButton::from_gilrs(button).unwrap();
+            Some(InputEvent::GamepadButtonReleased(id, button))
+        }
+        gilrs::EventType::Connected => Some(InputEvent::GamepadConnected(id)),
+        gilrs::EventType::Disconnected => Some(InputEvent::GamepadDisconnected(id)),
+        gilrs::EventType::AxisChanged(axis, value, _) => {
+            let axis = Axis::from_gilrs(axis);
+            Some(InputEvent::GamepadAxisChanged(id, axis, value))
+        }
+        gilrs::EventType::ButtonChanged(button, value, _) => {
+            let button = GamepadButton::from_gilrs(button)?;
+            Some(InputEvent::GamepadButtonChanged(id, button, value))
+        }
+        // only gilrs's filters drop events, and the one used here never does
+        gilrs::EventType::Dropped => None,
+        gilrs::EventType::ButtonRepeated(..) => None,
+    }
+}
+
+/// Converts a winit window event into the equivalent InputEvent, if there is one
+fn input_from_winit(
+    event: &WindowEvent,
+    viewport: &Viewport,
+    modifiers: Modifiers,
+    scroll_pixel_factor: f32,
+) -> Option<InputEvent> {
+    match event {
+        WindowEvent::KeyboardInput {
+            input:
+                KeyboardInput {
+                    state,
+                    virtual_keycode,
+                    scancode,
+                    ..
+                },
+            device_id: _,
+            is_synthetic: false,
+        } => {
+            let info = KeyInfo {
+                scancode: *scancode,
+                modifiers,
+                is_repeat: false,
+            };
+            match (virtual_keycode.and_then(Key::from_winit), state) {
+                (Some(key), ElementState::Pressed) => Some(InputEvent::KeyPressed(key, info)),
+                (Some(key), ElementState::Released) => Some(InputEvent::KeyReleased(key, info)),
+                (None, ElementState::Pressed) => Some(InputEvent::KeyScancodePressed(info)),
+                (None, ElementState::Released) => Some(InputEvent::KeyScancodeReleased(info)),
+            }
+        }
+        WindowEvent::CursorMoved {
+            device_id: _,
+            position,
+            ..
+        } => {
+            let pos = viewport.to_game([position.x as f32, position.y as f32]);
+            Some(InputEvent::MouseMoved(pos))
+        }
+        WindowEvent::MouseWheel {
+            device_id: _,
+            delta,
+            phase: TouchPhase::Moved,
+            ..
+        } => {
+            let dxdy = match delta {
+                MouseScrollDelta::LineDelta(dx, dy) => [*dx, *dy],
+                MouseScrollDelta::PixelDelta(crate::winit::dpi::LogicalPosition { x, y }) => [
+                    *x as f32 * scroll_pixel_factor,
+                    *y as f32 * scroll_pixel_factor,
+                ],
+            };
+            Some(InputEvent::Scroll(dxdy))
+        }
+        WindowEvent::MouseInput {
+            device_id: _,
+            state,
+            button,
+            ..
+        } => {
+            let button = MouseButton::from_winit(*button);
+            match state {
+                ElementState::Pressed => Some(InputEvent::MouseButtonPressed(button)),
+                ElementState::Released => Some(InputEvent::MouseButtonReleased(button)),
+            }
+        }
+        WindowEvent::ReceivedCharacter(ch) => Some(InputEvent::Char(*ch)),
+        WindowEvent::CursorEntered { device_id: _ } => Some(InputEvent::CursorEntered),
+        WindowEvent::CursorLeft { device_id: _ } => Some(InputEvent::CursorLeft),
+        // Some platforms (e.g. X11) make up key events for keys that are
+        // held down when focus changes. Releasing held keys is already done
+        // on focus loss (see `Game::focus_lost`), and making up presses on
+        // focus gain would only happen on some platforms, so these are ignored
+        WindowEvent::KeyboardInput {
+            is_synthetic: true, ..
+        } => None,
+        _ => None,
+    }
+}
+
+/// Reports the game being suspended or resumed, unless it already is
+///
+/// Both winit's own suspend events and minimizing the window
+/// end up here, so this keeps them from being reported twice
+fn set_suspended<G: Game>(game: &mut G, actx: &mut AppContext, suspended: bool) -> Result<()> {
+    if actx.input.is_suspended() == suspended {
+        return Ok(());
+    }
+    let event = if suspended {
+        InputEvent::Suspended
+    } else {
+        InputEvent::Resumed
+    };
+    dispatch(game, actx, event)
+}
+
 fn on_resize<G: Game>(
     actx: &mut AppContext,
     game: &mut G,
@@ -234,24 +658,119 @@ fn on_resize<G: Game>(
 ) -> Result<()> {
     let logical_size = physical_size.to_logical(scale_factor);
     let (width, height) = (logical_size.width, logical_size.height);
-    actx.graphics.resized(physical_size);
-    actx.graphics.set_scale([width as f32, height as f32]);
+    actx.graphics()?.resized(physical_size);
+    let scale = actx.viewport.resize(
+        [physical_size.width as f32, physical_size.height as f32],
+        scale_factor as f32,
+    );
+    actx.set_scale(scale);
     game.resize(actx, width, height)
 }
 
-fn spawn_gilrs_listener_thread(proxy: EventLoopProxy<OtherEvent>) {
+fn spawn_gilrs_listener_thread(
+    proxy: EventLoopProxy<OtherEvent>,
+    rumble_commands: Receiver<RumbleCommand>,
+    mappings: String,
+) {
     std::thread::spawn(move || {
-        let mut gilrs = Gilrs::new().unwrap();
-        loop {
-            while let Some(event) = gilrs.next_event() {
-                proxy.send_event(OtherEvent::Gilrs(event)).unwrap();
+        // the event loop is gone once `run` returns, and with it,
+        // any reason to keep listening
+        let _ = listen_to_gilrs(&proxy, &rumble_commands, &mappings);
+    });
+}
+
+/// Returns the next gamepad event, with d-pad axes turned into buttons
+fn next_gilrs_event(gilrs: &mut Gilrs) -> Option<gilrs::Event> {
+    let event = gilrs.next_event().filter_ev(&axis_dpad_to_button, gilrs)?;
+    gilrs.update(&event);
+    Some(event)
+}
+
+/// Forwards gamepad events to the event loop, until it closes
+fn listen_to_gilrs(
+    proxy: &EventLoopProxy<OtherEvent>,
+    rumble_commands: &Receiver<RumbleCommand>,
+    mappings: &str,
+) -> Result<(), EventLoopClosed<OtherEvent>> {
+    // gilrs's default filters include a deadzone, which would be applied
+    // on top of `AxisConfig`, so only the filter that turns d-pad axes
+    // into buttons is kept. State is updated by hand after filtering,
+    // since that filter looks at it to tell which d-pad button to release.
+    let gilrs = GilrsBuilder::new()
+        .add_mappings(mappings)
+        .with_default_filters(false)
+        .set_update_state(false)
+        .build();
+    let mut gilrs = match gilrs {
+        Ok(gilrs) => gilrs,
+        // gamepads aren't supported on this platform,
+        // which works the same as none being connected
+        Err(gilrs::Error::NotImplemented(gilrs)) => gilrs,
+        Err(error) => {
+            return proxy.send_event(OtherEvent::GilrsFailed(error.to_string()));
+        }
+    };
+    let mut effects = HashMap::new();
+    let mut infos = HashMap::new();
+    let send_infos = |gilrs: &Gilrs, infos: &mut HashMap<_, _>| {
+        for (id, info) in crate::input::poll_info(gilrs, infos) {
+            proxy.send_event(OtherEvent::GamepadInfo(id, info))?;
+        }
+        Ok(())
+    };
+    send_infos(&gilrs, &mut infos)?;
+    // gilrs only reports gamepads that connect after it starts
+    for (id, _) in gilrs.gamepads() {
+        let event = gilrs::Event::new(id, gilrs::EventType::Connected);
+        proxy.send_event(OtherEvent::Gilrs(event))?;
+    }
+    for tick in 0u64.. {
+        while let Ok(command) = rumble_commands.try_recv() {
+            crate::rumble::apply(&mut gilrs, &mut effects, command);
+        }
+        while let Some(event) = next_gilrs_event(&mut gilrs) {
+            // info is sent ahead of the connect event,
+            // so that it can be queried while handling it
+            if let gilrs::EventType::Connected | gilrs::EventType::Disconnected = event.event {
+                send_infos(&gilrs, &mut infos)?;
+            }
+            proxy.send_event(OtherEvent::Gilrs(event))?;
+        }
+        // about once a second, to pick up changes in power info
+        if tick % 45 == 0 {
+            send_infos(&gilrs, &mut infos)?;
+        }
+        std::thread::sleep(std::time::Duration::from_secs_f64(1.0 / 45.0));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "hot-reload")]
+fn spawn_asset_watcher_thread(
+    watch_list: crate::reload::WatchList,
+    proxy: EventLoopProxy<OtherEvent>,
+) {
+    std::thread::spawn(move || loop {
+        for path in watch_list.changed() {
+            if proxy.send_event(OtherEvent::AssetChanged(path)).is_err() {
+                return;
             }
-            std::thread::sleep(std::time::Duration::from_secs_f64(1.0 / 45.0));
         }
+        std::thread::sleep(std::time::Duration::from_millis(250));
     });
 }
 
 #[derive(Debug, Clone)]
 enum OtherEvent {
     Gilrs(gilrs::Event),
+
+    /// Sent by the gilrs thread whenever a gamepad's info changes,
+    /// with None once it is disconnected
+    GamepadInfo(GamepadId, Option<GamepadInfo>),
+
+    /// Sent by the gilrs thread if it couldn't start, right before it exits
+    GilrsFailed(String),
+
+    #[cfg(feature = "hot-reload")]
+    AssetChanged(String),
 }